@@ -1,12 +1,47 @@
-use clap::Parser;
-use std::{borrow::Cow, path::PathBuf};
+use clap::{Parser, Subcommand};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
 
-use crate::{SpedError, SpedResult};
+use crate::{
+    Codificacao, FormatoNumerico, ItemRepresentativo, ModoDeSaida, PerfilDeInjecao, SpedError,
+    SpedResult, expandir_entradas, ler_mapa_de_colunas, ler_perfil_de_injecao,
+};
+
+/// Nome padrão do arquivo de relações CTe -> NFes.
+pub const ARQUIVO_CTE_NFES: &str = "cte_nfes.txt";
+
+/// Nome padrão do arquivo de chaves complementares dos CTes.
+pub const ARQUIVO_COMPLEMENTARES: &str =
+    "transporte_subcontratado-chaves_complementares_dos_CTes.txt";
+
+/// Nome padrão do arquivo de relações MDFe -> CTes.
+pub const ARQUIVO_MDFE_CTES: &str = "mdfe_ctes.txt";
+
+/// Número padrão de linhas lidas por bloco nas passagens 1 e 2.
+pub const LINHAS_POR_BLOCO: usize = 32 * 1024;
+
+/// Subcomandos: relatórios gerados em lugar do arquivo enriquecido.
+#[derive(Subcommand, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comando {
+    /// Relatório de elegibilidade do crédito de PIS/COFINS sobre fretes.
+    ///
+    /// Classifica cada CT-e do arquivo (crédito permitido, duvidoso ou não permitido)
+    /// pela natureza da base de cálculo, CST, papel do tomador e entrada/saída,
+    /// com os CSTs e CFOPs das NF-es transportadas. Grava `<doc>.creditos_frete.csv`
+    /// com a regra que definiu a classe de cada CT-e.
+    CreditosFrete,
+}
 
 // Estrutura para o Clap processar os argumentos da linha de comando
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_precedence_over_arg = true)]
 struct Arguments {
+    #[command(subcommand)]
+    comando: Option<Comando>,
+
     /// Atualizar arquivo CSV original
     #[arg(short, long, default_value_t = false)]
     atualizar_origem: bool,
@@ -15,18 +50,136 @@ struct Arguments {
     #[arg(short, long, default_value_t = false)]
     clear: bool,
 
-    /// Arquivo de Documentos Fiscais.
+    /// Arquivo(s) de Documentos Fiscais: arquivos, diretórios ou padrões glob.
     ///
     /// Exemplo de arquivo esperado:
     ///
     /// - `ZZZ-874918-Info da Receita sobre o Contribuinte.csv`
-    #[arg(short, long, required = true)]
-    doc_path: Option<PathBuf>,
+    ///
+    /// Modo lote: `-d a.csv b.csv`, `-d dados/` ou `-d 'dados/ZZZ-*.csv'`.
+    /// As tabelas de relacionamento são carregadas uma única vez.
+    #[arg(short, long, required = true, num_args = 1..)]
+    doc_path: Vec<PathBuf>,
+
+    /// Arquivo de relações CTe -> NFes.
+    ///
+    /// Se omitido, procura `cte_nfes.txt` no diretório do arquivo `--doc-path`
+    /// e, em seguida, no diretório corrente.
+    #[arg(long, value_name = "ARQUIVO")]
+    cte_nfes: Option<PathBuf>,
+
+    /// Arquivo de chaves complementares dos CTes (opcional).
+    ///
+    /// Se omitido, procura `transporte_subcontratado-chaves_complementares_dos_CTes.txt`
+    /// no diretório do arquivo `--doc-path` e, em seguida, no diretório corrente.
+    /// Na ausência deste arquivo, a transitividade entre CTes complementares é ignorada.
+    #[arg(long, value_name = "ARQUIVO")]
+    complementares: Option<PathBuf>,
+
+    /// Arquivo de relações MDFe -> CTes (opcional).
+    ///
+    /// Se omitido, procura `mdfe_ctes.txt` no diretório do arquivo `--doc-path`
+    /// e, em seguida, no diretório corrente.
+    #[arg(long, value_name = "ARQUIVO")]
+    mdfe_ctes: Option<PathBuf>,
+
+    /// Arquivo(s) CSV de referência: arquivos, diretórios ou padrões glob.
+    ///
+    /// Com o mesmo layout do arquivo principal, apenas alimentam os resumos
+    /// das chaves ausentes no arquivo principal (ex: NF-es de outro estabelecimento
+    /// do mesmo grupo). Uma chave presente em mais de um arquivo de referência
+    /// é resumida apenas do primeiro. Os arquivos de referência nunca são reescritos.
+    #[arg(long, value_name = "ARQUIVO", num_args = 1..)]
+    referencia: Vec<PathBuf>,
+
+    /// Arquivo TOML com nomes alternativos (aliases) das colunas do CSV.
+    ///
+    /// Exemplo:
+    ///
+    /// [colunas]
+    ///
+    /// "Código NCM : NF Item (Todos)" = ["NCM", "Código NCM"]
+    #[arg(long, value_name = "ARQUIVO")]
+    mapa_colunas: Option<PathBuf>,
+
+    /// Arquivo TOML do perfil de injeção: colunas dos documentos relacionados
+    /// injetadas em cada coluna, por direção (CT-e -> NF-e e NF-e -> CT-e).
+    ///
+    /// Exemplo:
+    ///
+    /// [nfe_para_cte]
+    ///
+    /// max_info = 3
+    ///
+    /// colunas = [{ origem = "Código NCM : NF Item (Todos)", modo = "sobrescrever" }]
+    ///
+    /// Sem este arquivo, é usado o perfil padrão (16 colunas do CT-e e 10 da NF-e).
+    #[arg(long, value_name = "ARQUIVO")]
+    perfil_injecao: Option<PathBuf>,
+
+    /// Onde gravar as informações dos documentos relacionados.
+    ///
+    /// `colunas`: novas colunas ao final do cabeçalho (ex: "CT-es relacionados",
+    /// "UF início (CT-e)", "NCM (NF-e)"), sem alterar as colunas originais.
+    #[arg(long, value_enum, default_value_t = ModoDeSaida::Anotacoes)]
+    modo_saida: ModoDeSaida,
+
+    /// Separador dos valores de uma mesma célula em `--modo-saida colunas`
+    #[arg(long, default_value = " | ", value_parser = clap::builder::NonEmptyStringValueParser::new())]
+    separador: String,
+
+    /// Codificação dos arquivos CSV de entrada.
+    ///
+    /// `auto`: UTF-8 (com ou sem BOM) ou, se inválido, Windows-1252.
+    #[arg(long, value_enum, default_value_t = Codificacao::Auto)]
+    encoding: Codificacao,
+
+    /// Codificação do arquivo gravado (padrão: a mesma do arquivo de entrada)
+    #[arg(long, value_enum)]
+    encoding_saida: Option<Codificacao>,
+
+    /// Formato dos valores numéricos do CSV.
+    ///
+    /// `auto`: detectado pelo próprio valor; valores ambíguos (ex: `1.234`)
+    /// são lidos no formato brasileiro e relatados na Passagem 1.
+    #[arg(long, value_enum, default_value_t = FormatoNumerico::Auto)]
+    formato_numerico: FormatoNumerico,
+
+    /// Item cujos metadados representam o documento (descrição, NCM, natureza etc.).
+    ///
+    /// `cfop-ncm`: agrupa os itens por CFOP/NCM e escolhe o grupo de maior
+    /// valor somado (útil para CT-es com muitos componentes).
+    #[arg(long, value_enum, default_value_t = ItemRepresentativo::MaiorValor)]
+    item_representativo: ItemRepresentativo,
 
     /// Imprimir configuração
     #[arg(short, long, default_value_t = false)]
     exibir_config: bool,
 
+    /// Número de threads (0: todos os núcleos disponíveis).
+    ///
+    /// O resultado é idêntico para qualquer número de threads.
+    #[arg(short, long, default_value_t = 0)]
+    threads: usize,
+
+    /// Número de linhas lidas e processadas em paralelo por bloco.
+    ///
+    /// Limita a memória ocupada pelas linhas em processamento.
+    #[arg(long, default_value_t = LINHAS_POR_BLOCO, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    chunk_size: usize,
+
+    /// Número máximo de arquivos processados simultaneamente no modo lote
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u16).range(1..))]
+    jobs: u16,
+
+    /// Exportar relações e resumos em JSON Lines (`<doc>.relacoes.jsonl`)
+    #[arg(short, long, default_value_t = false)]
+    json: bool,
+
+    /// Remover as anotações "[Info d..]" de execuções anteriores (restaura o arquivo)
+    #[arg(short, long, default_value_t = false)]
+    limpar: bool,
+
     /// Máximo de caracteres por coluna
     #[arg(long, default_value_t = 3000)]
     max_char: usize,
@@ -35,6 +188,65 @@ struct Arguments {
     #[arg(long, default_value_t = 10)]
     max_info: usize,
 
+    /// Máximo de valores distintos de NCM, CFOP, CST e natureza por documento (0: desativado).
+    ///
+    /// Os valores de todos os itens são informados com o percentual do valor
+    /// do documento (ex: "NCM 1234 (70%), 5678 (30%)"); os excedentes somam em "outros".
+    #[arg(long, default_value_t = 5)]
+    max_valores_distintos: usize,
+
+    /// Ratear o frete dos CT-es entre os itens das NF-es relacionadas.
+    ///
+    /// Acrescenta a coluna "Frete rateado (CT-e)" ao final do arquivo: o valor de
+    /// cada CT-e é rateado entre as suas NF-es pelo valor total de cada NF-e e,
+    /// em seguida, entre os itens de cada NF-e pelo valor do item (em centavos,
+    /// sem perda no arredondamento).
+    #[arg(long, default_value_t = false)]
+    rateio_frete: bool,
+
+    /// Acrescentar às NF-es os tributos dos CT-es relacionados.
+    ///
+    /// Colunas numéricas ao final do arquivo, preenchidas nas linhas de item de NF-e
+    /// ("PIS rateado (CT-es)", etc.): PIS, COFINS, base de cálculo e valor do ICMS
+    /// dos CT-es relacionados, rateados como o frete (`--rateio-frete`), e a alíquota
+    /// efetiva do ICMS. A exportação JSON e o relatório trazem a parcela de cada NF-e.
+    #[arg(long, default_value_t = false)]
+    tributos_cte: bool,
+
+    /// Classificar a modalidade do frete (CIF, FOB ou terceiros) dos CT-es.
+    ///
+    /// Acrescenta a coluna "Modalidade do frete (CT-e)" ao final do arquivo,
+    /// preenchida nas linhas de CT-e e nas NF-es relacionadas: o documento do
+    /// tomador é comparado com os do remetente e do destinatário, indicando
+    /// também se o contribuinte é o tomador.
+    #[arg(long, default_value_t = false)]
+    modalidade_frete: bool,
+
+    /// Verificar a consistência entre os CT-es e as NF-es relacionadas.
+    ///
+    /// Grava as inconsistências em `<doc>.consistencia.csv`: remetente do CT-e
+    /// diferente do emitente da NF-e, destinatários divergentes, CT-e emitido
+    /// antes da NF-e e UF do emitente da NF-e fora do percurso do CT-e.
+    #[arg(long, default_value_t = false)]
+    consistencia: bool,
+
+    /// Acrescentar a coluna "Inconsistências CT-e/NF-e" às linhas de CT-e e NF-e
+    /// (implica `--consistencia`)
+    #[arg(long, default_value_t = false)]
+    coluna_consistencia: bool,
+
+    /// Ignorar o cache binário das tabelas de relacionamento e reconstruí-lo
+    #[arg(long, default_value_t = false)]
+    rebuild_cache: bool,
+
+    /// Simulação: executa as duas passagens sem gravar o CSV, apenas o relatório
+    #[arg(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Número de chaves com mais documentos relacionados exibidas no relatório (--dry-run)
+    #[arg(long, default_value_t = 10)]
+    top: usize,
+
     /// Não perguntar se deseja sobrescrever o original (apenas gera o arquivo modificado)
     #[arg(short, long, default_value_t = false)]
     no_prompt: bool,
@@ -44,30 +256,99 @@ struct Arguments {
     verbose: bool,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Config {
+    /// Subcomando (`None`: enriquecimento do arquivo).
+    pub comando: Option<Comando>,
     pub atualizar_origem: bool,
     pub clear: bool,
+    /// Arquivo em processamento.
     pub doc_path: PathBuf,
+    /// Todos os arquivos do lote (entradas de `--doc-path` expandidas).
+    pub doc_paths: Vec<PathBuf>,
+    pub cte_nfes_path: PathBuf,
+    pub complementares_path: Option<PathBuf>,
+    pub mdfe_ctes_path: Option<PathBuf>,
+    pub referencias: Vec<PathBuf>,
+    /// Aliases por nome padrão de coluna (ver `--mapa-colunas`).
+    pub aliases_de_colunas: BTreeMap<String, Vec<String>>,
+    /// Colunas injetadas por direção (ver `--perfil-injecao`).
+    pub perfil: PerfilDeInjecao,
+    /// Anotações ou colunas dedicadas (`--modo-saida`).
+    pub modo_saida: ModoDeSaida,
+    /// Separador dos valores de uma célula das colunas dedicadas (`--separador`).
+    pub separador: String,
+    /// Codificação de entrada (`--encoding`).
+    pub codificacao: Codificacao,
+    /// Codificação de saída (`--encoding-saida`); `None`: a mesma da entrada.
+    pub codificacao_saida: Option<Codificacao>,
+    /// Formato dos valores numéricos (`--formato-numerico`).
+    pub formato_numerico: FormatoNumerico,
+    /// Critério do item representativo (`--item-representativo`).
+    pub item_representativo: ItemRepresentativo,
+    pub rebuild_cache: bool,
+    pub dry_run: bool,
     pub exibir_config: bool,
+    pub top: usize,
+    pub jobs: usize,
+    pub threads: usize,
+    pub chunk_size: usize,
+    pub json: bool,
+    pub limpar: bool,
     pub max_char: usize,
     pub max_info: usize,
+    pub max_valores_distintos: usize,
+    /// Coluna do frete rateado por item de NF-e (`--rateio-frete`).
+    pub rateio_frete: bool,
+    /// Colunas dos tributos dos CT-es nas linhas de NF-e (`--tributos-cte`).
+    pub tributos_cte: bool,
+    /// Coluna da modalidade do frete nas linhas de CT-e e NF-e (`--modalidade-frete`).
+    pub modalidade_frete: bool,
+    /// Diagnóstico de consistência entre CT-es e NF-es (`--consistencia`).
+    pub consistencia: bool,
+    /// Coluna das inconsistências nas linhas de CT-e e NF-e (`--coluna-consistencia`).
+    pub coluna_consistencia: bool,
     pub no_prompt: bool,
     pub verbose: bool,
 }
 
 impl Config {
+    /// Número de linhas por bloco (`--chunk-size`); `LINHAS_POR_BLOCO` se não definido.
+    pub fn linhas_por_bloco(&self) -> usize {
+        if self.chunk_size == 0 {
+            LINHAS_POR_BLOCO
+        } else {
+            self.chunk_size
+        }
+    }
+
+    /// Indica se colunas são acrescentadas ao final das linhas: colunas dedicadas
+    /// (`--modo-saida colunas`), `--tributos-cte`, `--rateio-frete`,
+    /// `--modalidade-frete` ou `--coluna-consistencia`.
+    ///
+    /// Nesse caso, as linhas já enriquecidas também são candidatas à Passagem 2:
+    /// não são anotadas novamente, mas recebem os valores das colunas acrescentadas.
+    pub fn acrescenta_colunas(&self) -> bool {
+        self.modo_saida == ModoDeSaida::Colunas
+            || self.tributos_cte
+            || self.rateio_frete
+            || self.modalidade_frete
+            || self.coluna_consistencia
+    }
+
     /// Adiciona informações a um campo de texto respeitando o limite de caracteres.
     ///
     /// - `field`: Referência mutável para a coluna que receberá o texto.
     /// - `value`: O dado a ser injetado (ignora se estiver vazio).
     /// - `label`: O prefixo da informação (ex: "CT-e" ou "NF-e").
+    ///
+    /// Retorna `false` se o texto foi descartado por exceder `max_char`.
     #[inline]
-    pub fn append<'a>(&self, field: &mut Cow<'a, str>, value: &str, label: &str) {
+    pub fn append<'a>(&self, field: &mut Cow<'a, str>, value: &str, label: &str) -> bool {
         // Otimização: se o valor de origem for vazio, não há o que adicionar
         let value = value.trim();
         if value.is_empty() {
-            return;
+            return true;
         }
 
         // Definimos o artigo ("da" para NF-e, "do" para o restante)
@@ -84,25 +365,151 @@ impl Config {
         if tamanho_atual + tamanho_sufixo < self.max_char {
             // Se o campo for Borrowed, to_mut() faz o clone para String apenas aqui
             field.to_mut().push_str(&sufixo);
+            true
+        } else {
+            false
         }
     }
 }
 
 pub fn get_config() -> SpedResult<Config> {
-    let args = Arguments::parse();
+    configurar(Arguments::parse())
+}
+
+/// Valida os argumentos e localiza os arquivos de relacionamento.
+fn configurar(args: Arguments) -> SpedResult<Config> {
+    if args.limpar && args.comando.is_some() {
+        return Err(SpedError::Config(
+            "a opção --limpar não se aplica aos subcomandos".to_string(),
+        ));
+    }
+
+    // 1. Expansão das entradas (arquivos, diretórios e padrões glob)
+    // Como o Clap já exige 'required = true', a lista vazia só ocorreria em casos extremos.
+    let doc_paths = expandir_entradas(&args.doc_path)?;
+    let doc_path = doc_paths
+        .first()
+        .cloned()
+        .ok_or(SpedError::EfdFileNotFound)?;
+
+    // 2. Arquivos de relacionamento: caminho explícito ou descoberta automática
+    // (no modo lote, a partir do diretório do primeiro arquivo).
+    // No modo --limpar as relações não são utilizadas.
+    let cte_nfes_path = match args.cte_nfes {
+        _ if args.limpar => PathBuf::new(),
+        Some(path) => validar_arquivo(path, "CTe -> NFes", "cte-nfes")?,
+        None => localizar_arquivo(&doc_path, ARQUIVO_CTE_NFES).ok_or_else(|| {
+            SpedError::RelationFileNotFound {
+                tipo: "CTe -> NFes",
+                opcao: "cte-nfes",
+                arquivo: PathBuf::from(ARQUIVO_CTE_NFES),
+            }
+        })?,
+    };
+
+    // O arquivo de complementares é opcional: apenas o caminho explícito gera erro.
+    let complementares_path = match args.complementares {
+        Some(path) => Some(validar_arquivo(
+            path,
+            "CTe <-> CTe Complementar",
+            "complementares",
+        )?),
+        None => localizar_arquivo(&doc_path, ARQUIVO_COMPLEMENTARES),
+    };
+
+    let mdfe_ctes_path = match args.mdfe_ctes {
+        Some(path) => Some(validar_arquivo(path, "MDFe -> CTes", "mdfe-ctes")?),
+        None => localizar_arquivo(&doc_path, ARQUIVO_MDFE_CTES),
+    };
 
-    // 1. Extração funcional: Converte Option<PathBuf> em PathBuf ou retorna erro
-    // Como o Clap já exige 'required = true', este erro só ocorreria em casos extremos.
-    let doc_path = args.doc_path.ok_or(SpedError::EfdFileNotFound)?;
+    let referencias = if args.referencia.is_empty() {
+        Vec::new()
+    } else {
+        expandir_entradas(&args.referencia)?
+    };
+
+    let aliases_de_colunas = match &args.mapa_colunas {
+        Some(path) => ler_mapa_de_colunas(path)?,
+        None => BTreeMap::new(),
+    };
+
+    let perfil = match &args.perfil_injecao {
+        Some(path) => ler_perfil_de_injecao(path)?,
+        None => PerfilDeInjecao::default(),
+    };
 
     Ok(Config {
+        comando: args.comando,
         atualizar_origem: args.atualizar_origem,
         clear: args.clear,
         doc_path,
+        doc_paths,
+        cte_nfes_path,
+        complementares_path,
+        mdfe_ctes_path,
+        referencias,
+        aliases_de_colunas,
+        perfil,
+        modo_saida: args.modo_saida,
+        separador: args.separador,
+        codificacao: args.encoding,
+        // `auto` na saída equivale a manter a codificação da entrada
+        codificacao_saida: args.encoding_saida.filter(|c| *c != Codificacao::Auto),
+        formato_numerico: args.formato_numerico,
+        item_representativo: args.item_representativo,
+        rebuild_cache: args.rebuild_cache,
+        dry_run: args.dry_run,
         exibir_config: args.exibir_config,
+        top: args.top,
+        jobs: usize::from(args.jobs),
+        threads: args.threads,
+        chunk_size: args.chunk_size,
+        json: args.json,
+        limpar: args.limpar,
         max_char: args.max_char,
         max_info: args.max_info,
+        max_valores_distintos: args.max_valores_distintos,
+        rateio_frete: args.rateio_frete,
+        tributos_cte: args.tributos_cte,
+        modalidade_frete: args.modalidade_frete,
+        consistencia: args.consistencia || args.coluna_consistencia,
+        coluna_consistencia: args.coluna_consistencia,
         no_prompt: args.no_prompt,
         verbose: args.verbose,
     })
 }
+
+/// Procura `nome` no diretório do arquivo de documentos e, em seguida, no diretório corrente.
+fn localizar_arquivo(doc_path: &Path, nome: &str) -> Option<PathBuf> {
+    let dir_doc = doc_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+
+    [dir_doc.join(nome), PathBuf::from(nome)]
+        .into_iter()
+        .find(|path| path.is_file())
+}
+
+/// Verifica se o arquivo informado explicitamente na linha de comando existe.
+fn validar_arquivo(path: PathBuf, tipo: &'static str, opcao: &'static str) -> SpedResult<PathBuf> {
+    if path.is_file() {
+        Ok(path)
+    } else {
+        Err(SpedError::RelationFileNotFound {
+            tipo,
+            opcao,
+            arquivo: path,
+        })
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output args_tests
+#[cfg(test)]
+#[path = "tests/args_tests.rs"]
+mod args_tests;
@@ -0,0 +1,328 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Take, Write},
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use crate::{BUFFER, Chave, Config, Informacoes, KeyMap, SpedResult, fmt_milhares};
+
+/// Identificação do formato do cache (4 bytes mágicos + versão).
+const MAGIC: &[u8; 4] = b"ICTE";
+const VERSAO: u32 = 1;
+
+/// Impressão digital de um arquivo de relacionamento.
+///
+/// O cache é válido se o tamanho coincidir e, além disso, a data de
+/// modificação (mtime) ou o hash do conteúdo também coincidirem.
+/// Assim, o hash só é recalculado quando o mtime muda (ex: cópia do arquivo).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fonte {
+    pub tamanho: u64,
+    pub mtime_ns: u128,
+    pub hash: u64,
+}
+
+impl Fonte {
+    /// Lê tamanho e mtime do arquivo e calcula o hash do conteúdo.
+    pub fn from_path(path: &Path) -> io::Result<Self> {
+        let (tamanho, mtime_ns) = metadados(path)?;
+        Ok(Fonte {
+            tamanho,
+            mtime_ns,
+            hash: hash_do_arquivo(path)?,
+        })
+    }
+
+    /// Verifica se o arquivo ainda corresponde a esta impressão digital.
+    fn corresponde(&self, path: &Path) -> io::Result<bool> {
+        let (tamanho, mtime_ns) = metadados(path)?;
+
+        if tamanho != self.tamanho {
+            return Ok(false);
+        }
+        if mtime_ns == self.mtime_ns {
+            return Ok(true);
+        }
+        Ok(hash_do_arquivo(path)? == self.hash)
+    }
+}
+
+fn metadados(path: &Path) -> io::Result<(u64, u128)> {
+    let meta = fs::metadata(path)?;
+    let mtime_ns = meta
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    Ok((meta.len(), mtime_ns))
+}
+
+/// Hash FNV-1a (64 bits) do conteúdo: estável entre versões do compilador.
+fn hash_do_arquivo(path: &Path) -> io::Result<u64> {
+    let mut reader = BufReader::with_capacity(BUFFER, File::open(path)?);
+    let mut buf = vec![0u8; BUFFER];
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        for &b in &buf[..n] {
+            hash ^= u64::from(b);
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    Ok(hash)
+}
+
+/// Caminho padrão do cache: ao lado do arquivo CTe -> NFes.
+///
+/// Exemplo: `cte_nfes.txt` -> `cte_nfes.cache.bin`
+pub fn caminho_do_cache(config: &Config) -> PathBuf {
+    config.cte_nfes_path.with_extension("cache.bin")
+}
+
+/// Carrega as tabelas de relacionamento do cache binário, se válido,
+/// ou as reconstrói a partir dos arquivos texto e atualiza o cache
+/// (exceto no modo `--dry-run`, que não grava arquivos).
+///
+/// Falhas de leitura ou gravação do cache não interrompem o programa:
+/// apenas são informadas e as tabelas são lidas dos arquivos texto.
+pub fn carregar_informacoes(config: &Config) -> SpedResult<Informacoes> {
+    let cache_path = caminho_do_cache(config);
+    let paths = [
+        Some(config.cte_nfes_path.as_path()),
+        config.complementares_path.as_deref(),
+        config.mdfe_ctes_path.as_deref(),
+    ];
+
+    if !config.rebuild_cache {
+        match ler_cache_valido(&cache_path, &paths) {
+            Ok(Some(info)) => {
+                println!(
+                    "--- Tabelas de Relacionamento carregadas do cache <{}> ---",
+                    cache_path.display()
+                );
+                println!(
+                    " -> Relações CTe -> NFes carregadas: {}",
+                    fmt_milhares(info.cte_nfes.len())
+                );
+                return Ok(info);
+            }
+            Ok(None) => {}
+            Err(e) => eprintln!(" -> Cache ignorado <{}>: {e}", cache_path.display()),
+        }
+    }
+
+    let info = Informacoes::from_files(config.cte_nfes_path.as_path(), paths[1], paths[2])?;
+
+    if config.dry_run {
+        return Ok(info);
+    }
+
+    if let Err(e) = gravar_cache(&cache_path, &paths, &info) {
+        eprintln!(
+            " -> Não foi possível gravar o cache <{}>: {e}",
+            cache_path.display()
+        );
+    }
+
+    Ok(info)
+}
+
+/// Lê o cache e verifica se as fontes ainda correspondem aos arquivos atuais.
+fn ler_cache_valido(cache_path: &Path, paths: &[Option<&Path>]) -> io::Result<Option<Informacoes>> {
+    if !cache_path.is_file() {
+        return Ok(None);
+    }
+
+    let file = File::open(cache_path)?;
+    let tamanho = file.metadata()?.len();
+    let (fontes, info) = ler(BufReader::with_capacity(BUFFER, file), tamanho)?;
+
+    if fontes.len() != paths.len() {
+        return Ok(None);
+    }
+
+    for (fonte, path) in fontes.iter().zip(paths) {
+        let valido = match (fonte, path) {
+            (Some(fonte), Some(path)) => fonte.corresponde(path)?,
+            (None, None) => true,
+            _ => false,
+        };
+        if !valido {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(info))
+}
+
+/// Grava o cache em arquivo temporário e o renomeia (substituição atômica).
+fn gravar_cache(cache_path: &Path, paths: &[Option<&Path>], info: &Informacoes) -> io::Result<()> {
+    let fontes = paths
+        .iter()
+        .map(|path| path.map(Fonte::from_path).transpose())
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let tmp_path = cache_path.with_extension("bin.tmp");
+    let mut writer = BufWriter::with_capacity(BUFFER, File::create(&tmp_path)?);
+    escrever(&mut writer, &fontes, info)?;
+    writer.flush()?;
+    drop(writer);
+
+    fs::rename(&tmp_path, cache_path)
+}
+
+/// Serializa as fontes e as tabelas (`cte_nfes`, `cte_complementar`, `mdfe_ctes`).
+///
+/// Os índices invertidos (`nfe_ctes`, `cte_mdfes`) são recalculados na leitura.
+pub fn escrever<W: Write>(
+    w: &mut W,
+    fontes: &[Option<Fonte>],
+    info: &Informacoes,
+) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&VERSAO.to_le_bytes())?;
+
+    w.write_all(&(fontes.len() as u32).to_le_bytes())?;
+    for fonte in fontes {
+        match fonte {
+            Some(f) => {
+                w.write_all(&[1])?;
+                w.write_all(&f.tamanho.to_le_bytes())?;
+                w.write_all(&f.mtime_ns.to_le_bytes())?;
+                w.write_all(&f.hash.to_le_bytes())?;
+            }
+            None => w.write_all(&[0])?,
+        }
+    }
+
+    for map in [&info.cte_nfes, &info.cte_complementar, &info.mdfe_ctes] {
+        escrever_mapa(w, map)?;
+    }
+
+    Ok(())
+}
+
+fn escrever_mapa<W: Write>(w: &mut W, map: &KeyMap) -> io::Result<()> {
+    w.write_all(&(map.len() as u64).to_le_bytes())?;
+    for (chave, relacionadas) in map {
+        w.write_all(chave.as_bytes())?;
+        w.write_all(&(relacionadas.len() as u32).to_le_bytes())?;
+        for rel in relacionadas {
+            w.write_all(rel.as_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// Desserializa o conteúdo gravado por `escrever`.
+///
+/// `tamanho` é o número de bytes do cache: quantidades gravadas no arquivo
+/// que não caberiam nele são rejeitadas antes de qualquer alocação.
+pub fn ler<R: Read>(r: R, tamanho: u64) -> io::Result<(Vec<Option<Fonte>>, Informacoes)> {
+    let r = &mut r.take(tamanho);
+
+    let mut magic = [0u8; 4];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC || ler_u32(r)? != VERSAO {
+        return Err(invalido("formato ou versão incompatível"));
+    }
+
+    // Cada fonte ocupa ao menos 1 byte
+    let num_fontes = ler_u32(r)?;
+    let num_fontes = ler_quantidade(r, u64::from(num_fontes), 1)?;
+    let mut fontes = Vec::with_capacity(num_fontes);
+    for _ in 0..num_fontes {
+        let mut presente = [0u8; 1];
+        r.read_exact(&mut presente)?;
+        fontes.push(match presente[0] {
+            0 => None,
+            _ => Some(Fonte {
+                tamanho: ler_u64(r)?,
+                mtime_ns: ler_u128(r)?,
+                hash: ler_u64(r)?,
+            }),
+        });
+    }
+
+    let mut info = Informacoes {
+        cte_nfes: ler_mapa(r)?,
+        cte_complementar: ler_mapa(r)?,
+        mdfe_ctes: ler_mapa(r)?,
+        ..Default::default()
+    };
+
+    info.get_nfe_ctes();
+    info.get_cte_mdfes();
+
+    Ok((fontes, info))
+}
+
+fn ler_mapa<R: Read>(r: &mut Take<R>) -> io::Result<KeyMap> {
+    // Cada entrada ocupa ao menos 48 bytes: chave (44) e quantidade (4)
+    let len = ler_u64(r)?;
+    let len = ler_quantidade(r, len, 48)?;
+    let mut map = KeyMap::with_capacity(len);
+    for _ in 0..len {
+        let chave = ler_chave(r)?;
+        let num = ler_u32(r)?;
+        let num = ler_quantidade(r, u64::from(num), 44)?;
+        let relacionadas = (0..num).map(|_| ler_chave(r)).collect::<io::Result<_>>()?;
+        map.insert(chave, relacionadas);
+    }
+    Ok(map)
+}
+
+/// Valida uma quantidade lida do cache: `num` itens de ao menos `bytes_por_item`
+/// bytes devem caber no restante do arquivo (cache corrompido, caso contrário).
+fn ler_quantidade<R: Read>(r: &Take<R>, num: u64, bytes_por_item: u64) -> io::Result<usize> {
+    match num.checked_mul(bytes_por_item) {
+        Some(bytes) if bytes <= r.limit() => Ok(num as usize),
+        _ => Err(invalido(
+            "quantidade de registros excede o tamanho do arquivo",
+        )),
+    }
+}
+
+fn ler_chave<R: Read>(r: &mut R) -> io::Result<Chave> {
+    let mut bytes = [0u8; 44];
+    r.read_exact(&mut bytes)?;
+    Chave::from_bytes(bytes).ok_or_else(|| invalido("chave com caracteres não numéricos"))
+}
+
+fn ler_u32<R: Read>(r: &mut R) -> io::Result<u32> {
+    let mut b = [0u8; 4];
+    r.read_exact(&mut b)?;
+    Ok(u32::from_le_bytes(b))
+}
+
+fn ler_u64<R: Read>(r: &mut R) -> io::Result<u64> {
+    let mut b = [0u8; 8];
+    r.read_exact(&mut b)?;
+    Ok(u64::from_le_bytes(b))
+}
+
+fn ler_u128<R: Read>(r: &mut R) -> io::Result<u128> {
+    let mut b = [0u8; 16];
+    r.read_exact(&mut b)?;
+    Ok(u128::from_le_bytes(b))
+}
+
+fn invalido(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.to_string())
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output cache_tests
+#[cfg(test)]
+#[path = "tests/cache_tests.rs"]
+mod cache_tests;
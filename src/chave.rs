@@ -1,7 +1,102 @@
 use serde::Serialize;
-use std::fmt;
+use std::{collections::BTreeSet, fmt, ops::Range};
 const NUN_DIGITOS: usize = 44;
 
+/// Número máximo de amostras de chaves inválidas retidas para o relatório.
+const MAX_AMOSTRAS: usize = 10;
+
+/// Modelo do documento fiscal eletrônico (posições 21-22 da chave de acesso).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Modelo {
+    /// NF-e: Nota Fiscal Eletrônica (55)
+    Nfe,
+    /// CT-e: Conhecimento de Transporte Eletrônico (57)
+    Cte,
+    /// MDF-e: Manifesto Eletrônico de Documentos Fiscais (58)
+    Mdfe,
+    /// NFC-e: Nota Fiscal de Consumidor Eletrônica (65)
+    Nfce,
+    /// NF3-e: Nota Fiscal de Energia Elétrica Eletrônica (66)
+    Nf3e,
+    /// CT-e OS: Conhecimento de Transporte Eletrônico para Outros Serviços (67)
+    CteOs,
+    /// Qualquer outro código de modelo
+    Outro(u8),
+}
+
+impl Modelo {
+    /// Código numérico do modelo.
+    pub fn codigo(self) -> u8 {
+        match self {
+            Modelo::Nfe => 55,
+            Modelo::Cte => 57,
+            Modelo::Mdfe => 58,
+            Modelo::Nfce => 65,
+            Modelo::Nf3e => 66,
+            Modelo::CteOs => 67,
+            Modelo::Outro(codigo) => codigo,
+        }
+    }
+
+    /// Notas fiscais de mercadorias transportadas: NF-e (55) e NFC-e (65).
+    #[inline]
+    pub fn is_nota(self) -> bool {
+        matches!(self, Modelo::Nfe | Modelo::Nfce)
+    }
+
+    /// Conhecimentos de transporte: CT-e (57) e CT-e OS (67).
+    #[inline]
+    pub fn is_conhecimento(self) -> bool {
+        matches!(self, Modelo::Cte | Modelo::CteOs)
+    }
+
+    /// Documentos de transporte: conhecimentos (57, 67) e manifestos (58).
+    ///
+    /// Seus resumos são armazenados com os metadados de CT-e.
+    #[inline]
+    pub fn is_transporte(self) -> bool {
+        self.is_conhecimento() || self == Modelo::Mdfe
+    }
+}
+
+impl Serialize for Modelo {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl From<u8> for Modelo {
+    fn from(codigo: u8) -> Self {
+        match codigo {
+            55 => Modelo::Nfe,
+            57 => Modelo::Cte,
+            58 => Modelo::Mdfe,
+            65 => Modelo::Nfce,
+            66 => Modelo::Nf3e,
+            67 => Modelo::CteOs,
+            outro => Modelo::Outro(outro),
+        }
+    }
+}
+
+impl fmt::Display for Modelo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // pad() respeita largura e alinhamento (ex: "{:>7}")
+        match self {
+            Modelo::Nfe => f.pad("NF-e"),
+            Modelo::Cte => f.pad("CT-e"),
+            Modelo::Mdfe => f.pad("MDF-e"),
+            Modelo::Nfce => f.pad("NFC-e"),
+            Modelo::Nf3e => f.pad("NF3-e"),
+            Modelo::CteOs => f.pad("CT-e OS"),
+            Modelo::Outro(codigo) => f.pad(&format!("Modelo {codigo}")),
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Chave([u8; NUN_DIGITOS]);
 
@@ -69,6 +164,173 @@ impl Chave {
         }
     }
 
+    /// Reconstrói uma Chave a partir dos 44 bytes brutos (ex: leitura do cache binário).
+    /// Retorna None se algum byte não for dígito ASCII.
+    #[inline]
+    pub(crate) fn from_bytes(bytes: [u8; NUN_DIGITOS]) -> Option<Self> {
+        bytes
+            .iter()
+            .all(|b| b.wrapping_sub(b'0') < 10)
+            .then_some(Chave(bytes))
+    }
+
+    /// Os 44 dígitos ASCII da chave.
+    #[inline]
+    pub(crate) fn as_bytes(&self) -> &[u8; NUN_DIGITOS] {
+        &self.0
+    }
+
+    /// Converte um intervalo de dígitos ASCII da chave em número.
+    #[inline]
+    fn digitos(&self, range: Range<usize>) -> u32 {
+        self.0[range]
+            .iter()
+            .fold(0, |acc, &b| acc * 10 + u32::from(b - b'0'))
+    }
+
+    /// cUF: Código da UF do emitente (posições 1-2).
+    #[inline]
+    pub fn cuf(&self) -> u8 {
+        self.digitos(0..2) as u8
+    }
+
+    /// Sigla da UF do emitente, conforme o cUF (código IBGE); `None` se o código for desconhecido.
+    pub fn uf(&self) -> Option<&'static str> {
+        let sigla = match self.cuf() {
+            11 => "RO",
+            12 => "AC",
+            13 => "AM",
+            14 => "RR",
+            15 => "PA",
+            16 => "AP",
+            17 => "TO",
+            21 => "MA",
+            22 => "PI",
+            23 => "CE",
+            24 => "RN",
+            25 => "PB",
+            26 => "PE",
+            27 => "AL",
+            28 => "SE",
+            29 => "BA",
+            31 => "MG",
+            32 => "ES",
+            33 => "RJ",
+            35 => "SP",
+            41 => "PR",
+            42 => "SC",
+            43 => "RS",
+            50 => "MS",
+            51 => "MT",
+            52 => "GO",
+            53 => "DF",
+            _ => return None,
+        };
+        Some(sigla)
+    }
+
+    /// AAMM: Ano e mês de emissão (posições 3-6), como texto.
+    #[inline]
+    pub fn aamm(&self) -> &str {
+        &self.as_str()[2..6]
+    }
+
+    /// Ano de emissão com dois dígitos (AA).
+    #[inline]
+    pub fn ano(&self) -> u8 {
+        self.digitos(2..4) as u8
+    }
+
+    /// Mês de emissão (MM).
+    #[inline]
+    pub fn mes(&self) -> u8 {
+        self.digitos(4..6) as u8
+    }
+
+    /// CNPJ ou CPF do emitente (posições 7-20).
+    ///
+    /// O CPF ocupa os 11 dígitos finais, precedido de zeros.
+    #[inline]
+    pub fn cnpj_cpf_emitente(&self) -> &str {
+        &self.as_str()[6..20]
+    }
+
+    /// Código do modelo do documento fiscal (posições 21-22): 55, 57, etc.
+    #[inline]
+    pub fn codigo_modelo(&self) -> u8 {
+        self.digitos(20..22) as u8
+    }
+
+    /// Modelo do documento fiscal, decodificado a partir de `codigo_modelo`.
+    #[inline]
+    pub fn modelo(&self) -> Modelo {
+        Modelo::from(self.codigo_modelo())
+    }
+
+    /// Série do documento fiscal (posições 23-25).
+    #[inline]
+    pub fn serie(&self) -> u16 {
+        self.digitos(22..25) as u16
+    }
+
+    /// Número do documento fiscal (posições 26-34).
+    #[inline]
+    pub fn numero(&self) -> u32 {
+        self.digitos(25..34)
+    }
+
+    /// tpEmis: Forma de emissão (posição 35).
+    #[inline]
+    pub fn tp_emis(&self) -> u8 {
+        self.digitos(34..35) as u8
+    }
+
+    /// cNF: Código numérico que compõe a chave de acesso (posições 36-43).
+    #[inline]
+    pub fn cnf(&self) -> u32 {
+        self.digitos(35..43)
+    }
+
+    /// DV: Dígito verificador informado na chave (posição 44).
+    #[inline]
+    pub fn dv(&self) -> u8 {
+        self.digitos(43..44) as u8
+    }
+
+    /// Calcula o dígito verificador (módulo 11) a partir dos 43 primeiros dígitos.
+    ///
+    /// Os dígitos são multiplicados, da direita para a esquerda, pelos pesos
+    /// 2 a 9 (reiniciando em 2). Se o resto da soma por 11 for 0 ou 1, o DV é 0;
+    /// caso contrário, o DV é 11 - resto.
+    ///
+    /// ### Exemplo
+    /// ```
+    /// use adicionar_info_de_ctes_em_nfes::Chave;
+    ///
+    /// let chave = Chave::new("35200714200166000187550010000000071000000077").unwrap();
+    /// assert_eq!(chave.calcular_dv(), 7);
+    /// assert!(chave.dv_valido());
+    /// ```
+    pub fn calcular_dv(&self) -> u8 {
+        let soma: u32 = self.0[..NUN_DIGITOS - 1]
+            .iter()
+            .rev()
+            .zip((2..=9).cycle())
+            .map(|(&b, peso)| u32::from(b - b'0') * peso)
+            .sum();
+
+        match soma % 11 {
+            0 | 1 => 0,
+            resto => (11 - resto) as u8,
+        }
+    }
+
+    /// Verifica se o dígito verificador da chave confere com o módulo 11.
+    #[inline]
+    pub fn dv_valido(&self) -> bool {
+        self.dv() == self.calcular_dv()
+    }
+
     /// Atalho para verificar se é NF-e
     #[inline]
     pub fn is_nfe(&self) -> bool {
@@ -81,6 +343,18 @@ impl Chave {
         &self.0[20..22] == b"57"
     }
 
+    /// Atalho para verificar se é MDF-e
+    #[inline]
+    pub fn is_mdfe(&self) -> bool {
+        &self.0[20..22] == b"58"
+    }
+
+    /// Atalho para verificar se é CT-e OS
+    #[inline]
+    pub fn is_cte_os(&self) -> bool {
+        &self.0[20..22] == b"67"
+    }
+
     /// Retorna a chave como string slice (&str) para uso em logs ou formatação
     #[inline]
     pub fn as_str(&self) -> &str {
@@ -90,6 +364,62 @@ impl Chave {
     }
 }
 
+/// Registro de chaves com dígito verificador inválido: contagem e amostras.
+///
+/// As amostras são as menores chaves encontradas (em ordem), de modo que o
+/// resultado não depende da ordem de processamento entre threads.
+#[derive(Debug, Default, Clone)]
+pub struct ChavesInvalidas {
+    pub total: usize,
+    pub amostras: BTreeSet<Chave>,
+}
+
+impl ChavesInvalidas {
+    /// Retorna `true` se o DV da chave for válido; caso contrário, registra a chave.
+    #[inline]
+    pub fn verificar(&mut self, chave: Chave) -> bool {
+        if chave.dv_valido() {
+            return true;
+        }
+
+        self.total += 1;
+        self.amostras.insert(chave);
+        if self.amostras.len() > MAX_AMOSTRAS {
+            self.amostras.pop_last();
+        }
+
+        false
+    }
+
+    /// Combina dois registros (usado na redução entre threads).
+    pub fn merge(&mut self, other: Self) {
+        self.total += other.total;
+        self.amostras.extend(other.amostras);
+        while self.amostras.len() > MAX_AMOSTRAS {
+            self.amostras.pop_last();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// Imprime a contagem e as amostras, se houver chaves inválidas.
+    pub fn print_log(&self, origem: &str) {
+        if self.is_empty() {
+            return;
+        }
+
+        println!(
+            " -> ATENÇÃO: {} ocorrências de chaves com dígito verificador inválido em <{}>.",
+            self.total, origem
+        );
+        for chave in &self.amostras {
+            println!("    {chave}");
+        }
+    }
+}
+
 // Implementação de Display para facilitar o print (println!("{}", chave))
 impl fmt::Display for Chave {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -142,3 +472,13 @@ impl<'de> serde::Deserialize<'de> for Chave {
         deserializer.deserialize_str(ChaveVisitor)
     }
 }
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output chave_tests
+#[cfg(test)]
+#[path = "tests/chave_tests.rs"]
+mod chave_tests;
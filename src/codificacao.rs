@@ -0,0 +1,165 @@
+use clap::ValueEnum;
+use encoding_rs::{EncoderResult, WINDOWS_1252};
+use std::{
+    borrow::Cow,
+    fmt,
+    io::{self, Write},
+};
+
+/// Marca de ordem de bytes (BOM) UTF-8, gravada pelo Excel no início do arquivo.
+pub const BOM_UTF8: &[u8] = b"\xEF\xBB\xBF";
+
+/// Codificação de caracteres dos arquivos CSV (`--encoding` e `--encoding-saida`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Codificacao {
+    /// Detectar: BOM UTF-8, UTF-8 válido ou, caso contrário, Windows-1252.
+    #[default]
+    Auto,
+    /// UTF-8 sem BOM.
+    #[value(name = "utf-8", alias = "utf8")]
+    Utf8,
+    /// UTF-8 com BOM (arquivos salvos pelo Excel).
+    #[value(name = "utf-8-bom", alias = "utf8-bom")]
+    Utf8Bom,
+    /// Windows-1252 (superconjunto do Latin-1 / ISO-8859-1).
+    #[value(
+        name = "windows-1252",
+        alias = "cp1252",
+        alias = "latin1",
+        alias = "iso-8859-1"
+    )]
+    Windows1252,
+}
+
+impl fmt::Display for Codificacao {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let nome = match self {
+            Codificacao::Auto => "Auto",
+            Codificacao::Utf8 => "UTF-8",
+            Codificacao::Utf8Bom => "UTF-8 com BOM",
+            Codificacao::Windows1252 => "Windows-1252",
+        };
+        f.pad(nome)
+    }
+}
+
+impl Codificacao {
+    /// Resolve `Auto` a partir do conteúdo do arquivo.
+    ///
+    /// Um arquivo que não é UTF-8 válido é tratado como Windows-1252:
+    /// todos os bytes têm representação nesta codificação.
+    pub fn detectar(self, bytes: &[u8]) -> Self {
+        match self {
+            Codificacao::Auto if bytes.starts_with(BOM_UTF8) => Codificacao::Utf8Bom,
+            Codificacao::Auto if std::str::from_utf8(bytes).is_ok() => Codificacao::Utf8,
+            Codificacao::Auto => Codificacao::Windows1252,
+            definida => definida,
+        }
+    }
+
+    /// Tamanho do BOM no início do arquivo.
+    pub fn tamanho_do_bom(self) -> usize {
+        if self == Codificacao::Utf8Bom {
+            BOM_UTF8.len()
+        } else {
+            0
+        }
+    }
+
+    /// Converte o conteúdo do arquivo para UTF-8 (sem cópia, se já for UTF-8).
+    pub fn decodificar(self, bytes: &[u8]) -> Cow<'_, [u8]> {
+        match self {
+            Codificacao::Windows1252 => match WINDOWS_1252.decode_without_bom_handling(bytes).0 {
+                Cow::Borrowed(texto) => Cow::Borrowed(texto.as_bytes()),
+                Cow::Owned(texto) => Cow::Owned(texto.into_bytes()),
+            },
+            _ => Cow::Borrowed(bytes),
+        }
+    }
+}
+
+/// Writer que recebe texto UTF-8 e grava na codificação de saída.
+///
+/// Com `Utf8Bom`, o BOM é gravado na criação; em UTF-8, os bytes são repassados
+/// sem conversão. Caracteres sem representação em Windows-1252 são gravados como '?'.
+pub struct Codificador<W: Write> {
+    destino: W,
+    codificacao: Codificacao,
+    /// Bytes finais incompletos de um caractere UTF-8 (aguardando a próxima escrita).
+    pendentes: Vec<u8>,
+    saida: Vec<u8>,
+}
+
+impl<W: Write> Codificador<W> {
+    pub fn new(mut destino: W, codificacao: Codificacao) -> io::Result<Self> {
+        if codificacao == Codificacao::Utf8Bom {
+            destino.write_all(BOM_UTF8)?;
+        }
+
+        Ok(Codificador {
+            destino,
+            codificacao,
+            pendentes: Vec::new(),
+            saida: Vec::new(),
+        })
+    }
+
+    fn codificar(&mut self, texto: &str) -> io::Result<()> {
+        let mut encoder = WINDOWS_1252.new_encoder();
+        let mut entrada = texto;
+        self.saida.resize(texto.len().clamp(16, 64 * 1024), 0);
+
+        loop {
+            let (resultado, lidos, escritos) =
+                encoder.encode_from_utf8_without_replacement(entrada, &mut self.saida, false);
+            self.destino.write_all(&self.saida[..escritos])?;
+            entrada = &entrada[lidos..];
+
+            match resultado {
+                EncoderResult::InputEmpty => return Ok(()),
+                EncoderResult::OutputFull => {}
+                EncoderResult::Unmappable(_) => self.destino.write_all(b"?")?,
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for Codificador<W> {
+    fn write(&mut self, dados: &[u8]) -> io::Result<usize> {
+        if self.codificacao != Codificacao::Windows1252 {
+            return self.destino.write(dados);
+        }
+
+        self.pendentes.extend_from_slice(dados);
+        let pendentes = std::mem::take(&mut self.pendentes);
+
+        let validos = match std::str::from_utf8(&pendentes) {
+            Ok(texto) => texto,
+            // Caractere incompleto no final: aguarda os bytes restantes
+            Err(e) if e.error_len().is_none() => {
+                // SAFETY: `valid_up_to` delimita um prefixo UTF-8 válido
+                unsafe { std::str::from_utf8_unchecked(&pendentes[..e.valid_up_to()]) }
+            }
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+
+        self.codificar(validos)?;
+        self.pendentes = pendentes[validos.len()..].to_vec();
+
+        Ok(dados.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.destino.flush()
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output codificacao_tests
+#[cfg(test)]
+#[path = "tests/codificacao_tests.rs"]
+mod codificacao_tests;
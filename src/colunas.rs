@@ -1,6 +1,11 @@
-use crate::{Chave, Config, RE_MULTISPACE};
+use crate::{
+    Chave, Config, DirecaoDeInjecao, Distribuicao, FormatoNumerico, Metadados, ModoDeInjecao,
+    Numero, RE_ANOTACAO, RE_CHAVE_DE_ACESSO_ENRIQUECIDA, RE_MULTISPACE, campo_de_texto,
+    interpretar_numero, valor_significativo,
+};
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::BTreeMap};
 
 #[derive(Debug, Default, Serialize, Deserialize, Clone)]
 pub struct Colunas<'a> {
@@ -213,52 +218,16 @@ pub struct Colunas<'a> {
 }
 
 impl<'a> Colunas<'a> {
-    /// Obter f64 de valores númericos de formato do Brasil (Versão Zero-Allocation)
-    ///
-    /// Limpar os bytes em um buffer fixo.
+    /// Interpreta o valor do item no `formato` informado (ver `interpretar_numero`).
     #[inline]
-    pub fn get_valor_do_item(&self) -> Option<f64> {
-        let bytes = self.valor_item.as_bytes();
-        if bytes.is_empty() {
-            return None;
-        }
-
-        let tem_virgula = bytes.contains(&b',');
-        let mut buf = [0u8; 64];
-        let mut pos = 0;
-
-        for &b in bytes {
-            if pos >= 64 {
-                eprintln!(
-                    "\n[ERRO]: Valor numérico excede o limite de 64 caracteres e será ignorado.\n\
-                     Chave: {}\n\
-                     Valor problemático: '{}'",
-                    self.chave, self.valor_item
-                );
-                return None;
-            }
-
-            match b {
-                b'.' if tem_virgula => continue,
-                b',' => {
-                    buf[pos] = b'.';
-                    pos += 1;
-                }
-                // ADICIONADO: b'e' | b'E' para suportar notação científica
-                b'0'..=b'9' | b'-' | b'+' | b'.' | b'e' | b'E' => {
-                    buf[pos] = b;
-                    pos += 1;
-                }
-                _ => continue,
-            }
-        }
-
-        if pos == 0 {
-            return None;
-        }
+    pub fn valor_do_item(&self, formato: FormatoNumerico) -> Numero {
+        interpretar_numero(&self.valor_item, formato)
+    }
 
-        let s = unsafe { std::str::from_utf8_unchecked(&buf[..pos]) };
-        s.parse::<f64>().ok()
+    /// Obter o decimal exato do valor do item (formato detectado automaticamente).
+    #[inline]
+    pub fn get_valor_do_item(&self) -> Option<Decimal> {
+        self.valor_do_item(FormatoNumerico::Auto).valor()
     }
 
     pub fn get_valor_do_item_old(&self) -> Option<f64> {
@@ -300,189 +269,240 @@ impl<'a> Colunas<'a> {
         }
     }
 
-    /// Injeta metadados de um CT-e nesta NF-e (16 colunas)
-    pub fn injetar_metadata_cte(&mut self, config: &Config, c: &CteMetadata<'a>) {
-        let label = "CT-e";
-        config.append(&mut self.remetente_cnpj1, &c.remetente_cnpj1, label);
-        config.append(&mut self.remetente_cnpj2, &c.remetente_cnpj2, label);
-        config.append(&mut self.tomador_papel1, &c.tomador_papel1, label);
-        config.append(&mut self.tomador_papel2, &c.tomador_papel2, label);
-        config.append(&mut self.tomador_cnpj1, &c.tomador_cnpj1, label);
-        config.append(&mut self.tomador_cnpj2, &c.tomador_cnpj2, label);
-        config.append(&mut self.inicio_estado, &c.inicio_estado, label);
-        config.append(&mut self.inicio_municipio, &c.inicio_municipio, label);
-        config.append(&mut self.termino_estado, &c.termino_estado, label);
-        config.append(&mut self.termino_municipio, &c.termino_municipio, label);
-        config.append(&mut self.destinatario_cnpj, &c.destinatario_cnpj, label);
-        config.append(&mut self.destinatario_nome, &c.destinatario_nome, label);
-        config.append(&mut self.local_entrega, &c.local_entrega, label);
-        config.append(&mut self.descricao_natureza, &c.descricao_natureza, label);
-        config.append(&mut self.observacoes_gerais, &c.observacoes_gerais, label);
-        config.append(&mut self.descricao_cfop, &c.descricao_cfop, label);
+    /// Verifica se a linha já foi enriquecida por uma execução anterior.
+    ///
+    /// Todo enriquecimento reescreve ou anota a coluna "Chave de Acesso",
+    /// portanto basta inspecioná-la.
+    pub fn ja_enriquecida(&self) -> bool {
+        RE_CHAVE_DE_ACESSO_ENRIQUECIDA.is_match(&self.chave_de_acesso)
+            || RE_ANOTACAO.is_match(&self.chave_de_acesso)
     }
 
-    /// Injeta metadados de uma NF-e neste CT-e (10 colunas)
-    pub fn injetar_metadata_nfe(&mut self, config: &Config, n: &NfeMetadata<'a>) {
-        let label = "NF-e";
-        config.append(&mut self.contribuinte_nome, &n.contribuinte_nome, label);
-        config.append(&mut self.participante_nome, &n.participante_nome, label);
-        config.append(&mut self.observacoes, &n.observacoes, label);
-        config.append(&mut self.numero_di, &n.numero_di, label);
-        config.append(&mut self.descricao_cfop, &n.descricao_cfop, label);
-        config.append(
-            &mut self.descricao_mercadoria,
-            &n.descricao_mercadoria,
-            label,
-        );
-
-        // Lógica Especial de NCM: Overwrite se o NCM da NF-e for válido
-        if n.ncm_valido() {
-            self.ncm = n.ncm.clone();
+    /// Remove as anotações " [Info d..: ...]" ao final de cada coluna e esvazia
+    /// a "Chave de Acesso" reescrita.
+    ///
+    /// O texto original que precede as anotações é preservado. O conteúdo
+    /// original da "Chave de Acesso" reescrita e de um NCM sobrescrito é
+    /// restaurado por `limpar_arquivo` a partir do arquivo de valores originais.
+    ///
+    /// Retorna `true` se alguma coluna foi alterada.
+    pub fn limpar_anotacoes(&mut self) -> bool {
+        let mut mudou = false;
+
+        // Qualquer coluna pode ser destino de um perfil de injeção
+        for campo in self.campos_de_texto_mut() {
+            let fim = inicio_das_anotacoes(campo);
+            if fim < campo.len() {
+                campo.to_mut().truncate(fim);
+                mudou = true;
+            }
         }
 
-        config.append(&mut self.descricao_ncm, &n.descricao_ncm, label);
-        config.append(
-            &mut self.cst_descricao_cofins,
-            &n.cst_descricao_cofins,
-            label,
-        );
-        config.append(&mut self.cst_descricao_pis, &n.cst_descricao_pis, label);
+        if RE_CHAVE_DE_ACESSO_ENRIQUECIDA.is_match(&self.chave_de_acesso) {
+            self.chave_de_acesso = Cow::Borrowed("");
+            mudou = true;
+        }
+
+        mudou
     }
 
-    /// Extrai apenas o que é necessário para o resumo do CT-e
-    pub fn extrair_cte_metadata(&self) -> CteMetadata<'static> {
-        CteMetadata {
-            remetente_cnpj1: Cow::Owned(self.remetente_cnpj1.to_string()),
-            remetente_cnpj2: Cow::Owned(self.remetente_cnpj2.to_string()),
-            tomador_papel1: Cow::Owned(self.tomador_papel1.to_string()),
-            tomador_papel2: Cow::Owned(self.tomador_papel2.to_string()),
-            tomador_cnpj1: Cow::Owned(self.tomador_cnpj1.to_string()),
-            tomador_cnpj2: Cow::Owned(self.tomador_cnpj2.to_string()),
-            inicio_estado: Cow::Owned(self.inicio_estado.to_string()),
-            inicio_municipio: Cow::Owned(self.inicio_municipio.to_string()),
-            termino_estado: Cow::Owned(self.termino_estado.to_string()),
-            termino_municipio: Cow::Owned(self.termino_municipio.to_string()),
-            destinatario_cnpj: Cow::Owned(self.destinatario_cnpj.to_string()),
-            destinatario_nome: Cow::Owned(self.destinatario_nome.to_string()),
-            local_entrega: Cow::Owned(self.local_entrega.to_string()),
-            descricao_natureza: Cow::Owned(self.descricao_natureza.to_string()),
-            observacoes_gerais: Cow::Owned(self.observacoes_gerais.to_string()),
-            descricao_cfop: Cow::Owned(self.descricao_cfop.to_string()),
+    /// Restaura os valores originais das colunas, por nome de coluna
+    /// (ver `limpar_arquivo`). Colunas desconhecidas são ignoradas.
+    ///
+    /// Retorna `true` se alguma coluna foi alterada.
+    pub fn restaurar(&mut self, originais: &BTreeMap<String, String>) -> bool {
+        let mut campos = self.campos_de_texto_mut();
+        let mut mudou = false;
+
+        for (nome, original) in originais {
+            if let Some(campo) = campo_de_texto(nome).map(|i| &mut *campos[i])
+                && campo != original
+            {
+                *campo = Cow::Owned(original.clone());
+                mudou = true;
+            }
         }
+
+        mudou
     }
 
-    /// Extrai apenas o que é necessário para o resumo da NF-e
-    pub fn extrair_nfe_metadata(&self) -> NfeMetadata<'static> {
-        NfeMetadata {
-            contribuinte_nome: Cow::Owned(self.contribuinte_nome.to_string()),
-            participante_nome: Cow::Owned(self.participante_nome.to_string()),
-            observacoes: Cow::Owned(self.observacoes.to_string()),
-            numero_di: Cow::Owned(self.numero_di.to_string()),
-            descricao_cfop: Cow::Owned(self.descricao_cfop.to_string()),
-            descricao_mercadoria: Cow::Owned(self.descricao_mercadoria.to_string()),
-            ncm: Cow::Owned(self.ncm.to_string()),
-            descricao_ncm: Cow::Owned(self.descricao_ncm.to_string()),
-            cst_descricao_cofins: Cow::Owned(self.cst_descricao_cofins.to_string()),
-            cst_descricao_pis: Cow::Owned(self.cst_descricao_pis.to_string()),
+    /// Injeta nesta linha os metadados de um documento relacionado, conforme as
+    /// colunas da direção do perfil (ver `PerfilDeInjecao`).
+    ///
+    /// `ordem` é a posição do documento entre os relacionados (0: o primeiro),
+    /// comparada ao `max_info` de cada coluna.
+    ///
+    /// Retorna `false` se alguma informação foi descartada pelo limite `max_char`.
+    pub fn injetar_metadados(
+        &mut self,
+        config: &Config,
+        direcao: &DirecaoDeInjecao,
+        metadados: &Metadados,
+        ordem: usize,
+    ) -> bool {
+        let campos = self.campos_de_texto_mut();
+        let mut completo = true;
+
+        for coluna in &direcao.colunas {
+            if coluna.max_info.is_some_and(|max| ordem >= max) {
+                continue;
+            }
+
+            let valor = metadados.valor(coluna.origem);
+            let destino = &mut *campos[coluna.destino];
+
+            match coluna.modo {
+                ModoDeInjecao::Anotar => {
+                    completo &= config.append(destino, valor, direcao.rotulo);
+                }
+                // Ex: o NCM da NF-e substitui o NCM do CT-e, se for válido
+                ModoDeInjecao::Sobrescrever if valor_significativo(valor) => {
+                    *destino = Cow::Owned(valor.to_string());
+                }
+                ModoDeInjecao::Sobrescrever => {}
+            }
         }
+
+        completo
     }
-}
 
-// --- 16 Colunas que o CT-e fornece para a NF-e ---
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
-pub struct CteMetadata<'a> {
-    #[serde(
-        rename = "CTe - Remetente das mercadorias transportadas: CNPJ/CPF de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub remetente_cnpj1: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - Remetente das mercadorias transportadas: CNPJ/CPF de Conhecimento : ConhecimentoInformacaoNFe"
-    )]
-    pub remetente_cnpj2: Cow<'a, str>,
-    #[serde(
-        rename = "Descrição CTe - Indicador do 'papel' do tomador do serviço de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub tomador_papel1: Cow<'a, str>,
-    #[serde(
-        rename = "Descrição CTe - Indicador do 'papel' do tomador do serviço de Conhecimento : ConhecimentoInformacaoNFe"
-    )]
-    pub tomador_papel2: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - Outro tipo de Tomador: CNPJ/CPF de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub tomador_cnpj1: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - Outro tipo de Tomador: CNPJ/CPF de Conhecimento : ConhecimentoInformacaoNFe"
-    )]
-    pub tomador_cnpj2: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - UF do início da prestação de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub inicio_estado: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - Nome do Município do início da prestação de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub inicio_municipio: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - UF do término da prestação de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub termino_estado: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - Nome do Município do término da prestação de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub termino_municipio: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - Informações do Destinatário do CT-e: CNPJ/CPF de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub destinatario_cnpj: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - Informações do Destinatário do CT-e: Nome de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub destinatario_nome: Cow<'a, str>,
-    #[serde(
-        rename = "CTe - Local de Entrega constante na Nota Fiscal: Nome de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes"
-    )]
-    pub local_entrega: Cow<'a, str>,
-    #[serde(rename = "Descrição da Natureza da Operação : NF Item (Todos)")]
-    pub descricao_natureza: Cow<'a, str>,
-    #[serde(rename = "CTe - Observações Gerais de Conhecimento : ConhecimentoInformacaoNFe")]
-    pub observacoes_gerais: Cow<'a, str>,
-    #[serde(rename = "Descrição CFOP : NF Item (Todos)")]
-    pub descricao_cfop: Cow<'a, str>,
-}
+    /// Injeta a distribuição dos valores distintos de um documento relacionado.
+    ///
+    /// Apenas campos com mais de um valor distinto: com um único valor, a
+    /// informação já consta dos metadados do item representativo.
+    ///
+    /// Retorna `false` se alguma informação foi descartada pelo limite `max_char`.
+    pub fn injetar_distribuicao(&mut self, config: &Config, d: &Distribuicao, label: &str) -> bool {
+        let mut completo = true;
+        for (campo, valores, rotulo) in [
+            (&mut self.descricao_ncm, &d.ncm, "NCM"),
+            (&mut self.descricao_cfop, &d.cfop, "CFOP"),
+            (&mut self.cst_descricao_pis, &d.cst_pis, "CST PIS"),
+            (&mut self.cst_descricao_cofins, &d.cst_cofins, "CST COFINS"),
+            (&mut self.descricao_natureza, &d.natureza, "Natureza"),
+        ] {
+            if valores.len() > 1 {
+                completo &= config.append(campo, &valores.formatar(rotulo), label);
+            }
+        }
+        completo
+    }
 
-// --- 10 Colunas que a NF-e fornece para o CT-e ---
-#[derive(Debug, Default, Serialize, Deserialize, Clone)]
-pub struct NfeMetadata<'a> {
-    #[serde(rename = "Nome do Contribuinte : NF Item (Todos)")]
-    pub contribuinte_nome: Cow<'a, str>,
-    #[serde(rename = "Nome do Participante : NF (Todos)")]
-    pub participante_nome: Cow<'a, str>,
-    #[serde(rename = "Observações : NF (Todos)")]
-    pub observacoes: Cow<'a, str>,
-    #[serde(rename = "Número da DI : NF Item (Todos)")]
-    pub numero_di: Cow<'a, str>,
-    #[serde(rename = "Descrição CFOP : NF Item (Todos)")]
-    // CFOP é comum, mas o valor pode ser diferente
-    pub descricao_cfop: Cow<'a, str>,
-    #[serde(rename = "Descrição da Mercadoria/Serviço : NF Item (Todos)")]
-    pub descricao_mercadoria: Cow<'a, str>,
-    #[serde(rename = "Código NCM : NF Item (Todos)")]
-    pub ncm: Cow<'a, str>,
-    #[serde(rename = "Descrição NCM : NF Item (Todos)")]
-    pub descricao_ncm: Cow<'a, str>,
-    #[serde(rename = "CST COFINS Descrição : NF Item (Todos)")]
-    pub cst_descricao_cofins: Cow<'a, str>,
-    #[serde(rename = "CST PIS Descrição : NF Item (Todos)")]
-    pub cst_descricao_pis: Cow<'a, str>,
+    /// Extrai as colunas de origem da direção do perfil (metadados do resumo).
+    ///
+    /// Espaços múltiplos são reduzidos: apenas o que vai ser guardado na RAM.
+    pub fn extrair_metadados(&self, direcao: &DirecaoDeInjecao) -> Metadados {
+        let campos = self.campos_de_texto();
+        let mut metadados = Metadados::default();
+
+        for coluna in &direcao.colunas {
+            let mut valor = campos[coluna.origem].clone();
+            Colunas::sanitizar_campo(&mut valor);
+            metadados.inserir(coluna.origem, &valor);
+        }
+
+        metadados
+    }
 }
 
-impl<'a> NfeMetadata<'a> {
-    pub fn ncm_valido(&self) -> bool {
-        self.ncm.bytes().any(|b| matches!(b, b'1'..=b'9'))
+/// Início da sequência de anotações " [Info d..: ...]" ao final de `texto`
+/// (`texto.len()` se não houver): o texto que as precede é preservado,
+/// mesmo que contenha colchetes.
+fn inicio_das_anotacoes(texto: &str) -> usize {
+    let anotacoes: Vec<_> = RE_ANOTACAO.find_iter(texto).collect();
+    let mut inicio = texto.len();
+
+    // Cada anotação termina em ']' imediatamente antes da seguinte (ou do fim)
+    for anotacao in anotacoes.iter().rev() {
+        if anotacao.end() < inicio && texto[..inicio].ends_with(']') {
+            inicio = anotacao.start();
+        } else {
+            break;
+        }
     }
+
+    inicio
+}
+
+/// Acesso aos campos de texto de `Colunas` por posição: todos os campos,
+/// exceto a chave, na ordem das colunas (ver `PerfilDeInjecao`).
+macro_rules! campos_de_texto {
+    ($($campo:ident),+ $(,)?) => {
+        /// Número de campos de texto de `Colunas` (todas as colunas, exceto a chave).
+        pub const NUM_CAMPOS_DE_TEXTO: usize = [$(stringify!($campo)),+].len();
+
+        impl<'a> Colunas<'a> {
+            /// Campos de texto na ordem das colunas, sem a chave.
+            pub fn campos_de_texto(&self) -> [&Cow<'a, str>; NUM_CAMPOS_DE_TEXTO] {
+                [$(&self.$campo),+]
+            }
+
+            /// Versão mutável de `campos_de_texto`.
+            pub fn campos_de_texto_mut(&mut self) -> [&mut Cow<'a, str>; NUM_CAMPOS_DE_TEXTO] {
+                [$(&mut self.$campo),+]
+            }
+        }
+    };
 }
 
+campos_de_texto!(
+    contribuinte_cnpj,
+    contribuinte_nome,
+    entrada_ou_saida,
+    participante_cnpj,
+    participante_nome,
+    regime_tributario,
+    observacoes,
+    remetente_cnpj1,
+    remetente_cnpj2,
+    remetente_nome,
+    remetente_municipio,
+    tomador_papel1,
+    tomador_papel2,
+    tomador_cnpj1,
+    tomador_cnpj2,
+    inicio_estado,
+    inicio_municipio,
+    termino_estado,
+    termino_municipio,
+    destinatario_cnpj,
+    destinatario_nome,
+    local_entrega,
+    descricao_natureza,
+    cancelada,
+    origem,
+    natureza_bc,
+    modelo,
+    num_doc,
+    chave_de_acesso,
+    observacoes_gerais,
+    dia_emissao,
+    numero_di,
+    numero_item,
+    cfop,
+    descricao_cfop,
+    descricao_mercadoria,
+    ncm,
+    descricao_ncm,
+    aliq_cofins,
+    aliq_pis,
+    cst_descricao_cofins,
+    cst_descricao_pis,
+    valor_total,
+    valor_item,
+    valor_desconto,
+    valor_seguro,
+    valor_cofins,
+    valor_pis,
+    valor_ipi,
+    valor_bc_iss,
+    valor_iss,
+    aliq_icms,
+    valor_bc_icms,
+    valor_icms,
+    valor_icms_sub,
+);
+
 //----------------------------------------------------------------------------//
 //                                   Tests                                    //
 //----------------------------------------------------------------------------//
@@ -0,0 +1,320 @@
+use clap::ValueEnum;
+use csv::StringRecord;
+use rust_decimal::Decimal;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    Alteracao, Chave, Colunas, Config, DirecaoDeInjecao, DocMetadata, DocSummary, Informacoes,
+    Metadados, Modelo, ModoDeInjecao, PerfilDeInjecao, SpedError, SpedResult, decimal_to_str,
+    documentos_com_resumo, normalizar_coluna, valor_significativo,
+};
+
+/// Onde são gravadas as informações dos documentos relacionados (`--modo-saida`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ModoDeSaida {
+    /// Anotações " [Info do CT-e: ...]" acrescentadas às colunas existentes.
+    #[default]
+    Anotacoes,
+    /// Novas colunas ao final do cabeçalho: as colunas originais não são alteradas.
+    Colunas,
+}
+
+/// Colunas dos documentos relacionados: lista de chaves e valor total.
+const COLUNAS_DE_DOCUMENTOS: [&str; 6] = [
+    "CT-es relacionados",
+    "Valor total CT-es",
+    "NF-es relacionadas",
+    "Valor total NF-es",
+    "MDF-es relacionados",
+    "Valor total MDF-es",
+];
+
+/// Posições das colunas de chaves em `COLUNAS_DE_DOCUMENTOS`.
+const CTES: usize = 0;
+const NFES: usize = 2;
+const MDFES: usize = 4;
+
+/// Colunas dedicadas (`--modo-saida colunas`), acrescentadas ao final do cabeçalho:
+/// - chaves e valor total dos CT-es, NF-es e MDF-es relacionados;
+/// - colunas de `PerfilDeInjecao::cte_para_nfe` e a distribuição dos CT-es;
+/// - colunas de `PerfilDeInjecao::nfe_para_cte` e a distribuição das NF-es.
+///
+/// Uma célula reúne os valores distintos dos documentos relacionados,
+/// na ordem dos documentos, separados por `--separador`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColunasDedicadas {
+    /// Nomes das colunas, na ordem de gravação.
+    pub nomes: Vec<String>,
+    /// Posição da primeira coluna de `cte_para_nfe`.
+    inicio_cte: usize,
+    /// Posição da primeira coluna de `nfe_para_cte`.
+    inicio_nfe: usize,
+}
+
+impl ColunasDedicadas {
+    /// Monta as colunas a partir do perfil de injeção.
+    ///
+    /// Erro: nomes repetidos (ver o campo `nome` do perfil de injeção).
+    pub fn new(perfil: &PerfilDeInjecao) -> SpedResult<Self> {
+        let mut nomes: Vec<String> = COLUNAS_DE_DOCUMENTOS.map(String::from).to_vec();
+
+        let inicio_cte = nomes.len();
+        nomes.extend(colunas_da_direcao(&perfil.cte_para_nfe));
+
+        let inicio_nfe = nomes.len();
+        nomes.extend(colunas_da_direcao(&perfil.nfe_para_cte));
+
+        let mut vistos = HashSet::new();
+        if let Some(nome) = nomes
+            .iter()
+            .find(|nome| !vistos.insert(normalizar_coluna(nome)))
+        {
+            return Err(SpedError::Config(format!(
+                "Coluna dedicada repetida <{nome}>: informe nomes distintos no perfil de injeção"
+            )));
+        }
+
+        Ok(ColunasDedicadas {
+            nomes,
+            inicio_cte,
+            inicio_nfe,
+        })
+    }
+
+    /// Valores das colunas dedicadas da linha, conforme o modelo do documento
+    /// (as mesmas relações de `enriquecer_linha`), e a alteração correspondente.
+    ///
+    /// A linha não é alterada.
+    pub fn preencher(
+        &self,
+        row: &Colunas,
+        config: &Config,
+        info: &Informacoes,
+        cte_info: &HashMap<Chave, DocSummary>,
+        nfe_info: &HashMap<Chave, DocSummary>,
+    ) -> (Alteracao, Vec<String>) {
+        let mut valores = vec![String::new(); self.nomes.len()];
+        let chave = &row.chave;
+        let modelo = chave.modelo();
+
+        let alteracao = if row.chave_cancelada() {
+            Alteracao::Nenhuma
+        } else if modelo.is_nota() || modelo == Modelo::Mdfe {
+            let ctes = if modelo.is_nota() {
+                info.nfe_ctes.get(chave)
+            } else {
+                info.mdfe_ctes.get(chave)
+            };
+            self.preencher_documentos(&mut valores, CTES, ctes, cte_info, config)
+                .map_or(Alteracao::Nenhuma, |documentos| {
+                    let direcao = &config.perfil.cte_para_nfe;
+                    let completo = preencher_perfil(
+                        &mut valores[self.inicio_cte..],
+                        direcao,
+                        DocMetadata::cte,
+                        &documentos,
+                        config,
+                    );
+                    Alteracao::de_injecao(completo)
+                })
+        } else if modelo.is_conhecimento() {
+            let nfes = self
+                .preencher_documentos(
+                    &mut valores,
+                    NFES,
+                    info.cte_nfes.get(chave),
+                    nfe_info,
+                    config,
+                )
+                .map_or(Alteracao::Nenhuma, |documentos| {
+                    let direcao = &config.perfil.nfe_para_cte;
+                    let completo = preencher_perfil(
+                        &mut valores[self.inicio_nfe..],
+                        direcao,
+                        DocMetadata::nfe,
+                        &documentos,
+                        config,
+                    );
+                    Alteracao::de_injecao(completo)
+                });
+            let mdfes = self
+                .preencher_documentos(
+                    &mut valores,
+                    MDFES,
+                    info.cte_mdfes.get(chave),
+                    cte_info,
+                    config,
+                )
+                .map_or(Alteracao::Nenhuma, |_| Alteracao::Enriquecida);
+            nfes.max(mdfes)
+        } else {
+            Alteracao::Nenhuma
+        };
+
+        (alteracao, valores)
+    }
+
+    /// Preenche as chaves e o valor total dos documentos relacionados que possuem
+    /// resumo e os retorna (`None`, se não houver nenhum).
+    fn preencher_documentos<'b>(
+        &self,
+        valores: &mut [String],
+        posicao: usize,
+        relacionados: Option<&'b HashSet<Chave>>,
+        resumos: &'b HashMap<Chave, DocSummary>,
+        config: &Config,
+    ) -> Option<Vec<(&'b Chave, &'b DocSummary)>> {
+        let documentos = documentos_com_resumo(relacionados?, resumos);
+        if documentos.is_empty() {
+            return None;
+        }
+
+        let total: Decimal = documentos.iter().map(|d| d.1.item_valor_total).sum();
+        valores[posicao] = documentos
+            .iter()
+            .map(|d| d.0.as_str())
+            .collect::<Vec<_>>()
+            .join(&config.separador);
+        valores[posicao + 1] = decimal_to_str(total);
+
+        Some(documentos)
+    }
+}
+
+/// Verifica se o arquivo já possui alguma das colunas acrescentadas `nomes`
+/// (ex: arquivo gerado em execução anterior com `--modo-saida colunas`).
+pub fn verificar_colunas_acrescentadas(
+    nomes: &[String],
+    cabecalho: &StringRecord,
+    arquivo: &Path,
+) -> SpedResult<()> {
+    let nomes: HashSet<String> = nomes.iter().map(|n| normalizar_coluna(n)).collect();
+
+    match cabecalho
+        .iter()
+        .find(|coluna| nomes.contains(&normalizar_coluna(coluna)))
+    {
+        Some(coluna) => Err(SpedError::DuplicateColumnName {
+            arquivo: arquivo.to_path_buf(),
+            coluna: coluna.to_string(),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Nomes das colunas de uma direção do perfil, com o rótulo (ex: "UF início (CT-e)"),
+/// seguidos da coluna da distribuição.
+fn colunas_da_direcao(direcao: &DirecaoDeInjecao) -> Vec<String> {
+    direcao
+        .colunas
+        .iter()
+        .map(|coluna| coluna.nome.as_str())
+        .chain(["Distribuição"])
+        .map(|nome| format!("{nome} ({})", direcao.rotulo))
+        .collect()
+}
+
+/// Preenche as colunas de uma direção do perfil (`valores` inicia na primeira
+/// coluna da direção) com os metadados e a distribuição dos documentos.
+///
+/// `metadados`: `DocMetadata::cte` ou `DocMetadata::nfe`, conforme a direção.
+///
+/// Retorna `false` se algum valor foi descartado pelo limite `max_char`.
+fn preencher_perfil(
+    valores: &mut [String],
+    direcao: &DirecaoDeInjecao,
+    metadados: fn(&DocMetadata) -> Option<&Metadados>,
+    documentos: &[(&Chave, &DocSummary)],
+    config: &Config,
+) -> bool {
+    let documentos = &documentos[..documentos.len().min(direcao.limite(config.max_info))];
+    let mut completo = true;
+
+    for (coluna, valor) in direcao.colunas.iter().zip(valores.iter_mut()) {
+        let limite = coluna.max_info.unwrap_or(usize::MAX);
+        let valores_da_coluna = documentos
+            .iter()
+            .take(limite)
+            .filter_map(|(_, summary)| summary.metadata.as_ref().and_then(metadados))
+            .map(|m| m.valor(coluna.origem))
+            // Ex: o NCM "00000000" não é informado
+            .filter(|v| coluna.modo == ModoDeInjecao::Anotar || valor_significativo(v));
+
+        completo &= juntar(valor, valores_da_coluna, config);
+    }
+
+    let distribuicoes: Vec<String> = documentos
+        .iter()
+        .filter_map(|(_, summary)| summary.distribuicao.as_deref())
+        .flat_map(|d| {
+            [
+                (&d.ncm, "NCM"),
+                (&d.cfop, "CFOP"),
+                (&d.cst_pis, "CST PIS"),
+                (&d.cst_cofins, "CST COFINS"),
+                (&d.natureza, "Natureza"),
+            ]
+            .into_iter()
+            .filter(|(valores, _)| valores.len() > 1)
+            .map(|(valores, rotulo)| valores.formatar(rotulo))
+        })
+        .collect();
+
+    completo &= juntar(
+        &mut valores[direcao.colunas.len()],
+        distribuicoes.iter().map(String::as_str),
+        config,
+    );
+
+    completo
+}
+
+/// Junta na célula os valores distintos e não vazios, separados por `config.separador`.
+///
+/// Como em `Config::append`, um valor que excede `max_char` é descartado.
+/// Retorna `false` se algum valor foi descartado.
+fn juntar<'v>(
+    celula: &mut String,
+    valores: impl Iterator<Item = &'v str>,
+    config: &Config,
+) -> bool {
+    let mut distintos: Vec<&str> = Vec::new();
+    let mut tamanho = 0;
+    let mut completo = true;
+
+    for valor in valores.map(str::trim) {
+        if valor.is_empty() || distintos.contains(&valor) {
+            continue;
+        }
+
+        let separador = if distintos.is_empty() {
+            0
+        } else {
+            config.separador.chars().count()
+        };
+        let tamanho_valor = separador + valor.chars().count();
+
+        if tamanho + tamanho_valor < config.max_char {
+            tamanho += tamanho_valor;
+            distintos.push(valor);
+        } else {
+            completo = false;
+        }
+    }
+
+    *celula = distintos.join(&config.separador);
+    completo
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output colunas_dedicadas_tests
+#[cfg(test)]
+#[path = "tests/colunas_dedicadas_tests.rs"]
+mod colunas_dedicadas_tests;
@@ -0,0 +1,433 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    path::Path,
+};
+
+use crate::{
+    Chave, Colunas, DocSummary, Informacoes, RE_ANOTACAO, SpedResult, cnpj_ou_cpf, fmt_milhares,
+};
+
+/// Coluna das inconsistências do documento (`--coluna-consistencia`).
+pub const COLUNA_INCONSISTENCIAS: &str = "Inconsistências CT-e/NF-e";
+
+/// Data de emissão de um documento.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DataDeEmissao {
+    pub ano: u16,
+    pub mes: u8,
+    pub dia: u8,
+}
+
+impl DataDeEmissao {
+    /// Interpreta "dd/mm/aaaa" ou "aaaa-mm-dd", seguidos ou não do horário.
+    pub fn interpretar(texto: &str) -> Option<Self> {
+        let data = texto.trim().get(..10)?;
+        let (ano, mes, dia) = match data.as_bytes() {
+            [_, _, b'/', _, _, b'/', ..] => (&data[6..10], &data[3..5], &data[0..2]),
+            [_, _, _, _, b'-', _, _, b'-', ..] => (&data[0..4], &data[5..7], &data[8..10]),
+            _ => return None,
+        };
+
+        let data = DataDeEmissao {
+            ano: ano.parse().ok()?,
+            mes: mes.parse().ok()?,
+            dia: dia.parse().ok()?,
+        };
+        ((1..=12).contains(&data.mes) && (1..=31).contains(&data.dia)).then_some(data)
+    }
+
+    /// Primeiro dia do mês de emissão informado na chave (AAMM).
+    pub fn da_chave(chave: &Chave) -> Self {
+        DataDeEmissao {
+            ano: 2000 + u16::from(chave.ano()),
+            mes: chave.mes(),
+            dia: 1,
+        }
+    }
+}
+
+impl fmt::Display for DataDeEmissao {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}/{:02}/{:04}", self.dia, self.mes, self.ano)
+    }
+}
+
+/// Texto da coluna sem as anotações de execuções anteriores ("[Info d..]").
+fn sem_anotacao(texto: &str) -> &str {
+    RE_ANOTACAO
+        .find(texto)
+        .map_or(texto, |m| &texto[..m.start()])
+}
+
+/// CNPJ/CPF com 14 dígitos: o CPF é precedido de zeros, como na chave de acesso.
+fn documento(texto: &str) -> Option<String> {
+    cnpj_ou_cpf(sem_anotacao(texto)).map(|digitos| format!("{digitos:0>14}"))
+}
+
+/// Sigla da UF (duas letras, em maiúsculas).
+fn sigla_da_uf(texto: &str) -> Option<String> {
+    let sigla = sem_anotacao(texto).trim().to_uppercase();
+    (sigla.len() == 2 && sigla.chars().all(|c| c.is_ascii_alphabetic())).then_some(sigla)
+}
+
+/// Indica se a NF-e é de entrada (`Some(true)`) ou de saída (`Some(false)`).
+fn is_entrada(texto: &str) -> Option<bool> {
+    let texto = sem_anotacao(texto).trim().to_lowercase();
+    if texto.contains("entrada") || texto == "0" {
+        Some(true)
+    } else if texto.contains("saída") || texto.contains("saida") || texto == "1" {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Dados de um documento para a verificação de consistência entre CT-es e
+/// NF-es (`--consistencia`), acumulados entre os itens.
+///
+/// Os CNPJs/CPFs têm 14 dígitos (ver `documento`).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DadosDeConsistencia {
+    /// CT-e: remetentes. NF-e: participante das entradas (o emitente da chave
+    /// é considerado em `verificar`).
+    pub remetentes: BTreeSet<String>,
+    /// CT-e: destinatários. NF-e: participante das saídas ou contribuinte das entradas.
+    pub destinatarios: BTreeSet<String>,
+    /// Data de emissão mais antiga entre os itens.
+    pub emissao: Option<DataDeEmissao>,
+    /// UFs de início da prestação (apenas CT-es).
+    pub inicio: BTreeSet<String>,
+    /// UFs de término da prestação (apenas CT-es).
+    pub termino: BTreeSet<String>,
+}
+
+impl DadosDeConsistencia {
+    /// Dados da linha de CT-e ou NF-e.
+    pub fn ler(row: &Colunas) -> Self {
+        let mut dados = DadosDeConsistencia {
+            emissao: DataDeEmissao::interpretar(sem_anotacao(&row.dia_emissao)),
+            ..Default::default()
+        };
+
+        let modelo = row.chave.modelo();
+        if modelo.is_conhecimento() {
+            dados.remetentes.extend(documento(&row.remetente_cnpj1));
+            dados.remetentes.extend(documento(&row.remetente_cnpj2));
+            dados
+                .destinatarios
+                .extend(documento(&row.destinatario_cnpj));
+            dados.inicio.extend(sigla_da_uf(&row.inicio_estado));
+            dados.termino.extend(sigla_da_uf(&row.termino_estado));
+        } else if modelo.is_nota() {
+            match is_entrada(&row.entrada_ou_saida) {
+                Some(true) => {
+                    dados.remetentes.extend(documento(&row.participante_cnpj));
+                    dados
+                        .destinatarios
+                        .extend(documento(&row.contribuinte_cnpj));
+                }
+                Some(false) => {
+                    dados
+                        .destinatarios
+                        .extend(documento(&row.participante_cnpj));
+                }
+                None => {}
+            }
+        }
+
+        dados
+    }
+
+    /// Acumula os dados de outro item (ou resumo) do mesmo documento.
+    pub fn merge(&mut self, other: Self) {
+        self.remetentes.extend(other.remetentes);
+        self.destinatarios.extend(other.destinatarios);
+        self.inicio.extend(other.inicio);
+        self.termino.extend(other.termino);
+        self.emissao = match (self.emissao, other.emissao) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+/// Inconsistências verificadas entre um CT-e e uma NF-e relacionada.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Inconsistencia {
+    Remetente,
+    Destinatario,
+    DataDeEmissao,
+    Uf,
+}
+
+impl Inconsistencia {
+    pub fn descricao(self) -> &'static str {
+        match self {
+            Inconsistencia::Remetente => "Remetente do CT-e diferente do emitente da NF-e",
+            Inconsistencia::Destinatario => {
+                "Destinatário do CT-e diferente do destinatário da NF-e"
+            }
+            Inconsistencia::DataDeEmissao => "CT-e emitido antes da NF-e",
+            Inconsistencia::Uf => {
+                "UF do emitente da NF-e não é a UF de início nem a de término da prestação"
+            }
+        }
+    }
+}
+
+impl fmt::Display for Inconsistencia {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Inconsistencia::Remetente => f.pad("Remetente"),
+            Inconsistencia::Destinatario => f.pad("Destinatário"),
+            Inconsistencia::DataDeEmissao => f.pad("Data de emissão"),
+            Inconsistencia::Uf => f.pad("UF"),
+        }
+    }
+}
+
+/// Inconsistência de um par CT-e/NF-e, com os valores divergentes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Achado {
+    pub cte: Chave,
+    pub nfe: Chave,
+    pub inconsistencia: Inconsistencia,
+    pub valor_cte: String,
+    pub valor_nfe: String,
+}
+
+fn juntar(valores: &BTreeSet<String>) -> String {
+    valores
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Verifica o CT-e `cte` e a NF-e `nfe` relacionada (`dados_nfe`: `None` se a
+/// NF-e não constar dos arquivos).
+///
+/// Cada verificação exige os dados dos dois documentos; na falta deles, o emitente,
+/// a UF e o mês de emissão da NF-e são obtidos da chave:
+/// 1. Remetente: nenhum remetente do CT-e é o emitente da NF-e;
+/// 2. Destinatário: nenhum destinatário do CT-e é o destinatário da NF-e;
+/// 3. Data de emissão: o CT-e foi emitido antes da NF-e;
+/// 4. UF: a UF do emitente da NF-e não é a UF de início nem a de término da prestação.
+pub fn verificar(
+    cte: &Chave,
+    dados_cte: &DadosDeConsistencia,
+    nfe: &Chave,
+    dados_nfe: Option<&DadosDeConsistencia>,
+) -> Vec<Achado> {
+    let vazio = DadosDeConsistencia::default();
+    let dados_nfe = dados_nfe.unwrap_or(&vazio);
+    let mut achados = Vec::new();
+    let mut registrar = |inconsistencia, valor_cte, valor_nfe| {
+        achados.push(Achado {
+            cte: *cte,
+            nfe: *nfe,
+            inconsistencia,
+            valor_cte,
+            valor_nfe,
+        })
+    };
+
+    let mut emitentes = dados_nfe.remetentes.clone();
+    emitentes.insert(nfe.cnpj_cpf_emitente().to_string());
+    if !dados_cte.remetentes.is_empty() && dados_cte.remetentes.is_disjoint(&emitentes) {
+        registrar(
+            Inconsistencia::Remetente,
+            juntar(&dados_cte.remetentes),
+            juntar(&emitentes),
+        );
+    }
+
+    if !dados_cte.destinatarios.is_empty()
+        && !dados_nfe.destinatarios.is_empty()
+        && dados_cte
+            .destinatarios
+            .is_disjoint(&dados_nfe.destinatarios)
+    {
+        registrar(
+            Inconsistencia::Destinatario,
+            juntar(&dados_cte.destinatarios),
+            juntar(&dados_nfe.destinatarios),
+        );
+    }
+
+    let emissao_nfe = dados_nfe
+        .emissao
+        .unwrap_or_else(|| DataDeEmissao::da_chave(nfe));
+    if let Some(emissao_cte) = dados_cte.emissao
+        && emissao_cte < emissao_nfe
+    {
+        let valor_nfe = match dados_nfe.emissao {
+            Some(data) => data.to_string(),
+            None => format!("{:02}/20{:02} (chave)", nfe.mes(), nfe.ano()),
+        };
+        registrar(
+            Inconsistencia::DataDeEmissao,
+            emissao_cte.to_string(),
+            valor_nfe,
+        );
+    }
+
+    let prestacao_informada = !dados_cte.inicio.is_empty() || !dados_cte.termino.is_empty();
+    if let Some(uf) = nfe.uf()
+        && prestacao_informada
+        && !dados_cte.inicio.contains(uf)
+        && !dados_cte.termino.contains(uf)
+    {
+        registrar(
+            Inconsistencia::Uf,
+            format!(
+                "{} -> {}",
+                juntar(&dados_cte.inicio),
+                juntar(&dados_cte.termino)
+            ),
+            uf.to_string(),
+        );
+    }
+
+    achados
+}
+
+/// Linha do diagnóstico de consistência (`<doc>.consistencia.csv`).
+#[derive(Debug, Serialize)]
+struct LinhaDeDiagnostico<'a> {
+    #[serde(rename = "Chave do CT-e")]
+    cte: Chave,
+    #[serde(rename = "Chave da NF-e")]
+    nfe: Chave,
+    #[serde(rename = "Inconsistência")]
+    inconsistencia: String,
+    #[serde(rename = "Descrição")]
+    descricao: &'static str,
+    #[serde(rename = "Valor no CT-e")]
+    valor_cte: &'a str,
+    #[serde(rename = "Valor na NF-e")]
+    valor_nfe: &'a str,
+}
+
+/// Inconsistências entre os CT-es e as NF-es relacionadas (`--consistencia`).
+#[derive(Debug, Default)]
+pub struct Diagnostico {
+    /// Número de pares CT-e/NF-e verificados.
+    pub pares: usize,
+    /// Inconsistências, em ordem de chave do CT-e e da NF-e.
+    pub achados: Vec<Achado>,
+    /// Inconsistências de cada documento (CT-e ou NF-e).
+    por_documento: HashMap<Chave, BTreeSet<Inconsistencia>>,
+}
+
+impl Diagnostico {
+    /// Verifica os pares CT-e/NF-e de `info.cte_nfes` cujo CT-e possui os dados
+    /// de consistência no resumo (ver `verificar`).
+    pub fn new(
+        info: &Informacoes,
+        cte_info: &HashMap<Chave, DocSummary>,
+        nfe_info: &HashMap<Chave, DocSummary>,
+    ) -> Self {
+        let mut diagnostico = Diagnostico::default();
+
+        let mut ctes: Vec<(&Chave, &DadosDeConsistencia)> = info
+            .cte_nfes
+            .keys()
+            .filter_map(|cte| Some((cte, cte_info.get(cte)?.consistencia.as_deref()?)))
+            .collect();
+        ctes.sort_unstable_by_key(|(cte, _)| **cte);
+
+        for (cte, dados_cte) in ctes {
+            let mut nfes: Vec<&Chave> = info.cte_nfes[cte]
+                .iter()
+                .filter(|nfe| nfe.modelo().is_nota())
+                .collect();
+            nfes.sort_unstable();
+
+            for nfe in nfes {
+                diagnostico.pares += 1;
+                let dados_nfe = nfe_info
+                    .get(nfe)
+                    .and_then(|resumo| resumo.consistencia.as_deref());
+
+                for achado in verificar(cte, dados_cte, nfe, dados_nfe) {
+                    for chave in [achado.cte, achado.nfe] {
+                        diagnostico
+                            .por_documento
+                            .entry(chave)
+                            .or_default()
+                            .insert(achado.inconsistencia);
+                    }
+                    diagnostico.achados.push(achado);
+                }
+            }
+        }
+
+        diagnostico
+    }
+
+    /// Valor da coluna `COLUNA_INCONSISTENCIAS`: as inconsistências do documento,
+    /// separadas por `separador` (vazio se não houver).
+    pub fn coluna(&self, chave: &Chave, separador: &str) -> String {
+        self.por_documento
+            .get(chave)
+            .map(|inconsistencias| {
+                inconsistencias
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(separador)
+            })
+            .unwrap_or_default()
+    }
+
+    /// Grava as inconsistências em `path` (CSV delimitado por ';').
+    pub fn gravar(&self, path: &Path) -> SpedResult<()> {
+        let mut wtr = csv::WriterBuilder::new().delimiter(b';').from_path(path)?;
+        for achado in &self.achados {
+            wtr.serialize(LinhaDeDiagnostico {
+                cte: achado.cte,
+                nfe: achado.nfe,
+                inconsistencia: achado.inconsistencia.to_string(),
+                descricao: achado.inconsistencia.descricao(),
+                valor_cte: &achado.valor_cte,
+                valor_nfe: &achado.valor_nfe,
+            })?;
+        }
+        wtr.flush()?;
+        Ok(())
+    }
+
+    /// Imprime o número de pares verificados e de inconsistências por tipo.
+    pub fn imprimir(&self) {
+        let mut contagem: BTreeMap<Inconsistencia, usize> = BTreeMap::new();
+        for achado in &self.achados {
+            *contagem.entry(achado.inconsistencia).or_default() += 1;
+        }
+
+        println!(
+            " -> Consistência CT-e/NF-e: {} pares verificados, {} inconsistências",
+            fmt_milhares(self.pares),
+            fmt_milhares(self.achados.len())
+        );
+        for (inconsistencia, num) in contagem {
+            println!(
+                "      {:>7}: {}",
+                fmt_milhares(num),
+                inconsistencia.descricao()
+            );
+        }
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output consistencia_tests
+#[cfg(test)]
+#[path = "tests/consistencia_tests.rs"]
+mod consistencia_tests;
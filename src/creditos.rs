@@ -0,0 +1,444 @@
+use csv::StringRecord;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ArquivoMapeado, Chave, Colunas, Config, FormatoNumerico, Informacoes, MapaDeColunas,
+    SpedResult, decimal_to_str, fmt_milhares, leitor, raiz_do_documento, tomadores,
+};
+
+/// CST de PIS/COFINS com direito a crédito: 50 a 56 (básico) e 60 a 66 (presumido).
+const CST_COM_CREDITO: [u16; 14] = [50, 51, 52, 53, 54, 55, 56, 60, 61, 62, 63, 64, 65, 66];
+
+/// CST de PIS/COFINS de aquisição sem direito a crédito (70 a 75).
+const CST_SEM_CREDITO: [u16; 6] = [70, 71, 72, 73, 74, 75];
+
+/// CST de PIS/COFINS de mercadorias sem tributação: alíquota zero, isenção,
+/// suspensão, não incidência ou substituição (saídas 04 a 09 e entradas 70 a 75).
+const CST_SEM_TRIBUTACAO: [u16; 12] = [4, 5, 6, 7, 8, 9, 70, 71, 72, 73, 74, 75];
+
+/// Natureza da base de cálculo do crédito compatível com frete:
+/// 03 (serviços utilizados como insumo), 07 (armazenagem e frete na venda)
+/// e 14 (transporte de cargas - subcontratação).
+const NATUREZA_DE_FRETE: [u16; 3] = [3, 7, 14];
+
+/// CFOP de transferência entre estabelecimentos (três últimos dígitos).
+const CFOP_DE_TRANSFERENCIA: [u16; 8] = [151, 152, 155, 156, 408, 409, 552, 557];
+
+/// Classe do crédito de PIS/COFINS sobre o frete de um CT-e.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ClasseDeCredito {
+    Permitido,
+    Duvidoso,
+    NaoPermitido,
+}
+
+impl fmt::Display for ClasseDeCredito {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClasseDeCredito::Permitido => f.pad("Crédito permitido"),
+            ClasseDeCredito::Duvidoso => f.pad("Crédito duvidoso"),
+            ClasseDeCredito::NaoPermitido => f.pad("Crédito não permitido"),
+        }
+    }
+}
+
+/// Regras de classificação, na ordem de avaliação: a primeira regra
+/// satisfeita define a classe do CT-e (ver `classificar`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RegraDeCredito {
+    CteDeSaida,
+    CstSemDireitoACredito,
+    TomadorDiverso,
+    MercadoriasSemTributacao,
+    MercadoriasParcialmenteSemTributacao,
+    TransferenciaEntreEstabelecimentos,
+    TomadorNaoIdentificado,
+    NfesSemDados,
+    CstIndefinido,
+    NaturezaIncompativel,
+    CreditoPermitido,
+}
+
+impl RegraDeCredito {
+    pub fn classe(self) -> ClasseDeCredito {
+        match self {
+            RegraDeCredito::CteDeSaida
+            | RegraDeCredito::CstSemDireitoACredito
+            | RegraDeCredito::TomadorDiverso
+            | RegraDeCredito::MercadoriasSemTributacao => ClasseDeCredito::NaoPermitido,
+            RegraDeCredito::CreditoPermitido => ClasseDeCredito::Permitido,
+            _ => ClasseDeCredito::Duvidoso,
+        }
+    }
+
+    pub fn descricao(self) -> &'static str {
+        match self {
+            RegraDeCredito::CteDeSaida => "CT-e de saída: frete prestado pelo contribuinte",
+            RegraDeCredito::CstSemDireitoACredito => {
+                "CST PIS/COFINS do CT-e sem direito a crédito (70 a 75)"
+            }
+            RegraDeCredito::TomadorDiverso => "O contribuinte não é o tomador do serviço",
+            RegraDeCredito::MercadoriasSemTributacao => {
+                "Mercadorias com CST de alíquota zero, isenção, suspensão ou não incidência"
+            }
+            RegraDeCredito::MercadoriasParcialmenteSemTributacao => {
+                "Parte das NF-es com CST de alíquota zero, isenção, suspensão ou não incidência"
+            }
+            RegraDeCredito::TransferenciaEntreEstabelecimentos => {
+                "Transferência entre estabelecimentos (CFOP x151, x152, x408, x552...)"
+            }
+            RegraDeCredito::TomadorNaoIdentificado => "Tomador do serviço não identificado",
+            RegraDeCredito::NfesSemDados => "NF-es transportadas sem CST ou CFOP no arquivo",
+            RegraDeCredito::CstIndefinido => {
+                "CST PIS/COFINS do CT-e ausente ou sem definição do crédito (ex: 98, 99)"
+            }
+            RegraDeCredito::NaturezaIncompativel => {
+                "Natureza da base de cálculo ausente ou incompatível com frete (03, 07 ou 14)"
+            }
+            RegraDeCredito::CreditoPermitido => {
+                "CST com direito a crédito e natureza de frete; contribuinte tomador"
+            }
+        }
+    }
+}
+
+/// Código numérico no início de uma descrição (ex: "50 - Operação com Direito a Crédito").
+pub fn codigo_inicial(texto: &str) -> Option<u16> {
+    let texto = texto.trim();
+    let fim = texto
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(texto.len());
+    texto[..fim].parse().ok()
+}
+
+/// Insere o valor não vazio no conjunto.
+fn inserir(conjunto: &mut BTreeSet<String>, valor: &str) {
+    let valor = valor.trim();
+    if !valor.is_empty() {
+        conjunto.insert(valor.to_string());
+    }
+}
+
+/// Valores distintos dos itens de um CT-e do arquivo.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DadosDoCte {
+    pub valor: Decimal,
+    pub entrada_ou_saida: BTreeSet<String>,
+    pub natureza_bc: BTreeSet<String>,
+    pub cst_pis: BTreeSet<String>,
+    pub cst_cofins: BTreeSet<String>,
+    pub papeis_do_tomador: BTreeSet<String>,
+    /// Raízes dos CNPJs do contribuinte.
+    pub contribuintes: BTreeSet<String>,
+    /// Raízes dos CNPJs/CPFs do tomador, conforme o papel informado.
+    pub tomadores: BTreeSet<String>,
+}
+
+impl DadosDoCte {
+    pub fn registrar(&mut self, row: &Colunas, formato: FormatoNumerico) {
+        self.valor += row.valor_do_item(formato).valor().unwrap_or_default().abs();
+        inserir(&mut self.entrada_ou_saida, &row.entrada_ou_saida);
+        inserir(&mut self.natureza_bc, &row.natureza_bc);
+        inserir(&mut self.cst_pis, &row.cst_descricao_pis);
+        inserir(&mut self.cst_cofins, &row.cst_descricao_cofins);
+        self.contribuintes
+            .extend(raiz_do_documento(&row.contribuinte_cnpj));
+
+        inserir(&mut self.papeis_do_tomador, &row.tomador_papel1);
+        inserir(&mut self.papeis_do_tomador, &row.tomador_papel2);
+        self.tomadores.extend(
+            tomadores(row).filter_map(|(_, documento)| raiz_do_documento(documento.as_deref()?)),
+        );
+    }
+
+    /// CSTs de PIS e COFINS do CT-e (códigos).
+    fn csts(&self) -> impl Iterator<Item = Option<u16>> + '_ {
+        self.cst_pis
+            .iter()
+            .chain(&self.cst_cofins)
+            .map(|cst| codigo_inicial(cst))
+    }
+
+    /// Indica se o CT-e foi emitido pelo contribuinte (saída).
+    fn de_saida(&self) -> bool {
+        self.entrada_ou_saida.iter().any(|valor| {
+            let valor = valor.to_lowercase();
+            valor.contains("saída") || valor.contains("saida") || valor == "1"
+        })
+    }
+}
+
+/// CSTs e CFOPs distintos dos itens de uma NF-e transportada.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DadosDaNfe {
+    pub cst: BTreeSet<String>,
+    pub cfop: BTreeSet<String>,
+}
+
+impl DadosDaNfe {
+    pub fn registrar(&mut self, row: &Colunas) {
+        inserir(&mut self.cst, &row.cst_descricao_pis);
+        inserir(&mut self.cst, &row.cst_descricao_cofins);
+        inserir(&mut self.cfop, &row.cfop);
+    }
+
+    /// Todos os CSTs conhecidos da NF-e são de mercadorias sem tributação.
+    fn sem_tributacao(&self) -> bool {
+        !self.cst.is_empty()
+            && self
+                .cst
+                .iter()
+                .all(|cst| codigo_inicial(cst).is_some_and(|c| CST_SEM_TRIBUTACAO.contains(&c)))
+    }
+
+    /// Todos os CFOPs conhecidos da NF-e são de transferência.
+    fn transferencia(&self) -> bool {
+        !self.cfop.is_empty()
+            && self.cfop.iter().all(|cfop| {
+                codigo_inicial(cfop).is_some_and(|c| CFOP_DE_TRANSFERENCIA.contains(&(c % 1000)))
+            })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.cst.is_empty() && self.cfop.is_empty()
+    }
+}
+
+/// Classifica o crédito sobre o frete do CT-e: retorna a primeira regra satisfeita
+/// (ver `RegraDeCredito`), considerando as NF-es transportadas presentes no arquivo.
+///
+/// 1. Crédito não permitido: CT-e de saída, CST sem direito a crédito,
+///    contribuinte diferente do tomador ou mercadorias sem tributação;
+/// 2. Crédito duvidoso: parte das mercadorias sem tributação, transferências,
+///    tomador não identificado, NF-es sem dados, CST ou natureza indefinidos;
+/// 3. Crédito permitido: nenhuma das regras anteriores.
+pub fn classificar(cte: &DadosDoCte, nfes: &[&DadosDaNfe]) -> RegraDeCredito {
+    let nfes: Vec<&DadosDaNfe> = nfes.iter().copied().filter(|n| !n.is_empty()).collect();
+    let csts: Vec<Option<u16>> = cte.csts().collect();
+    let cst_em = |lista: &[u16], cst: Option<u16>| cst.is_some_and(|c| lista.contains(&c));
+
+    if cte.de_saida() {
+        RegraDeCredito::CteDeSaida
+    } else if csts.iter().any(|cst| cst_em(&CST_SEM_CREDITO, *cst)) {
+        RegraDeCredito::CstSemDireitoACredito
+    } else if !cte.tomadores.is_empty()
+        && !cte.contribuintes.is_empty()
+        && cte.tomadores.is_disjoint(&cte.contribuintes)
+    {
+        RegraDeCredito::TomadorDiverso
+    } else if !nfes.is_empty() && nfes.iter().all(|n| n.sem_tributacao()) {
+        RegraDeCredito::MercadoriasSemTributacao
+    } else if nfes.iter().any(|n| n.sem_tributacao()) {
+        RegraDeCredito::MercadoriasParcialmenteSemTributacao
+    } else if !nfes.is_empty() && nfes.iter().all(|n| n.transferencia()) {
+        RegraDeCredito::TransferenciaEntreEstabelecimentos
+    } else if cte.tomadores.is_empty() {
+        RegraDeCredito::TomadorNaoIdentificado
+    } else if nfes.is_empty() {
+        RegraDeCredito::NfesSemDados
+    } else if csts.is_empty() || !csts.iter().all(|cst| cst_em(&CST_COM_CREDITO, *cst)) {
+        RegraDeCredito::CstIndefinido
+    } else if cte.natureza_bc.is_empty()
+        || !cte.natureza_bc.iter().all(|natureza| {
+            codigo_inicial(natureza).is_some_and(|c| NATUREZA_DE_FRETE.contains(&c))
+        })
+    {
+        RegraDeCredito::NaturezaIncompativel
+    } else {
+        RegraDeCredito::CreditoPermitido
+    }
+}
+
+/// CT-es e NF-es transportadas lidos dos arquivos.
+#[derive(Debug, Default)]
+pub struct DadosDeCredito {
+    /// CT-es do arquivo principal, em ordem de chave.
+    pub ctes: BTreeMap<Chave, DadosDoCte>,
+    /// NF-es relacionadas a algum CT-e (arquivo principal e de referência).
+    pub nfes: HashMap<Chave, DadosDaNfe>,
+}
+
+impl DadosDeCredito {
+    /// Lê as linhas de `path`: os CT-es são lidos apenas do arquivo principal.
+    ///
+    /// Linhas canceladas são ignoradas e as anotações de execuções anteriores
+    /// são removidas antes da leitura (ver `Colunas::limpar_anotacoes`).
+    pub fn ler(
+        &mut self,
+        path: &Path,
+        config: &Config,
+        info: &Informacoes,
+        principal: bool,
+    ) -> SpedResult<()> {
+        let arquivo = ArquivoMapeado::abrir(path, config.codificacao)?;
+        let inicio = arquivo.inicio_dos_dados();
+        let mapa = MapaDeColunas::new(&arquivo.cabecalho()?, config, path)?;
+        let mut rdr = leitor(&arquivo.bytes()[inicio..]);
+        let mut record = StringRecord::new();
+        let mut buf = StringRecord::new();
+
+        while rdr.read_record(&mut record)? {
+            let posicao = inicio + record.position().map_or(0, |p| p.byte() as usize);
+            let mut row: Colunas = mapa
+                .deserializar(&record, &mut buf)
+                .map_err(|e| arquivo.erro_detalhado(posicao, record.as_byte_record(), e))?;
+
+            if row.chave_cancelada() {
+                continue;
+            }
+            row.limpar_anotacoes();
+
+            let modelo = row.chave.modelo();
+            if principal && modelo.is_conhecimento() {
+                self.ctes
+                    .entry(row.chave)
+                    .or_default()
+                    .registrar(&row, config.formato_numerico);
+            } else if modelo.is_nota() && info.nfe_ctes.contains_key(&row.chave) {
+                self.nfes.entry(row.chave).or_default().registrar(&row);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Linha do relatório de créditos (`<doc>.creditos_frete.csv`).
+#[derive(Debug, Serialize)]
+struct LinhaDeCredito {
+    #[serde(rename = "Chave do CT-e")]
+    chave: Chave,
+    #[serde(rename = "Classe")]
+    classe: String,
+    #[serde(rename = "Regra")]
+    regra: &'static str,
+    #[serde(rename = "Valor do CT-e")]
+    valor: String,
+    #[serde(rename = "Entrada/Saída")]
+    entrada_ou_saida: String,
+    #[serde(rename = "Natureza da BC")]
+    natureza_bc: String,
+    #[serde(rename = "CST PIS")]
+    cst_pis: String,
+    #[serde(rename = "CST COFINS")]
+    cst_cofins: String,
+    #[serde(rename = "Papel do tomador")]
+    papel_do_tomador: String,
+    #[serde(rename = "NF-es relacionadas")]
+    nfes: usize,
+    #[serde(rename = "NF-es com dados")]
+    nfes_com_dados: usize,
+    #[serde(rename = "CST PIS/COFINS das NF-es")]
+    cst_das_nfes: String,
+    #[serde(rename = "CFOP das NF-es")]
+    cfop_das_nfes: String,
+}
+
+/// Relatório de elegibilidade do crédito de PIS/COFINS sobre fretes
+/// (subcomando `creditos-frete`).
+///
+/// Cada CT-e do arquivo é classificado por `classificar`, com as NF-es
+/// transportadas (`Informacoes::cte_nfes`) lidas do arquivo e dos arquivos
+/// de referência. Grava `<doc>.creditos_frete.csv` e retorna o seu caminho.
+pub fn gerar_relatorio_de_creditos(config: &Config, info: &Informacoes) -> SpedResult<PathBuf> {
+    println!("--- Créditos de PIS/COFINS sobre fretes ---");
+
+    let mut dados = DadosDeCredito::default();
+    dados.ler(&config.doc_path, config, info, true)?;
+    for referencia in &config.referencias {
+        dados.ler(referencia, config, info, false)?;
+    }
+
+    let output_path = config.doc_path.with_extension("creditos_frete.csv");
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .from_path(&output_path)?;
+    let mut contagem: BTreeMap<RegraDeCredito, usize> = BTreeMap::new();
+    let juntar = |valores: &BTreeSet<String>| {
+        valores
+            .iter()
+            .map(String::as_str)
+            .collect::<Vec<_>>()
+            .join(&config.separador)
+    };
+
+    for (chave, cte) in &dados.ctes {
+        let relacionadas = info.cte_nfes.get(chave);
+        let nfes: Vec<&DadosDaNfe> = relacionadas
+            .into_iter()
+            .flatten()
+            .filter_map(|nfe| dados.nfes.get(nfe))
+            .collect();
+
+        let regra = classificar(cte, &nfes);
+        *contagem.entry(regra).or_default() += 1;
+
+        let mut cst_das_nfes = BTreeSet::new();
+        let mut cfop_das_nfes = BTreeSet::new();
+        for nfe in &nfes {
+            cst_das_nfes.extend(nfe.cst.iter().cloned());
+            cfop_das_nfes.extend(nfe.cfop.iter().cloned());
+        }
+
+        wtr.serialize(LinhaDeCredito {
+            chave: *chave,
+            classe: regra.classe().to_string(),
+            regra: regra.descricao(),
+            valor: decimal_to_str(cte.valor),
+            entrada_ou_saida: juntar(&cte.entrada_ou_saida),
+            natureza_bc: juntar(&cte.natureza_bc),
+            cst_pis: juntar(&cte.cst_pis),
+            cst_cofins: juntar(&cte.cst_cofins),
+            papel_do_tomador: juntar(&cte.papeis_do_tomador),
+            nfes: relacionadas.map_or(0, |r| r.len()),
+            nfes_com_dados: nfes.len(),
+            cst_das_nfes: juntar(&cst_das_nfes),
+            cfop_das_nfes: juntar(&cfop_das_nfes),
+        })?;
+    }
+    wtr.flush()?;
+
+    imprimir_contagem(&contagem);
+    println!(
+        " -> Relatório de {} CT-es gravado em <{}>\n",
+        fmt_milhares(dados.ctes.len()),
+        output_path.display()
+    );
+
+    Ok(output_path)
+}
+
+/// Imprime o número de CT-es por classe e, em cada classe, por regra.
+fn imprimir_contagem(contagem: &BTreeMap<RegraDeCredito, usize>) {
+    let mut por_classe: BTreeMap<ClasseDeCredito, Vec<(RegraDeCredito, usize)>> = BTreeMap::new();
+    for (regra, num) in contagem {
+        por_classe
+            .entry(regra.classe())
+            .or_default()
+            .push((*regra, *num));
+    }
+
+    for (classe, regras) in por_classe {
+        let total: usize = regras.iter().map(|(_, num)| num).sum();
+        println!(" -> {classe}: {}", fmt_milhares(total));
+        for (regra, num) in regras {
+            println!("      {:>7}: {}", fmt_milhares(num), regra.descricao());
+        }
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output creditos_tests
+#[cfg(test)]
+#[path = "tests/creditos_tests.rs"]
+mod creditos_tests;
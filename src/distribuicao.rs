@@ -0,0 +1,127 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+
+use crate::Colunas;
+
+/// Valores distintos de um campo, ponderados pelo valor dos itens.
+///
+/// Os valores são mantidos na ordem em que aparecem no arquivo; ao atingir
+/// o limite de valores por documento, o peso dos novos valores é somado em `outros`.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ValoresDistintos {
+    pub valores: Vec<(String, Decimal)>,
+    pub outros: Decimal,
+}
+
+impl ValoresDistintos {
+    /// Soma `peso` ao `valor` (campos vazios são ignorados).
+    pub fn registrar(&mut self, valor: &str, peso: Decimal, limite: usize) {
+        let valor = valor.trim();
+        if valor.is_empty() {
+            return;
+        }
+
+        match self.valores.iter().position(|(v, _)| v == valor) {
+            Some(i) => self.valores[i].1 += peso,
+            None if self.valores.len() < limite => self.valores.push((valor.to_string(), peso)),
+            None => self.outros += peso,
+        }
+    }
+
+    /// Número de valores distintos (inclusive `outros`).
+    pub fn len(&self) -> usize {
+        self.valores.len() + usize::from(!self.outros.is_zero())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Formata os valores em ordem decrescente de peso, com o percentual do total.
+    ///
+    /// Exemplo: "NCM 1234 (70%), 5678 (30%)"
+    pub fn formatar(&self, rotulo: &str) -> String {
+        let total: Decimal = self.valores.iter().map(|(_, p)| *p).sum::<Decimal>() + self.outros;
+
+        let mut ordenados: Vec<&(String, Decimal)> = self.valores.iter().collect();
+        ordenados.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+        let percentual = |peso: Decimal| {
+            (peso * Decimal::ONE_HUNDRED / total)
+                .round_dp_with_strategy(0, RoundingStrategy::MidpointAwayFromZero)
+        };
+
+        let mut partes: Vec<String> = ordenados
+            .iter()
+            .map(|(valor, peso)| format!("{valor} ({}%)", percentual(*peso)))
+            .collect();
+
+        if !self.outros.is_zero() {
+            partes.push(format!("outros ({}%)", percentual(self.outros)));
+        }
+
+        format!("{rotulo} {}", partes.join(", "))
+    }
+}
+
+/// Distribuição dos valores de campos selecionados entre todos os itens do documento.
+///
+/// Complementa os metadados do item representativo: uma NF-e com itens de
+/// NCMs diferentes informa todos eles (ex: "NCM 1234 (70%), 5678 (30%)").
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Distribuicao {
+    pub ncm: ValoresDistintos,
+    pub cfop: ValoresDistintos,
+    pub cst_pis: ValoresDistintos,
+    pub cst_cofins: ValoresDistintos,
+    pub natureza: ValoresDistintos,
+}
+
+/// Campos de um item considerados na `Distribuicao`.
+#[derive(Debug, Default, Clone)]
+pub struct CamposDistintos {
+    pub ncm: String,
+    pub cfop: String,
+    pub cst_pis: String,
+    pub cst_cofins: String,
+    pub natureza: String,
+}
+
+impl CamposDistintos {
+    pub fn new(row: &Colunas) -> Self {
+        // NCM sem dígito significativo (ex: "00000000") não é informado
+        let ncm = if row.ncm.bytes().any(|b| matches!(b, b'1'..=b'9')) {
+            row.ncm.to_string()
+        } else {
+            String::new()
+        };
+
+        CamposDistintos {
+            ncm,
+            cfop: row.cfop.to_string(),
+            cst_pis: row.cst_descricao_pis.to_string(),
+            cst_cofins: row.cst_descricao_cofins.to_string(),
+            natureza: row.descricao_natureza.to_string(),
+        }
+    }
+}
+
+impl Distribuicao {
+    /// Soma o valor do item (`peso`) aos valores dos seus campos.
+    pub fn registrar(&mut self, campos: &CamposDistintos, peso: Decimal, limite: usize) {
+        self.ncm.registrar(&campos.ncm, peso, limite);
+        self.cfop.registrar(&campos.cfop, peso, limite);
+        self.cst_pis.registrar(&campos.cst_pis, peso, limite);
+        self.cst_cofins.registrar(&campos.cst_cofins, peso, limite);
+        self.natureza.registrar(&campos.natureza, peso, limite);
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output distribuicao_tests
+#[cfg(test)]
+#[path = "tests/distribuicao_tests.rs"]
+mod distribuicao_tests;
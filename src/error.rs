@@ -6,6 +6,9 @@ pub type SpedResult<T> = Result<T, SpedError>;
 
 #[derive(Error, Debug)]
 pub enum SpedError {
+    #[error("Modo lote: {falhas} de {total} arquivos falharam!")]
+    BatchFailed { falhas: usize, total: usize },
+
     #[error("Erro de configuração: {0}")]
     Config(String),
 
@@ -52,6 +55,26 @@ pub enum SpedError {
         arquivo: PathBuf,
     },
 
+    #[error("Erro na exportação JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error(
+        "Arquivo <{arquivo}> sem colunas obrigatórias: <{colunas}>\n\
+        Informe nomes alternativos com a opção --mapa-colunas"
+    )]
+    MissingColumns { arquivo: PathBuf, colunas: String },
+
     #[error("Regex Error: {0}")]
     Regex(#[from] regex::Error),
+
+    #[error(
+        "Arquivo de relacionamento ({tipo}) não encontrado!\n\
+        Arquivo: {arquivo:?}\n\
+        Informe o caminho com a opção --{opcao}"
+    )]
+    RelationFileNotFound {
+        tipo: &'static str,
+        opcao: &'static str,
+        arquivo: PathBuf,
+    },
 }
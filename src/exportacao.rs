@@ -0,0 +1,122 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    BUFFER, Chave, DocSummary, Informacoes, KeyMap, Modelo, SpedResult, TributosDoCte,
+    fmt_milhares, tributos_por_nfe,
+};
+
+/// Registro de uma chave no arquivo JSON Lines: relações e resumo.
+///
+/// Os campos do resumo (`num_de_itens`, `item_valor_total`, `item_valor_maximo`,
+/// `tributos`, `modalidade` e `metadata`) são achatados no registro e omitidos se a chave
+/// não constar do CSV.
+///
+/// `tributos_ctes_rateados`: parcela da NF-e nos tributos dos CT-es relacionados
+/// (ver `tributos_por_nfe`), igual à soma das colunas de tributos dos seus itens.
+#[derive(Debug, Serialize)]
+pub struct RegistroJson<'a> {
+    pub chave: Chave,
+    pub modelo: Modelo,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub nfes: Vec<Chave>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ctes: Vec<Chave>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ctes_complementares: Vec<Chave>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub mdfes: Vec<Chave>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tributos_ctes_rateados: Option<TributosDoCte>,
+    #[serde(flatten)]
+    pub resumo: Option<&'a DocSummary>,
+}
+
+/// Retorna as chaves relacionadas em ordem crescente.
+fn relacionadas(map: &KeyMap, chave: &Chave) -> Vec<Chave> {
+    let mut chaves: Vec<Chave> = map
+        .get(chave)
+        .map(|set| set.iter().copied().collect())
+        .unwrap_or_default();
+    chaves.sort_unstable();
+    chaves
+}
+
+/// Exporta o grafo de relações e os resumos (`DocSummary`) em JSON Lines.
+///
+/// Cada linha é um objeto JSON de uma chave presente nas relações ou nos resumos,
+/// em ordem crescente de chave. Retorna o número de registros gravados.
+///
+/// Os valores decimais são exportados como texto (ex: `"1234.56"`), sem a perda
+/// de precisão da conversão para ponto flutuante.
+pub fn exportar_jsonl(
+    path: &Path,
+    info: &Informacoes,
+    cte_info: &HashMap<Chave, DocSummary>,
+    nfe_info: &HashMap<Chave, DocSummary>,
+) -> SpedResult<usize> {
+    // BTreeSet: ordem determinística das linhas
+    let chaves: BTreeSet<Chave> = [
+        &info.nfe_ctes,
+        &info.cte_nfes,
+        &info.cte_complementar,
+        &info.mdfe_ctes,
+        &info.cte_mdfes,
+    ]
+    .into_iter()
+    .flat_map(|map| map.keys())
+    .chain(cte_info.keys())
+    .chain(nfe_info.keys())
+    .copied()
+    .collect();
+
+    let tributos = tributos_por_nfe(info, cte_info, nfe_info);
+    let mut writer = BufWriter::with_capacity(BUFFER, File::create(path)?);
+
+    for chave in &chaves {
+        let modelo = chave.modelo();
+
+        let registro = RegistroJson {
+            chave: *chave,
+            modelo,
+            nfes: relacionadas(&info.cte_nfes, chave),
+            ctes: if modelo == Modelo::Mdfe {
+                relacionadas(&info.mdfe_ctes, chave)
+            } else {
+                relacionadas(&info.nfe_ctes, chave)
+            },
+            ctes_complementares: relacionadas(&info.cte_complementar, chave),
+            mdfes: relacionadas(&info.cte_mdfes, chave),
+            tributos_ctes_rateados: tributos.get(chave).copied(),
+            resumo: cte_info.get(chave).or_else(|| nfe_info.get(chave)),
+        };
+
+        serde_json::to_writer(&mut writer, &registro)?;
+        writer.write_all(b"\n")?;
+    }
+
+    writer.flush()?;
+
+    println!(
+        " -> Exportados {} registros JSON em <{}>.",
+        fmt_milhares(chaves.len()),
+        path.display()
+    );
+
+    Ok(chaves.len())
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output exportacao_tests
+#[cfg(test)]
+#[path = "tests/exportacao_tests.rs"]
+mod exportacao_tests;
@@ -2,13 +2,12 @@ use rayon::prelude::*;
 use regex::Regex;
 use std::{
     collections::{HashMap, HashSet},
-    fmt::Display,
     fs::File,
     io::{BufRead, BufReader},
     path::Path,
 };
 
-use crate::{Chave, KeyMap, SpedError, SpedResult, fmt_milhares};
+use crate::{Chave, ChavesInvalidas, KeyMap, Modelo, SpedError, SpedResult, fmt_milhares};
 
 // O estado (os HashMaps) deve ser uma struct separada ou variáveis no main
 #[derive(Debug, Default)]
@@ -16,43 +15,76 @@ pub struct Informacoes {
     pub nfe_ctes: HashMap<Chave, HashSet<Chave>>,
     pub cte_nfes: HashMap<Chave, HashSet<Chave>>,
     pub cte_complementar: HashMap<Chave, HashSet<Chave>>,
-    pub numero_total_de_linhas: usize,
+    pub mdfe_ctes: HashMap<Chave, HashSet<Chave>>,
+    pub cte_mdfes: HashMap<Chave, HashSet<Chave>>,
 }
 
 impl Informacoes {
     /// Carrega as tabelas de relacionamento em paralelo e processa a transitividade.
-    pub fn from_files<P>(path_cte_nfes: P, path_complementares: P) -> SpedResult<Self>
+    ///
+    /// Os arquivos de CTes complementares e de MDFe -> CTes são opcionais.
+    /// Sem o arquivo de complementares (`None`), as etapas de transitividade
+    /// e propagação são ignoradas.
+    pub fn from_files<P>(
+        path_cte_nfes: P,
+        path_complementares: Option<P>,
+        path_mdfe_ctes: Option<P>,
+    ) -> SpedResult<Self>
     where
-        P: AsRef<Path> + Display + Clone + Send + Sync + 'static,
+        P: AsRef<Path>,
     {
         println!("--- Carregando Tabelas de Relacionamento ---");
 
+        let path_cte_nfes = path_cte_nfes.as_ref();
+        let path_complementares = path_complementares.as_ref().map(|p| p.as_ref());
+        let path_mdfe_ctes = path_mdfe_ctes.as_ref().map(|p| p.as_ref());
+
         // 1. Carregamento inicial (IO)
-        // Use join do rayon para carregar os dois arquivos em paralelo!
-        // rayon::join executa as duas closures em threads diferentes.
-        // Capturamos os dois resultados.
-        let (cte_nfes, cte_complementar) = {
-            let (res1, res2) = rayon::join(
+        // Use join do rayon para carregar os arquivos em paralelo!
+        // rayon::join executa as closures em threads diferentes.
+        // Capturamos os resultados.
+        let (cte_nfes, (cte_complementar, mdfe_ctes)) = {
+            let (res1, (res2, res3)) = rayon::join(
                 || Self::ler_todas_as_nfes_deste_cte(path_cte_nfes),
-                || Self::ler_chave_complementar_deste_cte(path_complementares),
+                || {
+                    rayon::join(
+                        || {
+                            path_complementares
+                                .map(Self::ler_chave_complementar_deste_cte)
+                                .transpose()
+                        },
+                        || {
+                            path_mdfe_ctes
+                                .map(Self::ler_todos_os_ctes_deste_mdfe)
+                                .transpose()
+                        },
+                    )
+                },
             );
-            (res1?, res2?)
+            (res1?, (res2?, res3?))
         };
 
         let mut info = Self {
             cte_nfes,
-            cte_complementar,
+            mdfe_ctes: mdfe_ctes.unwrap_or_default(),
             ..Default::default()
         };
 
-        // 2. Expansão das relações (Transitividade)
-        info.expandir_cte_complementar();
+        if let Some(cte_complementar) = cte_complementar {
+            info.cte_complementar = cte_complementar;
 
-        // 3. Propagação de NFes para CTes complementares
-        info.propagar_nfes_para_cte_complementares();
+            // 2. Expansão das relações (Transitividade)
+            info.expandir_cte_complementar();
+
+            // 3. Propagação de NFes para CTes complementares
+            info.propagar_nfes_para_cte_complementares();
+        } else {
+            println!(" -> Arquivo de CTes complementares ausente: transitividade ignorada.");
+        }
 
-        // 4. Geração do índice invertido (NFe -> CTes)
+        // 4. Geração dos índices invertidos (NFe -> CTes e CTe -> MDFes)
         info.get_nfe_ctes();
+        info.get_cte_mdfes();
 
         println!(
             " -> Relações NFe -> CTes carregadas: {}",
@@ -62,17 +94,61 @@ impl Informacoes {
             " -> Relações CTe -> NFes carregadas: {}",
             fmt_milhares(info.cte_nfes.len())
         );
+        if !info.mdfe_ctes.is_empty() {
+            println!(
+                " -> Relações MDFe -> CTes carregadas: {}",
+                fmt_milhares(info.mdfe_ctes.len())
+            );
+        }
 
         Ok(info)
     }
 
+    /// Lê as relações CTe -> NFes.
+    ///
+    /// Cada linha inicia com a chave de um conhecimento (CT-e ou CT-e OS)
+    /// seguida das chaves das notas transportadas (NF-e ou NFC-e).
     pub fn ler_todas_as_nfes_deste_cte<P>(path: P) -> SpedResult<KeyMap>
     where
-        P: AsRef<Path> + Clone + Display,
+        P: AsRef<Path>,
+    {
+        Self::ler_relacoes(
+            path.as_ref(),
+            "CTe -> NFes",
+            Modelo::is_conhecimento,
+            Modelo::is_nota,
+        )
+    }
+
+    /// Lê as relações MDFe -> CTes.
+    ///
+    /// Cada linha inicia com a chave do MDF-e seguida das chaves
+    /// dos conhecimentos (CT-e ou CT-e OS) manifestados.
+    pub fn ler_todos_os_ctes_deste_mdfe<P>(path: P) -> SpedResult<KeyMap>
+    where
+        P: AsRef<Path>,
     {
-        let file = File::open(&path).map_err(|e| SpedError::IoReader {
+        Self::ler_relacoes(
+            path.as_ref(),
+            "MDFe -> CTes",
+            |modelo| modelo == Modelo::Mdfe,
+            Modelo::is_conhecimento,
+        )
+    }
+
+    /// Lê um arquivo de relações em que a primeira chave de cada linha é a origem
+    /// e as demais chaves são os documentos relacionados.
+    ///
+    /// Apenas origens e destinos aceitos pelos filtros de `Modelo` são retidos.
+    fn ler_relacoes(
+        path: &Path,
+        label: &str,
+        origem: fn(Modelo) -> bool,
+        destino: fn(Modelo) -> bool,
+    ) -> SpedResult<KeyMap> {
+        let file = File::open(path).map_err(|e| SpedError::IoReader {
             source: e,
-            arquivo: path.as_ref().to_path_buf(),
+            arquivo: path.to_path_buf(),
         })?;
 
         let reader = BufReader::new(file);
@@ -81,50 +157,64 @@ impl Informacoes {
         // \b garante que pegamos apenas sequências de 44 dígitos isoladas.
         let re = Regex::new(r"\b\d{44}\b")?;
 
-        let hash: KeyMap = reader
+        let (hash, invalidas): (KeyMap, ChavesInvalidas) = reader
             .lines()
             .par_bridge() // Transforma o iterador sequencial em paralelo
-            .filter_map(|line_result| {
-                let line = line_result.ok()?;
-                // Coleta todas as chaves da linha
-                let mut chaves = re.find_iter(&line).filter_map(|m| Chave::new(m.as_str()));
-
-                let cte = chaves.next()?;
-                if !cte.is_cte() {
-                    return None;
-                }
+            .fold(
+                <(KeyMap, ChavesInvalidas)>::default,
+                |(mut acc, mut invalidas), line_result| {
+                    let Ok(line) = line_result else {
+                        return (acc, invalidas);
+                    };
+
+                    // Coleta todas as chaves da linha
+                    let mut chaves = re.find_iter(&line).filter_map(|m| Chave::new(m.as_str()));
+
+                    let Some(chave_origem) = chaves.next() else {
+                        return (acc, invalidas);
+                    };
+
+                    // Chaves com DV inválido são registradas e descartadas
+                    if !origem(chave_origem.modelo()) || !invalidas.verificar(chave_origem) {
+                        return (acc, invalidas);
+                    }
 
-                let nfes: HashSet<Chave> = chaves.filter(|c| c.is_nfe()).collect();
+                    let relacionadas: HashSet<Chave> = chaves
+                        .filter(|c| destino(c.modelo()) && invalidas.verificar(*c))
+                        .collect();
 
-                if nfes.is_empty() {
-                    None
-                } else {
-                    Some((cte, nfes))
-                }
-            })
-            // Combina os resultados, se houver o mesmo CT-e em linhas diferentes.
-            .fold(HashMap::new, |mut acc: KeyMap, (cte, nfes)| {
-                acc.entry(cte).or_default().extend(nfes);
-                acc
-            })
-            .reduce(HashMap::new, |mut map1, map2| {
-                for (k, v) in map2 {
-                    map1.entry(k).or_default().extend(v);
-                }
-                map1
-            });
+                    // Combina os resultados, se houver a mesma origem em linhas diferentes.
+                    if !relacionadas.is_empty() {
+                        acc.entry(chave_origem).or_default().extend(relacionadas);
+                    }
+
+                    (acc, invalidas)
+                },
+            )
+            .reduce(
+                <(KeyMap, ChavesInvalidas)>::default,
+                |(mut map1, mut inv1), (map2, inv2)| {
+                    for (k, v) in map2 {
+                        map1.entry(k).or_default().extend(v);
+                    }
+                    inv1.merge(inv2);
+                    (map1, inv1)
+                },
+            );
 
-        Self::print_log("CTe -> NFes", &hash, path);
+        Self::print_log(label, &hash, path);
+        invalidas.print_log(&path.display().to_string());
         Ok(hash)
     }
 
     pub fn ler_chave_complementar_deste_cte<P>(path: P) -> SpedResult<KeyMap>
     where
-        P: AsRef<Path> + Clone + std::fmt::Display,
+        P: AsRef<Path>,
     {
-        let file = File::open(&path).map_err(|e| SpedError::IoReader {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|e| SpedError::IoReader {
             source: e,
-            arquivo: path.as_ref().to_path_buf(),
+            arquivo: path.to_path_buf(),
         })?;
 
         let reader = BufReader::new(file);
@@ -132,12 +222,12 @@ impl Informacoes {
 
         // Utilizamos try_fold para construir sub-mapas em cada thread
         // e try_reduce para mesclá-los de forma eficiente.
-        let hash: KeyMap = reader
+        let (hash, invalidas): (KeyMap, ChavesInvalidas) = reader
             .lines()
             .par_bridge() // Paraleliza o iterador de linhas
             .try_fold(
-                HashMap::new,
-                |mut acc: KeyMap, line_result| -> SpedResult<KeyMap> {
+                <(KeyMap, ChavesInvalidas)>::default,
+                |(mut acc, mut invalidas), line_result| -> SpedResult<(KeyMap, ChavesInvalidas)> {
                     let line = line_result?;
 
                     // Extrai chaves e converte para a struct Chave (ignora as inválidas)
@@ -145,25 +235,41 @@ impl Informacoes {
 
                     // Esperamos pelo menos duas chaves válidas na linha
                     if let (Some(cte), Some(comp)) = (matches.next(), matches.next()) {
-                        // Regra de negócio: Ambos devem ser CT-e (57) e não podem ser iguais
-                        if cte.is_cte() && comp.is_cte() && cte != comp {
-                            // Inserção bidirecional: Chave é Copy, então não precisamos de .clone()
-                            acc.entry(cte).or_default().insert(comp);
-                            acc.entry(comp).or_default().insert(cte);
+                        // Regra de negócio: Ambos devem ser conhecimentos (57 ou 67)
+                        // e não podem ser iguais
+                        if cte.modelo().is_conhecimento()
+                            && comp.modelo().is_conhecimento()
+                            && cte != comp
+                        {
+                            // Verifica o DV das duas chaves (sem curto-circuito),
+                            // para que ambas sejam registradas quando inválidas
+                            let cte_valida = invalidas.verificar(cte);
+                            let comp_valida = invalidas.verificar(comp);
+
+                            if cte_valida && comp_valida {
+                                // Inserção bidirecional: Chave é Copy, então não precisamos de .clone()
+                                acc.entry(cte).or_default().insert(comp);
+                                acc.entry(comp).or_default().insert(cte);
+                            }
                         }
                     }
-                    Ok(acc)
+                    Ok((acc, invalidas))
                 },
             )
-            .try_reduce(HashMap::new, |mut map_a, map_b| {
-                // Mescla os mapas das threads. extend() em HashSets é otimizado.
-                for (key, values) in map_b {
-                    map_a.entry(key).or_default().extend(values);
-                }
-                Ok(map_a)
-            })?;
+            .try_reduce(
+                <(KeyMap, ChavesInvalidas)>::default,
+                |(mut map_a, mut inv_a), (map_b, inv_b)| {
+                    // Mescla os mapas das threads. extend() em HashSets é otimizado.
+                    for (key, values) in map_b {
+                        map_a.entry(key).or_default().extend(values);
+                    }
+                    inv_a.merge(inv_b);
+                    Ok((map_a, inv_a))
+                },
+            )?;
 
         Self::print_log("CTe <-> CTe Complementar", &hash, path);
+        invalidas.print_log(&path.display().to_string());
         Ok(hash)
     }
 
@@ -363,18 +469,36 @@ impl Informacoes {
         }
     }
 
+    /// Gera o índice invertido CTe -> MDFes a partir de `mdfe_ctes`.
+    pub fn get_cte_mdfes(&mut self) {
+        self.cte_mdfes.clear();
+
+        for (&mdfe, ctes) in &self.mdfe_ctes {
+            for &cte in ctes {
+                self.cte_mdfes.entry(cte).or_default().insert(mdfe);
+            }
+        }
+    }
+
     #[inline]
-    fn print_log<P>(label: &str, map: &KeyMap, path: P)
-    where
-        P: Display,
-    {
+    fn print_log(label: &str, map: &KeyMap, path: &Path) {
         let num_de_items = map.values().map(|v| v.len()).sum::<usize>();
         println!(
             "Encontrado {:>6} chaves ({:>6} relações {}) no arquivo <{}>.",
             fmt_milhares(map.len()),
             fmt_milhares(num_de_items),
             label,
-            path
+            path.display()
         );
     }
 }
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output informacoes_tests
+#[cfg(test)]
+#[path = "tests/informacoes_tests.rs"]
+mod informacoes_tests;
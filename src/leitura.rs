@@ -0,0 +1,262 @@
+use csv::{ByteRecord, StringRecord};
+use memchr::{memchr, memchr2};
+use memmap2::Mmap;
+use std::{
+    borrow::Cow,
+    fmt::Display,
+    fs::File,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use crate::{Codificacao, SpedError, SpedResult};
+
+/// Linhas por fatia: unidade de trabalho de cada thread nas duas passagens.
+///
+/// Fatias pequenas equilibram a carga entre as threads;
+/// os blocos (`--chunk-size`) agrupam várias fatias.
+pub const LINHAS_POR_FATIA: usize = 4 * 1024;
+
+/// Conteúdo do arquivo em UTF-8.
+enum Conteudo {
+    /// Arquivo vazio (não é possível mapear 0 bytes).
+    Vazio,
+    /// Arquivo UTF-8 mapeado em memória (sem cópia).
+    Mapeado(Mmap),
+    /// Arquivo em outra codificação, convertido para UTF-8 na abertura.
+    Decodificado(Vec<u8>),
+}
+
+/// Arquivo CSV mapeado em memória (mmap).
+///
+/// As threads interpretam diretamente fatias do arquivo mapeado,
+/// sem cópias para buffers intermediários.
+/// Arquivos Windows-1252 são convertidos para UTF-8 em memória.
+pub struct ArquivoMapeado {
+    path: PathBuf,
+    conteudo: Conteudo,
+    /// Codificação do arquivo (detectada ou informada em `--encoding`).
+    codificacao: Codificacao,
+    /// Posição do primeiro byte após o cabeçalho.
+    inicio_dos_dados: usize,
+}
+
+impl ArquivoMapeado {
+    /// Mapeia o arquivo em memória, resolve a codificação e localiza o fim do cabeçalho.
+    pub fn abrir(path: &Path, codificacao: Codificacao) -> SpedResult<Self> {
+        let erro_de_leitura = |e| SpedError::IoReader {
+            source: e,
+            arquivo: path.to_path_buf(),
+        };
+
+        let file = File::open(path).map_err(erro_de_leitura)?;
+        let tamanho = file.metadata().map_err(erro_de_leitura)?.len();
+
+        let (conteudo, codificacao) = if tamanho == 0 {
+            (Conteudo::Vazio, codificacao.detectar(&[]))
+        } else {
+            // SAFETY: o arquivo é apenas lido e não deve ser alterado por outro
+            // processo durante a execução (o arquivo de saída é sempre outro).
+            let mmap = unsafe { Mmap::map(&file) }.map_err(erro_de_leitura)?;
+            let codificacao = codificacao.detectar(&mmap);
+            let conteudo = match codificacao.decodificar(&mmap) {
+                Cow::Borrowed(_) => Conteudo::Mapeado(mmap),
+                Cow::Owned(utf8) => Conteudo::Decodificado(utf8),
+            };
+            (conteudo, codificacao)
+        };
+
+        let mut arquivo = ArquivoMapeado {
+            path: path.to_path_buf(),
+            conteudo,
+            codificacao,
+            inicio_dos_dados: 0,
+        };
+        arquivo.inicio_dos_dados = fim_do_registro(arquivo.bytes(), codificacao.tamanho_do_bom());
+
+        Ok(arquivo)
+    }
+
+    /// Conteúdo do arquivo em UTF-8 (inclusive o BOM, se houver).
+    pub fn bytes(&self) -> &[u8] {
+        match &self.conteudo {
+            Conteudo::Vazio => &[],
+            Conteudo::Mapeado(mmap) => mmap,
+            Conteudo::Decodificado(utf8) => utf8,
+        }
+    }
+
+    /// Codificação do arquivo (nunca `Auto`).
+    pub fn codificacao(&self) -> Codificacao {
+        self.codificacao
+    }
+
+    /// Bytes do cabeçalho, sem o BOM e incluindo o terminador de linha.
+    pub fn cabecalho_bruto(&self) -> &[u8] {
+        &self.bytes()[self.codificacao.tamanho_do_bom()..self.inicio_dos_dados]
+    }
+
+    /// Posição do primeiro byte após o cabeçalho.
+    pub fn inicio_dos_dados(&self) -> usize {
+        self.inicio_dos_dados
+    }
+
+    /// Nomes das colunas (cabeçalho vazio para arquivo vazio).
+    pub fn cabecalho(&self) -> SpedResult<StringRecord> {
+        let mut headers = StringRecord::new();
+        leitor(self.cabecalho_bruto()).read_record(&mut headers)?;
+        Ok(headers)
+    }
+
+    /// Terminador de linha do arquivo (do cabeçalho): as linhas alteradas
+    /// são gravadas com o mesmo terminador das linhas copiadas.
+    pub fn terminador(&self) -> csv::Terminator {
+        if self.cabecalho_bruto().ends_with(b"\r\n") {
+            csv::Terminator::CRLF
+        } else {
+            csv::Terminator::Any(b'\n')
+        }
+    }
+
+    /// Divide os dados (após o cabeçalho) em fatias de até `linhas_por_fatia` registros.
+    ///
+    /// As fronteiras sempre coincidem com o fim de um registro: quebras de linha
+    /// dentro de campos entre aspas não dividem o registro.
+    pub fn fatias(&self, linhas_por_fatia: usize) -> Vec<Range<usize>> {
+        let bytes = self.bytes();
+        let mut fatias = Vec::new();
+        let mut inicio = self.inicio_dos_dados;
+
+        while inicio < bytes.len() {
+            let mut fim = inicio;
+            for _ in 0..linhas_por_fatia.max(1) {
+                fim = fim_do_registro(bytes, fim);
+                if fim == bytes.len() {
+                    break;
+                }
+            }
+            fatias.push(inicio..fim);
+            inicio = fim;
+        }
+
+        fatias
+    }
+
+    /// Converte um erro na linha iniciada em `posicao` em `SpedError::CsvDetailed`.
+    ///
+    /// O número da linha é calculado apenas no caso de erro.
+    pub fn erro_detalhado(
+        &self,
+        posicao: usize,
+        record: &ByteRecord,
+        erro: impl Display,
+    ) -> SpedError {
+        let bytes = self.bytes();
+        let linha_numero = memchr::memchr_iter(b'\n', &bytes[..posicao.min(bytes.len())]).count();
+
+        SpedError::CsvDetailed {
+            arquivo: self.path.clone(),
+            linha_numero: linha_numero as u64 + 1,
+            conteudo: record
+                .iter()
+                .map(String::from_utf8_lossy)
+                .collect::<Vec<_>>()
+                .join(";"),
+            erro: erro.to_string(),
+        }
+    }
+}
+
+/// Posições das linhas registradas na Passagem 1 para a Passagem 2.
+///
+/// Somente as linhas com documentos relacionados (`candidatas`) são
+/// interpretadas na Passagem 2; as demais são copiadas diretamente
+/// do arquivo mapeado, sem nova leitura CSV.
+#[derive(Debug, Default, Clone)]
+pub struct IndiceDeLinhas {
+    /// Número de linhas de dados (sem o cabeçalho).
+    pub linhas: usize,
+    /// Linhas já enriquecidas em execução anterior (nunca são candidatas).
+    pub ja_enriquecidas: usize,
+    /// Faixas de bytes das linhas candidatas, na ordem do arquivo.
+    pub candidatas: Vec<Range<usize>>,
+}
+
+/// Reader CSV (sem cabeçalho) sobre uma fatia do arquivo mapeado.
+///
+/// O número de campos é verificado pelo chamador em relação ao cabeçalho,
+/// pois cada fatia é lida por um Reader independente.
+pub fn leitor(bytes: &[u8]) -> csv::Reader<&[u8]> {
+    csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .has_headers(false)
+        .flexible(true)
+        .trim(csv::Trim::All) // Remove espaços nas extremidades
+        .quoting(true)
+        .double_quote(true)
+        .from_reader(bytes)
+}
+
+/// Lê o primeiro registro de `bytes` em `record`.
+pub fn ler_registro(bytes: &[u8], record: &mut ByteRecord) -> csv::Result<bool> {
+    leitor(bytes).read_byte_record(record)
+}
+
+/// Faixas dos registros de `bytes`, cada uma com o seu terminador de linha.
+pub fn registros(bytes: &[u8]) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut inicio = 0;
+    std::iter::from_fn(move || {
+        (inicio < bytes.len()).then(|| {
+            let fim = fim_do_registro(bytes, inicio);
+            let faixa = inicio..fim;
+            inicio = fim;
+            faixa
+        })
+    })
+}
+
+/// Posição do primeiro byte após o registro iniciado em `inicio`
+/// (após o '\n' final ou o fim do arquivo).
+///
+/// Aspas só iniciam um campo entre aspas no início do campo, como no crate csv:
+/// `TUBO 1/2"` é um campo comum, e `"A;B\nC"` é um único campo.
+fn fim_do_registro(bytes: &[u8], inicio: usize) -> usize {
+    let mut i = inicio;
+
+    while i < bytes.len() {
+        // Início de campo
+        if bytes[i] == b'"' {
+            i += 1;
+            // Campo entre aspas: termina na aspa que não é seguida de outra ("")
+            loop {
+                match memchr(b'"', &bytes[i..]) {
+                    Some(p) if bytes.get(i + p + 1) == Some(&b'"') => i += p + 2,
+                    Some(p) => {
+                        i += p + 1;
+                        break;
+                    }
+                    None => return bytes.len(),
+                }
+            }
+        }
+
+        // Restante do campo: até o próximo delimitador ou fim de linha
+        match memchr2(b';', b'\n', &bytes[i..]) {
+            Some(p) if bytes[i + p] == b'\n' => return i + p + 1,
+            Some(p) => i += p + 1,
+            None => return bytes.len(),
+        }
+    }
+
+    bytes.len()
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output leitura_tests
+#[cfg(test)]
+#[path = "tests/leitura_tests.rs"]
+mod leitura_tests;
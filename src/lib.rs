@@ -1,14 +1,42 @@
 mod args;
+mod cache;
 mod chave;
+mod codificacao;
 mod colunas;
+mod colunas_dedicadas;
+mod consistencia;
+mod creditos;
+mod distribuicao;
 mod error;
+mod exportacao;
 mod informacoes;
+mod leitura;
+mod lote;
+mod mapeamento;
+mod modalidade;
+mod numeros;
+mod perfil;
 mod processor;
+mod rateio;
 mod regex;
+mod relatorio;
+mod tributos;
 mod utils;
 
 pub use self::{
-    args::*, chave::*, colunas::*, error::*, informacoes::*, processor::*, regex::*, utils::*,
+    args::*, cache::*, chave::*, codificacao::*, colunas::*, colunas_dedicadas::*, consistencia::*,
+    creditos::*, distribuicao::*, error::*, exportacao::*, informacoes::*, leitura::*, lote::*,
+    mapeamento::*, modalidade::*, numeros::*, perfil::*, processor::*, rateio::*, regex::*,
+    relatorio::*, tributos::*, utils::*,
 };
 
-pub const BUFFER: usize = 1014 * 1024; // 1MB
+pub const BUFFER: usize = 1024 * 1024; // 1 MiB
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Funções auxiliares compartilhadas pelos testes (`mock_chave`, `chave_valida`, `dec`).
+#[cfg(test)]
+#[path = "tests/comum.rs"]
+mod comum;
@@ -0,0 +1,190 @@
+use rayon::prelude::*;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{SpedError, SpedResult, fmt_milhares};
+
+/// Resultado do processamento de um arquivo no modo lote.
+#[derive(Debug, Clone)]
+pub struct ResultadoArquivo {
+    pub arquivo: PathBuf,
+    /// Arquivo gerado (`None` se o processamento falhou).
+    pub saida: Option<PathBuf>,
+    pub linhas: usize,
+    pub alteracoes: usize,
+    /// Mensagem de erro, se o processamento do arquivo falhou.
+    pub erro: Option<String>,
+}
+
+impl ResultadoArquivo {
+    pub fn falhou(&self) -> bool {
+        self.erro.is_some()
+    }
+}
+
+/// Expande as entradas de `--doc-path` em uma lista ordenada de arquivos CSV.
+///
+/// Cada entrada pode ser:
+/// - um arquivo;
+/// - um diretório: todos os `*.csv` do diretório (não recursivo);
+/// - um padrão glob, ex: `'dados/ZZZ-*.csv'`.
+///
+/// Arquivos `*.modificado.csv` (saídas de execuções anteriores) são ignorados
+/// em diretórios e padrões glob. Arquivos repetidos são considerados uma única vez.
+pub fn expandir_entradas(entradas: &[PathBuf]) -> SpedResult<Vec<PathBuf>> {
+    let mut arquivos = Vec::new();
+
+    for entrada in entradas {
+        if entrada.is_file() {
+            arquivos.push(entrada.clone());
+        } else if entrada.is_dir() {
+            let mut csvs = Vec::new();
+            for item in fs::read_dir(entrada)? {
+                let path = item?.path();
+                if path.is_file() && is_csv_de_entrada(&path) {
+                    csvs.push(path);
+                }
+            }
+            csvs.sort();
+            arquivos.extend(csvs);
+        } else {
+            let padrao = entrada.to_string_lossy();
+            let csvs = glob::glob(&padrao)
+                .map_err(|e| SpedError::Config(format!("Padrão inválido <{padrao}>: {e}")))?
+                .filter_map(Result::ok)
+                .filter(|path| path.is_file() && is_csv_de_entrada(path))
+                .collect::<Vec<_>>();
+
+            if csvs.is_empty() {
+                return Err(SpedError::IoReader {
+                    source: std::io::ErrorKind::NotFound.into(),
+                    arquivo: entrada.clone(),
+                });
+            }
+            arquivos.extend(csvs);
+        }
+    }
+
+    // Remove repetições mantendo a ordem de entrada
+    let mut vistos = std::collections::HashSet::new();
+    arquivos.retain(|path| vistos.insert(path.clone()));
+
+    if arquivos.is_empty() {
+        return Err(SpedError::EfdFileNotFound);
+    }
+
+    Ok(arquivos)
+}
+
+/// Arquivo `.csv` que não é saída de uma execução anterior.
+fn is_csv_de_entrada(path: &Path) -> bool {
+    let nome = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    nome.ends_with(".csv") && !nome.ends_with(".modificado.csv")
+}
+
+/// Processa os arquivos em grupos de `simultaneos` arquivos por vez (em paralelo).
+///
+/// Limitar o número de arquivos simultâneos limita a memória ocupada pelos
+/// resumos (`DocSummary`) de cada arquivo. A ordem dos resultados é a de `arquivos`.
+///
+/// `processar` retorna o arquivo gerado, o número de linhas e o de alterações.
+pub fn processar_lote<F>(
+    arquivos: &[PathBuf],
+    simultaneos: usize,
+    processar: F,
+) -> Vec<ResultadoArquivo>
+where
+    F: Fn(&Path) -> SpedResult<(PathBuf, usize, usize)> + Sync,
+{
+    arquivos
+        .chunks(simultaneos.max(1))
+        .flat_map(|grupo| {
+            grupo
+                .par_iter()
+                .map(|arquivo| match processar(arquivo) {
+                    Ok((saida, linhas, alteracoes)) => ResultadoArquivo {
+                        arquivo: arquivo.clone(),
+                        saida: Some(saida),
+                        linhas,
+                        alteracoes,
+                        erro: None,
+                    },
+                    Err(e) => ResultadoArquivo {
+                        arquivo: arquivo.clone(),
+                        saida: None,
+                        linhas: 0,
+                        alteracoes: 0,
+                        erro: Some(e.to_string()),
+                    },
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Imprime a tabela consolidada do lote: linhas e alterações por arquivo.
+pub fn imprimir_tabela_do_lote(resultados: &[ResultadoArquivo]) {
+    let largura = resultados
+        .iter()
+        .map(|r| r.arquivo.display().to_string().chars().count())
+        .chain(std::iter::once("Arquivo".len()))
+        .max()
+        .unwrap_or(0);
+
+    println!("--- Resumo do Lote ({} arquivos) ---", resultados.len());
+    println!(
+        " {:<largura$} | {:>12} | {:>12} | Situação",
+        "Arquivo", "Linhas", "Alterações"
+    );
+    println!(
+        " {:-<largura$}-+-{:-<12}-+-{:-<12}-+-{:-<8}",
+        "", "", "", ""
+    );
+
+    for r in resultados {
+        let situacao = r.erro.as_deref().map_or("OK".to_string(), |e| {
+            // Apenas a primeira linha da mensagem de erro
+            format!("ERRO: {}", e.lines().next().unwrap_or_default())
+        });
+        println!(
+            " {:<largura$} | {:>12} | {:>12} | {}",
+            r.arquivo.display().to_string(),
+            fmt_milhares(r.linhas),
+            fmt_milhares(r.alteracoes),
+            situacao
+        );
+    }
+
+    let linhas: usize = resultados.iter().map(|r| r.linhas).sum();
+    let alteracoes: usize = resultados.iter().map(|r| r.alteracoes).sum();
+    let falhas = resultados.iter().filter(|r| r.falhou()).count();
+
+    println!(
+        " {:-<largura$}-+-{:-<12}-+-{:-<12}-+-{:-<8}",
+        "", "", "", ""
+    );
+    println!(
+        " {:<largura$} | {:>12} | {:>12} | {} falha(s)",
+        "Total",
+        fmt_milhares(linhas),
+        fmt_milhares(alteracoes),
+        falhas
+    );
+    println!();
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output lote_tests
+#[cfg(test)]
+#[path = "tests/lote_tests.rs"]
+mod lote_tests;
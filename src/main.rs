@@ -1,9 +1,16 @@
 use adicionar_info_de_ctes_em_nfes::{
-    Informacoes, SpedResult, clear_screen, enriquecer_arquivo, get_config, get_summaries,
-    imprimir_versao_do_programa, sobrescrever_arquivo,
+    Comando, Config, Informacoes, Relatorio, SpedError, SpedResult, SummaryPair,
+    carregar_informacoes, clear_screen, enriquecer_arquivo, exportar_jsonl, fmt_milhares,
+    gerar_relatorio_de_creditos, get_config, get_summaries_de_referencia, get_summaries_e_indice,
+    imprimir_tabela_do_lote, imprimir_versao_do_programa, limpar_arquivo, processar_lote,
+    sobrescrever_arquivo, substituir_arquivo,
 };
 use execution_time::ExecutionTime;
-use std::{fs, process};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
 
 /*
 05.adicionar_info_de_CTes_em_NFes.pl -i 'ZZZ-874918-Info da Receita sobre o Contribuinte.csv'
@@ -40,6 +47,12 @@ fn run() -> SpedResult<()> {
     // 1. Configurações (Parâmetros da CLI) (O "O QUE" fazer)
     let config = get_config()?;
 
+    // Pool global de threads do Rayon (0: número de núcleos)
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(config.threads)
+        .build_global()
+        .map_err(|e| SpedError::Config(e.to_string()))?;
+
     clear_screen(config.clear)?;
     imprimir_versao_do_programa();
 
@@ -48,15 +61,83 @@ fn run() -> SpedResult<()> {
     }
 
     // 2. Informações (O "COM O QUE" trabalhar)
-    // Toda a complexidade de arquivos texto e transitividade está escondida aqui
-    let mut info = Informacoes::from_files(
-        "cte_nfes.txt",
-        "transporte_subcontratado-chaves_complementares_dos_CTes.txt",
-    )?;
+    // Toda a complexidade de arquivos texto, transitividade e cache está escondida aqui.
+    // No modo --limpar as relações não são carregadas.
+    let info = if config.limpar {
+        None
+    } else {
+        Some(carregar_informacoes(&config)?)
+    };
+
+    // Subcomando creditos-frete: apenas o relatório, os arquivos não são enriquecidos
+    if let (Some(Comando::CreditosFrete), Some(info)) = (config.comando, &info) {
+        for arquivo in &config.doc_paths {
+            let config_arquivo = Config {
+                doc_path: arquivo.clone(),
+                ..config.clone()
+            };
+            gerar_relatorio_de_creditos(&config_arquivo, info)?;
+        }
+        timer.print_elapsed_time();
+        return Ok(());
+    }
+
+    // Resumos dos arquivos de referência: coletados uma única vez
+    let referencia = if config.limpar || config.referencias.is_empty() {
+        None
+    } else {
+        Some(get_summaries_de_referencia(&config.referencias, &config)?)
+    };
+
+    // Modo lote: as relações são carregadas uma única vez para todos os arquivos
+    if config.doc_paths.len() > 1 {
+        return processar_lote_de_arquivos(&config, info.as_ref(), referencia.as_ref(), &timer);
+    }
 
     // 3. Processamento (A execução propriamente dita)
+    let (output_path, relatorio) = processar_arquivo(&config, info.as_ref(), referencia.as_ref())?;
+
+    // Modo simulação: apenas o relatório, sem arquivo gravado
+    if config.dry_run {
+        timer.print_elapsed_time();
+        return Ok(());
+    }
+
+    println!("Arquivo: {:?}", output_path.display());
+    println!("Número total de linhas: {}\n", relatorio.linhas);
+
+    // 9. Finalização
+    timer.print_elapsed_time();
+    println!();
+
+    finalizar(&config, &output_path, relatorio.alteracoes())
+}
+
+/// Executa as passagens sobre `config.doc_path`: limpeza (`--limpar`)
+/// ou coleta de resumos seguida do enriquecimento.
+///
+/// Os resumos de `referencia` complementam os resumos do arquivo.
+fn processar_arquivo(
+    config: &Config,
+    info: Option<&Informacoes>,
+    referencia: Option<&SummaryPair>,
+) -> SpedResult<(PathBuf, Relatorio)> {
+    // Modo --limpar: restaura o arquivo sem utilizar as relações
+    let Some(info) = info else {
+        return limpar_arquivo(config);
+    };
+
     println!("--- Passagem 1: Coletando resumos de documentos ---");
-    let (cte_info, nfe_info) = get_summaries(&config.doc_path, &config)?;
+    let (pair, indice) = get_summaries_e_indice(&config.doc_path, config, Some(info))?;
+    let (mut cte_info, mut nfe_info) = (pair.ctes, pair.nfes);
+
+    if let Some(referencia) = referencia {
+        let acrescentadas = referencia.acrescentar_ausentes(&mut cte_info, &mut nfe_info);
+        println!(
+            " -> Resumos acrescentados dos arquivos de referência: {}",
+            fmt_milhares(acrescentadas)
+        );
+    }
 
     if config.verbose {
         println!("\n--- Primeiros 10 CTes encontrados ---\n");
@@ -70,21 +151,89 @@ fn run() -> SpedResult<()> {
         }
     }
 
+    // Exportação opcional do grafo de relações e dos resumos (JSON Lines)
+    if config.json {
+        let json_path = config.doc_path.with_extension("relacoes.jsonl");
+        exportar_jsonl(&json_path, info, &cte_info, &nfe_info)?;
+    }
+
     // 8. Passagem 2: Enriquecimento
-    let (output_path, alteracoes) = enriquecer_arquivo(&config, &mut info, &cte_info, &nfe_info)?;
+    let (output_path, relatorio) = enriquecer_arquivo(config, info, &cte_info, &nfe_info, &indice)?;
 
-    println!("Arquivo: {:?}", output_path.display());
-    println!("Número total de linhas: {}\n", info.numero_total_de_linhas);
+    if config.dry_run {
+        println!("Número total de linhas: {}\n", relatorio.linhas);
+        relatorio.imprimir();
+    } else if config.verbose {
+        relatorio.imprimir();
+    }
 
-    // 9. Finalização
+    Ok((output_path, relatorio))
+}
+
+/// Processa todos os arquivos de `config.doc_paths` (em paralelo, até `config.jobs`
+/// arquivos por vez), finaliza cada arquivo e imprime a tabela consolidada.
+///
+/// Retorna `SpedError::BatchFailed` se algum arquivo falhou.
+fn processar_lote_de_arquivos(
+    config: &Config,
+    info: Option<&Informacoes>,
+    referencia: Option<&SummaryPair>,
+    timer: &ExecutionTime,
+) -> SpedResult<()> {
+    println!(
+        "--- Modo lote: {} arquivos ({} simultâneos) ---\n",
+        config.doc_paths.len(),
+        config.jobs
+    );
+
+    let mut resultados = processar_lote(&config.doc_paths, config.jobs, |arquivo| {
+        let config_arquivo = Config {
+            doc_path: arquivo.to_path_buf(),
+            ..config.clone()
+        };
+        let (output_path, relatorio) = processar_arquivo(&config_arquivo, info, referencia)?;
+        Ok((output_path, relatorio.linhas, relatorio.alteracoes()))
+    });
+
+    // A finalização é sequencial: pode perguntar ao usuário se sobrescreve o original
+    if !config.dry_run {
+        for resultado in resultados.iter_mut().filter(|r| !r.falhou()) {
+            let Some(output_path) = resultado.saida.clone() else {
+                continue;
+            };
+            let config_arquivo = Config {
+                doc_path: resultado.arquivo.clone(),
+                ..config.clone()
+            };
+            println!("Arquivo: {:?}", resultado.arquivo.display());
+            if let Err(e) = finalizar(&config_arquivo, &output_path, resultado.alteracoes) {
+                resultado.erro = Some(e.to_string());
+            }
+        }
+        println!();
+    }
+
+    imprimir_tabela_do_lote(&resultados);
     timer.print_elapsed_time();
-    println!();
 
+    let falhas = resultados.iter().filter(|r| r.falhou()).count();
+    if falhas > 0 {
+        return Err(SpedError::BatchFailed {
+            falhas,
+            total: resultados.len(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Decide o destino do arquivo modificado: remover, renomear ou perguntar.
+fn finalizar(config: &Config, output_path: &Path, alteracoes: usize) -> SpedResult<()> {
     if alteracoes == 0 {
         println!(" -> ATENÇÃO: Nenhuma correspondência encontrada. Removendo arquivo temporário.");
-        fs::remove_file(&output_path)?;
+        fs::remove_file(output_path)?;
     } else if config.atualizar_origem {
-        fs::rename(&output_path, &config.doc_path)?;
+        substituir_arquivo(output_path, &config.doc_path)?;
         println!(" -> Arquivo original atualizado automaticamente.");
     } else if config.no_prompt {
         println!(
@@ -94,7 +243,7 @@ fn run() -> SpedResult<()> {
         println!(" -> Encerrando sem sobrescrever o original (--no-prompt ativado).");
     } else {
         // Se não houver flag de atualizar nem de no-prompt, pergunta ao usuário
-        sobrescrever_arquivo(&config.doc_path, &output_path)?;
+        sobrescrever_arquivo(&config.doc_path, output_path)?;
     }
 
     Ok(())
@@ -0,0 +1,356 @@
+use csv::{ByteRecord, StringRecord};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    io::Write,
+    path::Path,
+    sync::LazyLock,
+};
+
+use crate::{Colunas, Config, SpedError, SpedResult, coluna_do_campo, fmt_milhares, nome_do_campo};
+
+/// Coluna obrigatória: sem ela não há como identificar o documento fiscal.
+pub const COLUNA_CHAVE: &str = "Chave da Nota Fiscal Eletrônica : NF Item (Todos)";
+
+/// Coluna obrigatória: sem ela não há resumos (`DocSummary`).
+pub const COLUNA_VALOR_ITEM: &str = "Valor da Nota Proporcional : NF Item (Todos) SOMA";
+
+/// Nomes das colunas de `Colunas`, na ordem dos campos (obtidos dos `#[serde(rename)]`).
+///
+/// Serializa uma linha vazia com cabeçalho e lê a primeira linha gerada,
+/// evitando manter uma segunda lista dos 56 nomes de colunas.
+pub static CABECALHOS: LazyLock<Vec<String>> = LazyLock::new(|| {
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b';')
+        .has_headers(true)
+        .from_writer(Vec::new());
+    wtr.serialize(Colunas::default())
+        .expect("Colunas deve ser serializável");
+    let bytes = wtr.into_inner().expect("escrita em memória");
+
+    csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .has_headers(true)
+        .from_reader(bytes.as_slice())
+        .headers()
+        .expect("cabeçalho gerado em memória")
+        .iter()
+        .map(String::from)
+        .collect()
+});
+
+/// Normaliza o nome de uma coluna para comparação:
+/// - remove BOM e espaços nas extremidades;
+/// - ignora maiúsculas/minúsculas;
+/// - remove o sufixo "(Todos)", alterado com frequência pela Receita;
+/// - reduz espaços múltiplos a um único espaço.
+///
+/// Exemplo: `"Número da Nota : NF Item (Todos)"` e `"número da nota :  NF Item"`
+/// são equivalentes.
+pub fn normalizar_coluna(nome: &str) -> String {
+    nome.trim_start_matches('\u{feff}')
+        .to_lowercase()
+        .replace("(todos)", "")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Arquivo TOML de mapeamento de colunas (`--mapa-colunas`).
+///
+/// Exemplo:
+/// ```toml
+/// [colunas]
+/// "Valor da Nota Proporcional : NF Item (Todos) SOMA" = ["Valor do Item", "Valor Proporcional"]
+/// "Código NCM : NF Item (Todos)" = "NCM"
+/// ```
+#[derive(Debug, Deserialize)]
+struct ArquivoDeMapeamento {
+    #[serde(default)]
+    colunas: BTreeMap<String, Aliases>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Aliases {
+    Um(String),
+    Varios(Vec<String>),
+}
+
+/// Lê o arquivo TOML de mapeamento e retorna os aliases por coluna padrão.
+///
+/// As chaves do TOML são comparadas aos nomes padrão de forma normalizada
+/// (ver `normalizar_coluna`). Uma chave desconhecida gera erro.
+pub fn ler_mapa_de_colunas(path: &Path) -> SpedResult<BTreeMap<String, Vec<String>>> {
+    let conteudo = fs::read_to_string(path).map_err(|e| SpedError::IoReader {
+        source: e,
+        arquivo: path.to_path_buf(),
+    })?;
+
+    let arquivo: ArquivoDeMapeamento = toml::from_str(&conteudo)
+        .map_err(|e| SpedError::Config(format!("Mapa de colunas <{}>: {e}", path.display())))?;
+
+    let padroes: HashMap<String, &String> = CABECALHOS
+        .iter()
+        .map(|nome| (normalizar_coluna(nome), nome))
+        .collect();
+
+    let mut aliases: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for (coluna, valor) in arquivo.colunas {
+        let padrao = padroes.get(&normalizar_coluna(&coluna)).ok_or_else(|| {
+            SpedError::Config(format!(
+                "Mapa de colunas <{}>: coluna desconhecida <{coluna}>",
+                path.display()
+            ))
+        })?;
+
+        let lista = match valor {
+            Aliases::Um(alias) => vec![alias],
+            Aliases::Varios(lista) => lista,
+        };
+
+        aliases.entry(padrao.to_string()).or_default().extend(lista);
+    }
+
+    Ok(aliases)
+}
+
+/// Correspondência entre as colunas do arquivo CSV e os campos de `Colunas`.
+///
+/// Construído a partir do cabeçalho, permite ler arquivos com colunas em outra
+/// ordem, com nomes alternativos (aliases), ausentes ou extras.
+/// As colunas extras são preservadas na gravação.
+#[derive(Debug, Clone)]
+pub struct MapaDeColunas {
+    /// Para cada campo de `Colunas`: índice da coluna no arquivo.
+    indices: Vec<Option<usize>>,
+    /// Número de colunas do arquivo.
+    num_colunas: usize,
+    /// Colunas do arquivo na mesma ordem dos campos de `Colunas`.
+    identidade: bool,
+    /// Colunas padrão ausentes no arquivo.
+    pub ausentes: Vec<String>,
+    /// Colunas do arquivo que não correspondem a nenhum campo.
+    pub extras: Vec<String>,
+}
+
+impl MapaDeColunas {
+    /// Constrói o mapa a partir do cabeçalho do arquivo.
+    ///
+    /// Erros:
+    /// - `EmptyColumnName`: coluna com nome em branco;
+    /// - `DuplicateColumnName`: nome repetido ou duas colunas para o mesmo campo;
+    /// - `MissingColumns`: colunas obrigatórias (chave e valor do item) ausentes.
+    pub fn new(headers: &StringRecord, config: &Config, arquivo: &Path) -> SpedResult<Self> {
+        // Nome normalizado (padrão ou alias) -> índice do campo
+        let mut nomes: HashMap<String, usize> = HashMap::new();
+        for (campo, nome) in CABECALHOS.iter().enumerate() {
+            nomes.insert(normalizar_coluna(nome), campo);
+            for alias in config.aliases_de_colunas.get(nome).into_iter().flatten() {
+                nomes.insert(normalizar_coluna(alias), campo);
+            }
+        }
+
+        let mut indices = vec![None; CABECALHOS.len()];
+        let mut vistos: HashMap<&str, usize> = HashMap::new();
+        let mut extras = Vec::new();
+
+        for (i, coluna) in headers.iter().enumerate() {
+            let coluna = coluna.trim_start_matches('\u{feff}').trim();
+
+            if coluna.is_empty() {
+                return Err(SpedError::EmptyColumnName {
+                    arquivo: arquivo.to_path_buf(),
+                });
+            }
+
+            if vistos.insert(coluna, i).is_some() {
+                return Err(SpedError::DuplicateColumnName {
+                    arquivo: arquivo.to_path_buf(),
+                    coluna: coluna.to_string(),
+                });
+            }
+
+            match nomes.get(&normalizar_coluna(coluna)) {
+                Some(&campo) if indices[campo].is_some() => {
+                    return Err(SpedError::DuplicateColumnName {
+                        arquivo: arquivo.to_path_buf(),
+                        coluna: coluna.to_string(),
+                    });
+                }
+                Some(&campo) => indices[campo] = Some(i),
+                None => extras.push(coluna.to_string()),
+            }
+        }
+
+        let ausentes: Vec<String> = CABECALHOS
+            .iter()
+            .zip(&indices)
+            .filter(|(_, indice)| indice.is_none())
+            .map(|(nome, _)| nome.clone())
+            .collect();
+
+        let obrigatorias: Vec<&str> = [COLUNA_CHAVE, COLUNA_VALOR_ITEM]
+            .into_iter()
+            .filter(|nome| ausentes.iter().any(|a| a == nome))
+            .collect();
+
+        if !obrigatorias.is_empty() {
+            return Err(SpedError::MissingColumns {
+                arquivo: arquivo.to_path_buf(),
+                colunas: obrigatorias.join("; "),
+            });
+        }
+
+        let identidade = headers.len() == CABECALHOS.len()
+            && indices.iter().enumerate().all(|(c, i)| *i == Some(c));
+
+        Ok(MapaDeColunas {
+            indices,
+            num_colunas: headers.len(),
+            identidade,
+            ausentes,
+            extras,
+        })
+    }
+
+    /// Colunas do arquivo na ordem padrão: nenhuma conversão é necessária.
+    pub fn is_identidade(&self) -> bool {
+        self.identidade
+    }
+
+    /// Número de colunas do arquivo (cabeçalho).
+    pub fn num_colunas(&self) -> usize {
+        self.num_colunas
+    }
+
+    /// Imprime as colunas ausentes e extras (se houver).
+    pub fn relatar(&self, arquivo: &Path) {
+        if self.ausentes.is_empty() && self.extras.is_empty() {
+            return;
+        }
+
+        println!(" -> Mapeamento de colunas de <{}>:", arquivo.display());
+        if !self.ausentes.is_empty() {
+            println!(
+                "    Colunas ausentes ({}), consideradas vazias:",
+                fmt_milhares(self.ausentes.len())
+            );
+            for nome in &self.ausentes {
+                println!("      - {nome}");
+            }
+        }
+        if !self.extras.is_empty() {
+            println!(
+                "    Colunas extras ({}), preservadas sem alteração:",
+                fmt_milhares(self.extras.len())
+            );
+            for nome in &self.extras {
+                println!("      - {nome}");
+            }
+        }
+    }
+
+    /// Deserializa a linha em `Colunas`.
+    ///
+    /// Na ordem padrão, a deserialização é direta (zero-copy sobre `record`).
+    /// Caso contrário, os campos são reordenados em `buf` (campos ausentes ficam vazios).
+    pub fn deserializar<'r>(
+        &self,
+        record: &'r StringRecord,
+        buf: &'r mut StringRecord,
+    ) -> Result<Colunas<'r>, csv::Error> {
+        if self.identidade {
+            return record.deserialize(None);
+        }
+
+        buf.clear();
+        for indice in &self.indices {
+            buf.push_field(indice.and_then(|i| record.get(i)).unwrap_or(""));
+        }
+        buf.deserialize(None)
+    }
+
+    /// Versão de `deserializar` para `ByteRecord` (Passagem 1).
+    pub fn deserializar_bytes<'r>(
+        &self,
+        record: &'r ByteRecord,
+        buf: &'r mut ByteRecord,
+    ) -> Result<Colunas<'r>, csv::Error> {
+        if self.identidade {
+            return record.deserialize(None);
+        }
+
+        buf.clear();
+        for indice in &self.indices {
+            buf.push_field(indice.and_then(|i| record.get(i)).unwrap_or(b""));
+        }
+        buf.set_position(record.position().cloned());
+        buf.deserialize(None)
+    }
+
+    /// Grava a linha alterada, remontada na ordem original do arquivo (ver `registro_de_saida`).
+    pub fn gravar<W: Write>(
+        &self,
+        wtr: &mut csv::Writer<W>,
+        original: &StringRecord,
+        row: &Colunas,
+    ) -> SpedResult<()> {
+        wtr.write_record(&self.registro_de_saida(original, row))?;
+        Ok(())
+    }
+
+    /// Monta a linha de saída na ordem original do arquivo: os campos de texto
+    /// de `row` alterados substituem as colunas mapeadas e as demais são
+    /// preservadas como lidas (inclusive a chave, que nunca é alterada).
+    pub fn registro_de_saida<'r>(
+        &self,
+        original: &'r StringRecord,
+        row: &'r Colunas,
+    ) -> StringRecord {
+        let mut saida: Vec<&str> = (0..self.num_colunas)
+            .map(|i| original.get(i).unwrap_or(""))
+            .collect();
+
+        for (campo, valor) in row.campos_de_texto().into_iter().enumerate() {
+            if let Some(i) = self.indices[coluna_do_campo(campo)]
+                && saida[i] != valor.as_ref()
+            {
+                saida[i] = valor;
+            }
+        }
+
+        StringRecord::from(saida)
+    }
+
+    /// Valores lidos em `original` dos campos de texto alterados em `row`,
+    /// por nome de coluna (os mesmos campos substituídos por `registro_de_saida`).
+    pub fn valores_originais(
+        &self,
+        original: &StringRecord,
+        row: &Colunas,
+    ) -> BTreeMap<String, String> {
+        row.campos_de_texto()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(campo, valor)| {
+                let i = self.indices[coluna_do_campo(campo)]?;
+                let lido = original.get(i).unwrap_or("");
+                (lido != valor.as_ref())
+                    .then(|| (nome_do_campo(campo).to_string(), lido.to_string()))
+            })
+            .collect()
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output mapeamento_tests
+#[cfg(test)]
+#[path = "tests/mapeamento_tests.rs"]
+mod mapeamento_tests;
@@ -0,0 +1,216 @@
+use serde::{Serialize, Serializer};
+use std::{
+    collections::{BTreeSet, HashMap},
+    fmt,
+};
+
+use crate::{Chave, Colunas, Config, DocSummary, Informacoes};
+
+/// Coluna da modalidade do frete (`--modalidade-frete`).
+pub const COLUNA_MODALIDADE: &str = "Modalidade do frete (CT-e)";
+
+/// Papel do tomador do serviço de transporte (descrição do CT-e).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PapelDoTomador {
+    Remetente,
+    Expedidor,
+    Recebedor,
+    Destinatario,
+    Outros,
+}
+
+impl PapelDoTomador {
+    /// Interpreta a descrição do papel (ex: "Remetente", "3 - Destinatário").
+    pub fn interpretar(descricao: &str) -> Option<Self> {
+        let descricao = descricao.to_lowercase();
+        [
+            ("remetente", PapelDoTomador::Remetente),
+            ("expedidor", PapelDoTomador::Expedidor),
+            ("recebedor", PapelDoTomador::Recebedor),
+            ("destinat", PapelDoTomador::Destinatario),
+            ("outro", PapelDoTomador::Outros),
+        ]
+        .into_iter()
+        .find(|(nome, _)| descricao.contains(nome))
+        .map(|(_, papel)| papel)
+    }
+}
+
+/// Dígitos do CNPJ (14) ou CPF (11); `None` se o documento for inválido ou ausente.
+pub fn cnpj_ou_cpf(texto: &str) -> Option<String> {
+    let digitos: String = texto.chars().filter(char::is_ascii_digit).collect();
+    matches!(digitos.len(), 11 | 14).then_some(digitos)
+}
+
+/// Raiz do CNPJ (8 primeiros dígitos) ou o CPF completo: identifica o contribuinte
+/// em todos os seus estabelecimentos.
+pub fn raiz_do_documento(texto: &str) -> Option<String> {
+    cnpj_ou_cpf(texto).map(|mut digitos| {
+        digitos.truncate(if digitos.len() == 14 { 8 } else { 11 });
+        digitos
+    })
+}
+
+/// Tomadores informados na linha do CT-e (colunas 1 e 2): o papel e o documento
+/// do tomador conforme o papel (remetente, destinatário ou outro tomador).
+///
+/// O expedidor e o recebedor não possuem colunas de documento.
+pub fn tomadores<'r>(
+    row: &'r Colunas,
+) -> impl Iterator<Item = (PapelDoTomador, Option<String>)> + 'r {
+    [
+        (
+            &row.tomador_papel1,
+            &row.remetente_cnpj1,
+            &row.tomador_cnpj1,
+        ),
+        (
+            &row.tomador_papel2,
+            &row.remetente_cnpj2,
+            &row.tomador_cnpj2,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(papel, remetente, outro)| {
+        let papel = PapelDoTomador::interpretar(papel)?;
+        let texto = match papel {
+            PapelDoTomador::Remetente => remetente,
+            PapelDoTomador::Destinatario => &row.destinatario_cnpj,
+            PapelDoTomador::Outros => outro,
+            PapelDoTomador::Expedidor | PapelDoTomador::Recebedor => return Some((papel, None)),
+        };
+        Some((papel, cnpj_ou_cpf(texto)))
+    })
+}
+
+/// Modalidade normalizada do frete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Modalidade {
+    /// O remetente é o tomador (frete pago pelo vendedor).
+    Cif,
+    /// O destinatário é o tomador (frete pago pelo comprador).
+    Fob,
+    /// O tomador não é o remetente nem o destinatário.
+    Terceiros,
+}
+
+/// Modalidade do frete de um CT-e e se o contribuinte é o tomador do serviço.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ModalidadeDoFrete {
+    pub modalidade: Modalidade,
+    /// A raiz do CNPJ do tomador é a do contribuinte.
+    pub contribuinte_tomador: bool,
+}
+
+impl ModalidadeDoFrete {
+    /// Modalidade da linha do CT-e (`None`: papel do tomador não informado).
+    ///
+    /// O documento do tomador é comparado com os do remetente (CIF) e do
+    /// destinatário (FOB); sem correspondência, prevalece o papel informado:
+    /// remetente (CIF), destinatário (FOB) ou os demais papéis (terceiros).
+    pub fn da_linha(row: &Colunas) -> Option<Self> {
+        let (papel, tomador) = tomadores(row).next()?;
+
+        let remetente =
+            cnpj_ou_cpf(&row.remetente_cnpj1).or_else(|| cnpj_ou_cpf(&row.remetente_cnpj2));
+        let destinatario = cnpj_ou_cpf(&row.destinatario_cnpj);
+
+        let modalidade = match &tomador {
+            Some(doc) if remetente.as_ref() == Some(doc) => Modalidade::Cif,
+            Some(doc) if destinatario.as_ref() == Some(doc) => Modalidade::Fob,
+            _ => match papel {
+                PapelDoTomador::Remetente => Modalidade::Cif,
+                PapelDoTomador::Destinatario => Modalidade::Fob,
+                _ => Modalidade::Terceiros,
+            },
+        };
+
+        let contribuinte = raiz_do_documento(&row.contribuinte_cnpj);
+        let contribuinte_tomador = contribuinte.is_some()
+            && tomador.as_deref().and_then(raiz_do_documento) == contribuinte;
+
+        Some(ModalidadeDoFrete {
+            modalidade,
+            contribuinte_tomador,
+        })
+    }
+}
+
+impl fmt::Display for ModalidadeDoFrete {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modalidade = match self.modalidade {
+            Modalidade::Cif => "CIF",
+            Modalidade::Fob => "FOB",
+            Modalidade::Terceiros => "Terceiros",
+        };
+        if self.contribuinte_tomador {
+            write!(f, "{modalidade} (contribuinte tomador)")
+        } else {
+            f.write_str(modalidade)
+        }
+    }
+}
+
+/// Na exportação JSON, a modalidade é o texto da coluna (ex: "FOB").
+impl Serialize for ModalidadeDoFrete {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Modalidades distintas dos CT-es relacionados à NF-e `chave`.
+pub fn modalidades_dos_ctes(
+    chave: &Chave,
+    info: &Informacoes,
+    cte_info: &HashMap<Chave, DocSummary>,
+) -> BTreeSet<ModalidadeDoFrete> {
+    info.nfe_ctes
+        .get(chave)
+        .into_iter()
+        .flatten()
+        .filter_map(|cte| cte_info.get(cte)?.modalidade)
+        .collect()
+}
+
+/// Valor da coluna `COLUNA_MODALIDADE` na linha.
+///
+/// - CT-e: a modalidade do resumo (primeiro item do CT-e) ou, na sua falta, a da linha;
+/// - NF-e: as modalidades distintas dos CT-es relacionados, separadas por `config.separador`;
+/// - demais documentos e linhas canceladas: vazio.
+pub fn coluna_modalidade(
+    row: &Colunas,
+    config: &Config,
+    info: &Informacoes,
+    cte_info: &HashMap<Chave, DocSummary>,
+) -> String {
+    let modelo = row.chave.modelo();
+
+    if row.chave_cancelada() {
+        String::new()
+    } else if modelo.is_conhecimento() {
+        cte_info
+            .get(&row.chave)
+            .and_then(|resumo| resumo.modalidade)
+            .or_else(|| ModalidadeDoFrete::da_linha(row))
+            .map(|m| m.to_string())
+            .unwrap_or_default()
+    } else if modelo.is_nota() {
+        modalidades_dos_ctes(&row.chave, info, cte_info)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(&config.separador)
+    } else {
+        String::new()
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output modalidade_tests
+#[cfg(test)]
+#[path = "tests/modalidade_tests.rs"]
+mod modalidade_tests;
@@ -0,0 +1,299 @@
+use clap::ValueEnum;
+use rust_decimal::Decimal;
+use std::{collections::BTreeMap, path::Path};
+
+use crate::fmt_milhares;
+
+/// Número máximo de exemplos de valores problemáticos guardados por coluna.
+const MAX_EXEMPLOS: usize = 5;
+
+/// Formato dos valores numéricos do CSV (`--formato-numerico`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FormatoNumerico {
+    /// Detectar pelo próprio valor; valores ambíguos são lidos no formato brasileiro.
+    #[default]
+    Auto,
+    /// Brasileiro: `1.234,56` (ponto de milhar, vírgula decimal).
+    #[value(name = "br")]
+    Br,
+    /// Internacional: `1,234.56` (vírgula de milhar, ponto decimal).
+    #[value(name = "internacional", alias = "int")]
+    Internacional,
+}
+
+/// Resultado da interpretação de um valor numérico.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Numero {
+    /// Campo vazio.
+    Vazio,
+    /// Valor interpretado sem ambiguidade.
+    Valor(Decimal),
+    /// Valor com duas leituras possíveis (ex: `1.234` = 1234 ou 1,234).
+    /// `valor` é a leitura adotada e `alternativa`, a descartada.
+    Ambiguo {
+        valor: Decimal,
+        alternativa: Decimal,
+    },
+    /// Valor que não pôde ser interpretado no formato escolhido.
+    Invalido,
+}
+
+impl Numero {
+    /// Valor adotado (inclusive de valores ambíguos).
+    pub fn valor(&self) -> Option<Decimal> {
+        match self {
+            Numero::Valor(valor) | Numero::Ambiguo { valor, .. } => Some(*valor),
+            Numero::Vazio | Numero::Invalido => None,
+        }
+    }
+}
+
+/// Interpreta um valor numérico no `formato` informado.
+///
+/// - Ruídos como espaços e símbolos de moeda (`R$`) são ignorados.
+/// - Notação científica (`1.23e4`) é aceita em qualquer formato.
+/// - No formato `Auto`, com os dois separadores presentes, o último é o decimal.
+///   Com um único separador seguido de exatamente 3 dígitos (`1.234`, `1,234`),
+///   o valor é `Ambiguo` e prevalece a leitura brasileira.
+/// - Valores além da precisão de `Decimal` (28 dígitos) são `Invalido`.
+pub fn interpretar_numero(texto: &str, formato: FormatoNumerico) -> Numero {
+    let texto = texto.trim();
+    if texto.is_empty() {
+        return Numero::Vazio;
+    }
+
+    // Remove ruídos, preservando dígitos, sinais, separadores e expoente
+    let limpo: String = texto
+        .chars()
+        .filter(|c| c.is_ascii_digit() || matches!(c, '+' | '-' | '.' | ',' | 'e' | 'E'))
+        .collect();
+
+    if !limpo.bytes().any(|b| b.is_ascii_digit()) {
+        return Numero::Invalido;
+    }
+
+    if limpo.contains(['e', 'E']) {
+        return Decimal::from_scientific(&limpo.replace(',', "."))
+            .map_or(Numero::Invalido, Numero::Valor);
+    }
+
+    let (negativo, corpo) = match limpo.as_bytes()[0] {
+        b'-' => (true, &limpo[1..]),
+        b'+' => (false, &limpo[1..]),
+        _ => (false, limpo.as_str()),
+    };
+
+    if corpo.contains(['+', '-']) {
+        return Numero::Invalido;
+    }
+
+    let com_sinal = |valor: Decimal| if negativo { -valor } else { valor };
+    let br = || converter(corpo, ',', '.').map(com_sinal);
+    let internacional = || converter(corpo, '.', ',').map(com_sinal);
+
+    let resultado = match formato {
+        FormatoNumerico::Br => br(),
+        FormatoNumerico::Internacional => internacional(),
+        FormatoNumerico::Auto => {
+            let ultima_virgula = corpo.rfind(',');
+            let ultimo_ponto = corpo.rfind('.');
+
+            match (ultima_virgula, ultimo_ponto) {
+                (Some(v), Some(p)) if v > p => br(),
+                (Some(_), Some(_)) => internacional(),
+                (None, None) => br(),
+                (Some(_), None) | (None, Some(_)) => {
+                    if is_ambiguo(corpo) {
+                        return match (br(), internacional()) {
+                            (Some(valor), Some(alternativa)) if valor != alternativa => {
+                                Numero::Ambiguo { valor, alternativa }
+                            }
+                            (Some(valor), _) => Numero::Valor(valor),
+                            _ => Numero::Invalido,
+                        };
+                    }
+
+                    // Um único separador é decimal; repetido, é separador de milhar
+                    let separador = if ultima_virgula.is_some() { ',' } else { '.' };
+                    let repetido = corpo.matches(separador).count() > 1;
+                    if (separador == ',') != repetido {
+                        br()
+                    } else {
+                        internacional()
+                    }
+                }
+            }
+        }
+    };
+
+    resultado.map_or(Numero::Invalido, Numero::Valor)
+}
+
+/// Um único separador, entre 1 a 3 dígitos (sem zero à esquerda) e exatamente 3 dígitos:
+/// pode ser tanto separador de milhar quanto decimal.
+fn is_ambiguo(corpo: &str) -> bool {
+    let Some((inteiro, fracao)) = corpo.split_once(['.', ',']) else {
+        return false;
+    };
+
+    (1..=3).contains(&inteiro.len())
+        && !inteiro.starts_with('0')
+        && fracao.len() == 3
+        && fracao.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// Converte `corpo` (sem sinal) com os separadores informados.
+///
+/// O separador de milhar deve separar grupos de exatamente 3 dígitos
+/// (o primeiro grupo pode ter de 1 a 3 dígitos).
+fn converter(corpo: &str, decimal: char, milhar: char) -> Option<Decimal> {
+    let (inteiro, fracao) = match corpo.split_once(decimal) {
+        Some((inteiro, fracao)) => (inteiro, Some(fracao)),
+        None => (corpo, None),
+    };
+
+    let grupos: Vec<&str> = inteiro.split(milhar).collect();
+    let grupos_validos = grupos.len() == 1
+        || (grupos[0].len() <= 3
+            && !grupos[0].is_empty()
+            && grupos[1..].iter().all(|g| g.len() == 3));
+
+    let digitos = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+
+    if !grupos_validos || !grupos.iter().all(|g| digitos(g)) {
+        return None;
+    }
+
+    let mut normalizado = grupos.concat();
+    if let Some(fracao) = fracao {
+        if !digitos(fracao) || (normalizado.is_empty() && fracao.is_empty()) {
+            return None;
+        }
+        normalizado.push('.');
+        normalizado.push_str(fracao);
+    }
+
+    if normalizado.is_empty() {
+        return None;
+    }
+
+    // Decimal exato: todas as casas decimais da origem são preservadas
+    Decimal::from_str_exact(&normalizado).ok()
+}
+
+/// Estatísticas de interpretação de uma coluna numérica.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EstatisticaDaColuna {
+    pub validos: usize,
+    pub vazios: usize,
+    pub ambiguos: usize,
+    pub invalidos: usize,
+    /// Primeiros valores inválidos (até `MAX_EXEMPLOS`).
+    pub exemplos_invalidos: Vec<String>,
+    /// Primeiros valores ambíguos (até `MAX_EXEMPLOS`): texto, valor adotado e alternativa.
+    pub exemplos_ambiguos: Vec<(String, Decimal, Decimal)>,
+}
+
+/// Relatório de qualidade dos dados numéricos, por coluna (Passagem 1).
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct QualidadeNumerica {
+    pub colunas: BTreeMap<&'static str, EstatisticaDaColuna>,
+}
+
+impl QualidadeNumerica {
+    /// Contabiliza a interpretação de `texto` na coluna `coluna`.
+    pub fn registrar(&mut self, coluna: &'static str, texto: &str, numero: &Numero) {
+        let estatistica = self.colunas.entry(coluna).or_default();
+
+        match numero {
+            Numero::Vazio => estatistica.vazios += 1,
+            Numero::Valor(_) => estatistica.validos += 1,
+            Numero::Ambiguo { valor, alternativa } => {
+                estatistica.ambiguos += 1;
+                if estatistica.exemplos_ambiguos.len() < MAX_EXEMPLOS {
+                    estatistica
+                        .exemplos_ambiguos
+                        .push((texto.to_string(), *valor, *alternativa));
+                }
+            }
+            Numero::Invalido => {
+                estatistica.invalidos += 1;
+                if estatistica.exemplos_invalidos.len() < MAX_EXEMPLOS {
+                    estatistica.exemplos_invalidos.push(texto.to_string());
+                }
+            }
+        }
+    }
+
+    /// Soma as estatísticas de outra parte do arquivo (na ordem do arquivo).
+    pub fn merge(&mut self, other: Self) {
+        for (coluna, outra) in other.colunas {
+            let estatistica = self.colunas.entry(coluna).or_default();
+            estatistica.validos += outra.validos;
+            estatistica.vazios += outra.vazios;
+            estatistica.ambiguos += outra.ambiguos;
+            estatistica.invalidos += outra.invalidos;
+
+            let livres = MAX_EXEMPLOS.saturating_sub(estatistica.exemplos_invalidos.len());
+            estatistica
+                .exemplos_invalidos
+                .extend(outra.exemplos_invalidos.into_iter().take(livres));
+
+            let livres = MAX_EXEMPLOS.saturating_sub(estatistica.exemplos_ambiguos.len());
+            estatistica
+                .exemplos_ambiguos
+                .extend(outra.exemplos_ambiguos.into_iter().take(livres));
+        }
+    }
+
+    /// Indica se alguma coluna possui valores inválidos ou ambíguos.
+    pub fn tem_problemas(&self) -> bool {
+        self.colunas
+            .values()
+            .any(|e| e.invalidos > 0 || e.ambiguos > 0)
+    }
+
+    /// Imprime as colunas com valores inválidos ou ambíguos (se houver).
+    pub fn imprimir(&self, arquivo: &Path) {
+        if !self.tem_problemas() {
+            return;
+        }
+
+        println!(
+            " -> Qualidade dos valores numéricos de <{}>:",
+            arquivo.display()
+        );
+
+        for (coluna, e) in &self.colunas {
+            if e.invalidos == 0 && e.ambiguos == 0 {
+                continue;
+            }
+
+            println!("    {coluna}:");
+            println!(
+                "      válidos: {}, vazios: {}, ambíguos: {}, inválidos (linhas fora dos resumos): {}",
+                fmt_milhares(e.validos),
+                fmt_milhares(e.vazios),
+                fmt_milhares(e.ambiguos),
+                fmt_milhares(e.invalidos)
+            );
+            for (texto, valor, alternativa) in &e.exemplos_ambiguos {
+                println!("      ambíguo: '{texto}' lido como {valor} (alternativa: {alternativa})");
+            }
+            for texto in &e.exemplos_invalidos {
+                println!("      inválido: '{texto}'");
+            }
+        }
+        println!("    Informe o formato com a opção --formato-numerico (br ou internacional)");
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output numeros_tests
+#[cfg(test)]
+#[path = "tests/numeros_tests.rs"]
+mod numeros_tests;
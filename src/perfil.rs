@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize, ser::SerializeMap};
+use std::{fs, path::Path, sync::LazyLock};
+
+use crate::{
+    CABECALHOS, COLUNA_CHAVE, COLUNA_VALOR_ITEM, SpedError, SpedResult, normalizar_coluna,
+};
+
+/// Perfil padrão: as 16 colunas do CT-e injetadas nas NF-es e
+/// as 10 colunas da NF-e injetadas nos CT-es.
+const PERFIL_PADRAO_TOML: &str = include_str!("perfil_padrao.toml");
+
+static PERFIL_PADRAO: LazyLock<PerfilDeInjecao> = LazyLock::new(|| {
+    interpretar_perfil(PERFIL_PADRAO_TOML, None).expect("perfil_padrao.toml deve ser válido")
+});
+
+/// Como o valor da coluna de origem é gravado na coluna de destino.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModoDeInjecao {
+    /// Acrescenta " [Info do CT-e: valor]" ao destino.
+    #[default]
+    Anotar,
+    /// Substitui o destino, se a origem tiver valor significativo.
+    Sobrescrever,
+}
+
+/// Coluna injetada: campo do documento relacionado (`origem`) gravado
+/// no campo da linha enriquecida (`destino`).
+///
+/// Os campos são posições em `Colunas::campos_de_texto`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColunaInjetada {
+    /// Nome da coluna dedicada (`--modo-saida colunas`), sem o rótulo da direção.
+    pub nome: String,
+    pub origem: usize,
+    pub destino: usize,
+    /// Máximo de documentos relacionados nesta coluna (além do limite da direção).
+    pub max_info: Option<usize>,
+    pub modo: ModoDeInjecao,
+}
+
+/// Colunas injetadas em uma direção (ex: CT-e -> NF-e).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirecaoDeInjecao {
+    /// Rótulo das anotações: "CT-e" ou "NF-e".
+    pub rotulo: &'static str,
+    /// Máximo de documentos relacionados; `None`: `--max-info`.
+    pub max_info: Option<usize>,
+    pub colunas: Vec<ColunaInjetada>,
+}
+
+impl DirecaoDeInjecao {
+    /// Máximo de documentos relacionados da direção (`padrao`: `--max-info`).
+    pub fn limite(&self, padrao: usize) -> usize {
+        self.max_info.unwrap_or(padrao)
+    }
+}
+
+/// Perfil de injeção (`--perfil-injecao`): quais colunas dos documentos
+/// relacionados são injetadas em quais colunas, em cada direção.
+///
+/// O perfil padrão (`perfil_padrao.toml`) reproduz as colunas históricas.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PerfilDeInjecao {
+    /// Colunas do CT-e injetadas nas NF-es e nos MDF-es.
+    pub cte_para_nfe: DirecaoDeInjecao,
+    /// Colunas da NF-e injetadas nos CT-es.
+    pub nfe_para_cte: DirecaoDeInjecao,
+}
+
+impl Default for PerfilDeInjecao {
+    fn default() -> Self {
+        PERFIL_PADRAO.clone()
+    }
+}
+
+/// Metadados de um documento: valores das colunas de origem de uma direção do perfil.
+///
+/// Na exportação JSON, são um objeto com os nomes das colunas, na ordem do perfil.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Metadados {
+    campos: Vec<(usize, String)>,
+}
+
+impl Metadados {
+    /// Guarda o valor do campo `origem` (uma única vez por campo).
+    pub fn inserir(&mut self, origem: usize, valor: &str) {
+        if self.campos.iter().all(|(campo, _)| *campo != origem) {
+            self.campos.push((origem, valor.trim().to_string()));
+        }
+    }
+
+    /// Valor do campo `origem` (vazio se ausente).
+    pub fn valor(&self, origem: usize) -> &str {
+        self.campos
+            .iter()
+            .find(|(campo, _)| *campo == origem)
+            .map_or("", |(_, valor)| valor)
+    }
+}
+
+impl Serialize for Metadados {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.campos.len()))?;
+        for (campo, valor) in &self.campos {
+            map.serialize_entry(nome_do_campo(*campo), valor)?;
+        }
+        map.end()
+    }
+}
+
+/// Nome da coluna do campo de texto `campo` (ver `Colunas::campos_de_texto`).
+pub fn nome_do_campo(campo: usize) -> &'static str {
+    &CABECALHOS[coluna_do_campo(campo)]
+}
+
+/// Posição em `CABECALHOS` do campo de texto `campo` (ver `Colunas::campos_de_texto`).
+pub fn coluna_do_campo(campo: usize) -> usize {
+    let chave = posicao_da_chave();
+    if campo < chave { campo } else { campo + 1 }
+}
+
+/// Posição do campo de texto da coluna `nome`, comparado de forma normalizada
+/// (ver `normalizar_coluna`). A chave não é um campo de texto.
+pub fn campo_de_texto(nome: &str) -> Option<usize> {
+    let nome = normalizar_coluna(nome);
+    let chave = posicao_da_chave();
+    CABECALHOS
+        .iter()
+        .position(|coluna| normalizar_coluna(coluna) == nome)
+        .filter(|&i| i != chave)
+        .map(|i| if i < chave { i } else { i - 1 })
+}
+
+/// Posição da coluna da chave em `CABECALHOS` (única coluna que não é texto).
+fn posicao_da_chave() -> usize {
+    CABECALHOS
+        .iter()
+        .position(|nome| nome == COLUNA_CHAVE)
+        .expect("a chave é uma coluna de Colunas")
+}
+
+/// Colunas lidas pelo processamento em qualquer linha (cancelamento, anotações,
+/// valores, datas e participantes): não podem ser destino de uma injeção.
+const COLUNAS_LIDAS: [&str; 9] = [
+    "Cancelada : NF (Todos)",
+    "Inf. NFe - Chave de acesso da NF-e : ConhecimentoInformacaoNFe",
+    COLUNA_VALOR_ITEM,
+    "Número do Item : NF Item (Todos)",
+    "Dia da Emissão : NF Item (Todos)",
+    "Código CFOP : NF Item (Todos)",
+    "Entrada/Saída : NF (Todos)",
+    "CNPJ do Contribuinte : NF Item (Todos)",
+    "CPF/CNPJ do Participante : NF (Todos)",
+];
+
+/// Colunas lidas nas linhas de CT-e (tributos, modalidade do frete, consistência
+/// e créditos): não podem ser destino na direção NF-e -> CT-e.
+const COLUNAS_LIDAS_NOS_CTES: [&str; 14] = [
+    "ICMS: Base de Cálculo : NF Item (Todos) SOMA",
+    "Natureza da Base de Cálculo do Crédito Descrição : NF Item (Todos)",
+    "Descrição CTe - Indicador do 'papel' do tomador do serviço de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes",
+    "Descrição CTe - Indicador do 'papel' do tomador do serviço de Conhecimento : ConhecimentoInformacaoNFe",
+    "CTe - Outro tipo de Tomador: CNPJ/CPF de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes",
+    "CTe - Outro tipo de Tomador: CNPJ/CPF de Conhecimento : ConhecimentoInformacaoNFe",
+    "CTe - Remetente das mercadorias transportadas: CNPJ/CPF de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes",
+    "CTe - Remetente das mercadorias transportadas: CNPJ/CPF de Conhecimento : ConhecimentoInformacaoNFe",
+    "CTe - Informações do Destinatário do CT-e: CNPJ/CPF de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes",
+    "CTe - UF do início da prestação de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes",
+    "CTe - UF do término da prestação de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes",
+    "PIS: Valor do Tributo : NF Item (Todos) SOMA",
+    "COFINS: Valor do Tributo : NF Item (Todos) SOMA",
+    "ICMS: Valor do Tributo : NF Item (Todos) SOMA",
+];
+
+/// Indica se o valor é significativo para `ModoDeInjecao::Sobrescrever`:
+/// contém algum caractere alfanumérico diferente de '0' (ex: NCM "00000000" não é).
+pub fn valor_significativo(valor: &str) -> bool {
+    valor.chars().any(|c| c.is_alphanumeric() && c != '0')
+}
+
+/// Arquivo TOML do perfil de injeção (ver `perfil_padrao.toml`).
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ArquivoDePerfil {
+    cte_para_nfe: Option<DirecaoToml>,
+    nfe_para_cte: Option<DirecaoToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DirecaoToml {
+    max_info: Option<usize>,
+    colunas: Vec<ColunaToml>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ColunaToml {
+    nome: Option<String>,
+    origem: String,
+    destino: Option<String>,
+    max_info: Option<usize>,
+    #[serde(default)]
+    modo: ModoDeInjecao,
+}
+
+/// Lê o perfil de injeção (`--perfil-injecao`).
+///
+/// Uma direção omitida no arquivo mantém as colunas do perfil padrão.
+pub fn ler_perfil_de_injecao(path: &Path) -> SpedResult<PerfilDeInjecao> {
+    let conteudo = fs::read_to_string(path).map_err(|e| SpedError::IoReader {
+        source: e,
+        arquivo: path.to_path_buf(),
+    })?;
+
+    interpretar_perfil(&conteudo, Some(path))
+}
+
+/// Interpreta o TOML do perfil: os nomes das colunas são comparados aos nomes
+/// padrão de forma normalizada (ver `normalizar_coluna`).
+///
+/// Erros: coluna desconhecida, chave como origem ou coluna lida pelo processamento
+/// como destino (`COLUNAS_LIDAS`; nos CT-es, também `COLUNAS_LIDAS_NOS_CTES`).
+fn interpretar_perfil(conteudo: &str, path: Option<&Path>) -> SpedResult<PerfilDeInjecao> {
+    let origem_do_perfil = path.map_or("perfil padrão".to_string(), |p| {
+        format!("<{}>", p.display())
+    });
+    let erro =
+        |msg: String| SpedError::Config(format!("Perfil de injeção {origem_do_perfil}: {msg}"));
+
+    let arquivo: ArquivoDePerfil = toml::from_str(conteudo).map_err(|e| erro(e.to_string()))?;
+
+    let campo = |nome: &str| -> SpedResult<usize> {
+        campo_de_texto(nome)
+            .ok_or_else(|| erro(format!("coluna desconhecida ou não permitida <{nome}>")))
+    };
+
+    let direcao = |toml: Option<DirecaoToml>,
+                   padrao: fn(&PerfilDeInjecao) -> &DirecaoDeInjecao,
+                   rotulo: &'static str,
+                   lidas: &[&str]|
+     -> SpedResult<DirecaoDeInjecao> {
+        let Some(toml) = toml else {
+            return Ok(padrao(&PERFIL_PADRAO).clone());
+        };
+
+        let colunas = toml
+            .colunas
+            .into_iter()
+            .map(|coluna| {
+                let origem = campo(&coluna.origem)?;
+                let destino = match &coluna.destino {
+                    Some(nome) => campo(nome)?,
+                    None => origem,
+                };
+
+                if COLUNAS_LIDAS
+                    .iter()
+                    .chain(lidas)
+                    .any(|&nome| campo_de_texto(nome) == Some(destino))
+                {
+                    return Err(erro(format!(
+                        "a coluna <{}> é lida pelo processamento e não pode ser destino",
+                        nome_do_campo(destino)
+                    )));
+                }
+
+                Ok(ColunaInjetada {
+                    nome: coluna
+                        .nome
+                        .unwrap_or_else(|| nome_do_campo(origem).to_string()),
+                    origem,
+                    destino,
+                    max_info: coluna.max_info,
+                    modo: coluna.modo,
+                })
+            })
+            .collect::<SpedResult<Vec<_>>>()?;
+
+        Ok(DirecaoDeInjecao {
+            rotulo,
+            max_info: toml.max_info,
+            colunas,
+        })
+    };
+
+    // O perfil padrão define as duas direções (não há recursão em PERFIL_PADRAO)
+    Ok(PerfilDeInjecao {
+        cte_para_nfe: direcao(arquivo.cte_para_nfe, |p| &p.cte_para_nfe, "CT-e", &[])?,
+        nfe_para_cte: direcao(
+            arquivo.nfe_para_cte,
+            |p| &p.nfe_para_cte,
+            "NF-e",
+            &COLUNAS_LIDAS_NOS_CTES,
+        )?,
+    })
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output perfil_tests
+#[cfg(test)]
+#[path = "tests/perfil_tests.rs"]
+mod perfil_tests;
@@ -1,333 +1,823 @@
 use crate::{
-    BUFFER, Chave, Colunas, Config, CteMetadata, Informacoes, NfeMetadata, SpedError, SpedResult,
-    fmt_milhares,
+    ArquivoMapeado, COLUNA_VALOR_ITEM, CamposDistintos, Chave, ChavesInvalidas, Colunas, Config,
+    DadosDeConsistencia, Distribuicao, IndiceDeLinhas, Informacoes, LINHAS_POR_FATIA,
+    MapaDeColunas, Metadados, ModalidadeDoFrete, Modelo, PerfilDeInjecao, QualidadeNumerica,
+    SpedResult, TributosDoCte, fmt_milhares, leitor, ler_registro, pode_ser_enriquecida,
 };
-use csv::{ByteRecord, ReaderBuilder};
+use clap::ValueEnum;
+use csv::ByteRecord;
 use rayon::prelude::*;
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
 use std::{
-    collections::{HashMap, hash_map::Entry},
-    fs::File,
-    io::BufReader,
-    path::Path,
+    collections::{HashMap, HashSet, hash_map::Entry},
+    ops::Range,
+    path::{Path, PathBuf},
 };
 
 /// 0.00005 é a metade da quarta casa.
 ///
 /// Qualquer valor absoluto menor será desconsiderado
 /// na soma de número de itens de NFe.
-const DELTA: f64 = 0.00005;
+const DELTA: Decimal = Decimal::from_parts(5, 0, 0, false, 5);
+
+pub fn decimal_to_str(valor: Decimal) -> String {
+    // 1. Formata com 2 casas decimais e arredondamento comercial (ex: 1234.705 -> "1234.71")
+    let arredondado = valor
+        .abs()
+        .round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero);
+    format!("{arredondado:.2}")
+}
+
+/// Como `decimal_to_str`, mas preserva o sinal (ex: tributos de CT-es negativos).
+pub fn decimal_com_sinal_to_str(valor: Decimal) -> String {
+    let arredondado = valor.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero);
+    format!("{arredondado:.2}")
+}
+
+/// Resultado do enriquecimento de uma linha.
+///
+/// A ordem das variantes permite combinar resultados com `max`:
+/// `Nenhuma < Enriquecida < Truncada`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Alteracao {
+    /// A linha não foi alterada.
+    Nenhuma,
+    /// A linha recebeu informações de documentos relacionados.
+    Enriquecida,
+    /// A linha foi enriquecida, mas parte das informações excedeu `max_char`.
+    Truncada,
+}
+
+impl Alteracao {
+    /// Indica se a linha foi alterada (enriquecida, com ou sem truncamento).
+    #[inline]
+    pub fn mudou(self) -> bool {
+        self != Alteracao::Nenhuma
+    }
+
+    /// `Enriquecida` ou, se alguma informação excedeu `max_char`, `Truncada`.
+    #[inline]
+    pub fn de_injecao(completo: bool) -> Self {
+        if completo {
+            Alteracao::Enriquecida
+        } else {
+            Alteracao::Truncada
+        }
+    }
+}
+
+/// Critério de escolha do item representativo do documento (`--item-representativo`).
+///
+/// Os metadados (descrição, NCM, natureza etc.) injetados nos documentos
+/// relacionados são os do item representativo.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ItemRepresentativo {
+    /// Item de maior valor.
+    #[default]
+    MaiorValor,
+    /// Item de menor número (o primeiro item do documento).
+    PrimeiroItem,
+    /// Primeiro item do grupo CFOP/NCM de maior valor somado.
+    CfopNcm,
+    /// Item de maior valor do grupo CFOP/NCM de maior valor somado.
+    CfopNcmMaiorValor,
+}
+
+impl ItemRepresentativo {
+    /// Indica se o item depende do agrupamento dos itens por CFOP/NCM:
+    /// o representante é conhecido apenas após a leitura de todos os itens.
+    pub fn por_grupo(self) -> bool {
+        matches!(
+            self,
+            ItemRepresentativo::CfopNcm | ItemRepresentativo::CfopNcmMaiorValor
+        )
+    }
+
+    /// Indica se o item `a` precede o item `b` na escolha do representante.
+    ///
+    /// Critérios: 1º maior valor (apenas nas estratégias por valor),
+    /// 2º menor número de item, 3º linha anterior.
+    /// O resultado não depende da ordem de processamento (threads).
+    pub fn precede(self, a: &ItemCandidato, b: &ItemCandidato) -> bool {
+        let por_valor = matches!(
+            self,
+            ItemRepresentativo::MaiorValor | ItemRepresentativo::CfopNcmMaiorValor
+        );
+        if por_valor && a.valor != b.valor {
+            return a.valor > b.valor;
+        }
+        (a.numero, a.posicao) < (b.numero, b.posicao)
+    }
+}
+
+/// Critérios de acumulação dos resumos, obtidos de `Config`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CriteriosDoResumo {
+    /// Ver `--item-representativo`.
+    pub item_representativo: ItemRepresentativo,
+    /// Valores distintos por campo e documento (`--max-valores-distintos`); 0: desativado.
+    pub max_valores_distintos: usize,
+    /// Guardar a posição e o valor dos itens das NF-es (`--rateio-frete` e `--tributos-cte`).
+    pub itens_das_nfes: bool,
+}
+
+impl From<&Config> for CriteriosDoResumo {
+    fn from(config: &Config) -> Self {
+        CriteriosDoResumo {
+            item_representativo: config.item_representativo,
+            max_valores_distintos: config.max_valores_distintos,
+            itens_das_nfes: config.rateio_frete || config.tributos_cte,
+        }
+    }
+}
+
+/// Item candidato a representante do documento.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ItemCandidato {
+    pub valor: Decimal,
+    pub numero: u64,
+    /// Posição (byte) do início da linha no arquivo mapeado.
+    pub posicao: usize,
+}
 
-pub fn f64_to_str(valor: f64) -> String {
-    // 1. Formata com 2 casas decimais e arredondamento (ex: 1234.706 -> "1234.71")
-    format!("{:.2}", valor.abs())
+/// Itens de um documento com o mesmo CFOP e NCM.
+#[derive(Debug, Default, Clone)]
+pub struct GrupoDeItens {
+    /// Chave do grupo: "CFOP/NCM".
+    pub cfop_ncm: String,
+    /// Soma dos valores dos itens do grupo.
+    pub peso: Decimal,
+    pub num_de_itens: usize,
+    /// Representante do grupo.
+    pub item: ItemCandidato,
 }
 
 // O Enum não precisa de Default porque ele é usado dentro de um Option
 // Mas é boa prática manter Debug e Clone
-#[derive(Debug, Clone)]
+// untagged: na exportação JSON, os metadados aparecem diretamente como objeto
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
 pub enum DocMetadata {
-    Cte(Box<CteMetadata<'static>>),
-    Nfe(Box<NfeMetadata<'static>>),
+    /// Colunas de origem de `PerfilDeInjecao::cte_para_nfe`.
+    Cte(Metadados),
+    /// Colunas de origem de `PerfilDeInjecao::nfe_para_cte`.
+    Nfe(Metadados),
+}
+
+impl DocMetadata {
+    /// Metadados de CT-e (`None` para metadados de NF-e).
+    pub fn cte(&self) -> Option<&Metadados> {
+        match self {
+            DocMetadata::Cte(c) => Some(c),
+            DocMetadata::Nfe(_) => None,
+        }
+    }
+
+    /// Metadados de NF-e (`None` para metadados de CT-e).
+    pub fn nfe(&self) -> Option<&Metadados> {
+        match self {
+            DocMetadata::Nfe(n) => Some(n),
+            DocMetadata::Cte(_) => None,
+        }
+    }
 }
 
 /// Sumarizar informações de Documentos Fiscais.
 ///
+/// Os valores são decimais exatos (`Decimal`): a soma de milhares de itens
+/// não acumula erros de arredondamento. Na exportação JSON, são strings
+/// (ex: "10.05"), para preservar todas as casas decimais.
+///
 /// Armazena (considera apenas o itens de valores não nulos):
 /// - número de itens;
 /// - valor total;
 /// - valor máximo do item;
-/// - metadata do item de maior valor da chave.
-#[derive(Debug, Default)]
+/// - metadata do item representativo da chave (ver `ItemRepresentativo`);
+/// - distribuição de NCM, CFOP, CST e natureza entre todos os itens (ver `Distribuicao`);
+/// - tributos dos CT-es somados entre todos os itens (ver `TributosDoCte`);
+/// - modalidade do frete do CT-e, conforme o primeiro item (ver `ModalidadeDoFrete`);
+/// - dados da verificação de consistência entre CT-es e NF-es (ver `DadosDeConsistencia`).
+///
+/// `item_valor`, `item_numero` e `item_posicao` (byte do início da linha)
+/// identificam o item representativo e desempatam os candidatos (não são exportados).
+#[derive(Debug, Default, Clone, Serialize)]
 pub struct DocSummary {
     pub num_de_itens: usize,
-    pub item_valor_total: f64,
-    pub item_valor_maximo: f64,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub item_valor_total: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub item_valor_maximo: Decimal,
+    #[serde(skip)]
+    pub item_valor: Decimal,
+    #[serde(skip)]
+    pub item_numero: u64,
+    #[serde(skip)]
+    pub item_posicao: usize,
+    /// Grupos CFOP/NCM em acumulação (apenas nas estratégias por grupo).
+    #[serde(skip)]
+    pub grupos: Vec<GrupoDeItens>,
+    /// Valores distintos dos itens (apenas com `--max-valores-distintos` > 0).
+    #[serde(skip)]
+    pub distribuicao: Option<Box<Distribuicao>>,
+    /// Posição da linha e valor de cada item, na ordem do arquivo
+    /// (apenas NF-es do arquivo em processamento, com `--rateio-frete` ou `--tributos-cte`).
+    #[serde(skip)]
+    pub itens: Vec<(usize, Decimal)>,
+    /// Tributos do CT-e (`None`: documento sem tributos ou que não é CT-e).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tributos: Option<TributosDoCte>,
+    /// Modalidade do frete do CT-e (`None`: tomador não informado ou documento que não é CT-e).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub modalidade: Option<ModalidadeDoFrete>,
+    /// Documentos, datas e UFs dos itens (apenas com `--consistencia`).
+    #[serde(skip)]
+    pub consistencia: Option<Box<DadosDeConsistencia>>,
     pub metadata: Option<DocMetadata>,
 }
 
 impl DocSummary {
-    /// Combina dois resumos. Usado para unir os resultados de diferentes threads (Rayon).
-    pub fn merge(&mut self, other: Self) {
-        self.num_de_itens += other.num_de_itens;
-        self.item_valor_total += other.item_valor_total;
-
-        // Se o valor do outro resumo for estritamente maior,
-        // substituímos o metadado vencedor.
-        if other.item_valor_maximo > self.item_valor_maximo {
-            self.item_valor_maximo = other.item_valor_maximo;
-            self.metadata = other.metadata;
+    /// Item representativo atual.
+    pub fn representante(&self) -> ItemCandidato {
+        ItemCandidato {
+            valor: self.item_valor,
+            numero: self.item_numero,
+            posicao: self.item_posicao,
         }
     }
+
+    /// Indica se o item `candidato` supera o item representativo atual.
+    pub fn supera(&self, estrategia: ItemRepresentativo, candidato: &ItemCandidato) -> bool {
+        self.metadata.is_none() || estrategia.precede(candidato, &self.representante())
+    }
+
+    fn definir_representante(&mut self, item: ItemCandidato) {
+        self.item_valor = item.valor;
+        self.item_numero = item.numero;
+        self.item_posicao = item.posicao;
+    }
+
+    /// Acumula o item no seu grupo CFOP/NCM.
+    fn agrupar(&mut self, estrategia: ItemRepresentativo, cfop_ncm: String, item: ItemCandidato) {
+        match self.grupos.iter_mut().find(|g| g.cfop_ncm == cfop_ncm) {
+            Some(grupo) => {
+                grupo.peso += item.valor;
+                grupo.num_de_itens += 1;
+                if estrategia.precede(&item, &grupo.item) {
+                    grupo.item = item;
+                }
+            }
+            None => self.grupos.push(GrupoDeItens {
+                cfop_ncm,
+                peso: item.valor,
+                num_de_itens: 1,
+                item,
+            }),
+        }
+    }
+
+    /// Define o representante a partir do grupo CFOP/NCM de maior valor somado
+    /// (desempate: mais itens e, depois, menor CFOP/NCM) e libera os grupos.
+    ///
+    /// Retorna o representante, cujos metadados devem ser lidos em seguida.
+    fn escolher_representante_do_grupo(&mut self) -> Option<ItemCandidato> {
+        let grupos = std::mem::take(&mut self.grupos);
+        let grupo = grupos.iter().max_by(|a, b| {
+            a.peso
+                .cmp(&b.peso)
+                .then(a.num_de_itens.cmp(&b.num_de_itens))
+                .then_with(|| b.cfop_ncm.cmp(&a.cfop_ncm))
+        })?;
+
+        self.definir_representante(grupo.item);
+        Some(grupo.item)
+    }
 }
 
-/// Estrutura auxiliar para acumular os dois mapas durante o processamento paralelo.
+/// Metadados do item representativo, conforme o modelo do documento.
+///
+/// Guarda apenas as colunas de origem da direção do perfil, descartando o resto da linha.
+pub fn extrair_metadata(row: &Colunas, modelo: Modelo, perfil: &PerfilDeInjecao) -> DocMetadata {
+    if modelo.is_nota() {
+        DocMetadata::Nfe(row.extrair_metadados(&perfil.nfe_para_cte))
+    } else {
+        DocMetadata::Cte(row.extrair_metadados(&perfil.cte_para_nfe))
+    }
+}
+
+/// Estrutura auxiliar para acumular os dois mapas de resumos.
 #[derive(Default)]
 pub struct SummaryPair {
     pub ctes: HashMap<Chave, DocSummary>,
     pub nfes: HashMap<Chave, DocSummary>,
+    pub invalidas: ChavesInvalidas,
+    pub criterios: CriteriosDoResumo,
 }
 
 impl SummaryPair {
-    /// Mescla dois pares de resumos consumindo o segundo e fundindo-o no primeiro.
-    /// Utiliza a Entry API para evitar buscas duplas no mapa.
-    pub fn merge(mut self, other: Self) -> Self {
-        // Mesclar o mapa de CT-es
-        for (k, v) in other.ctes {
-            match self.ctes.entry(k) {
-                Entry::Occupied(mut entry) => entry.get_mut().merge(v),
-                Entry::Vacant(entry) => {
-                    entry.insert(v);
-                }
+    /// Acumula um item no resumo do seu documento.
+    ///
+    /// `ler_metadata` deserializa a linha completa apenas quando o item se torna
+    /// o novo representante, para extrair os metadados (ver `extrair_metadata`).
+    /// Nas estratégias por grupo, o item é apenas agrupado por CFOP/NCM
+    /// (ver `escolher_representantes`).
+    fn acumular<F>(&mut self, item: ItemDoResumo, ler_metadata: F) -> SpedResult<()>
+    where
+        F: FnOnce(Modelo) -> SpedResult<DocMetadata>,
+    {
+        let chave = item.chave;
+
+        // Decide em qual mapa usar com base no modelo da chave:
+        // documentos de transporte (CT-e, CT-e OS, MDF-e) ou notas (NF-e, NFC-e)
+        let modelo = chave.modelo();
+        let map = if modelo.is_transporte() {
+            &mut self.ctes
+        } else if modelo.is_nota() {
+            &mut self.nfes
+        } else {
+            return Ok(()); // Ignora se não for documento de interesse
+        };
+
+        // Chaves com DV inválido são registradas e não entram no resumo
+        if !self.invalidas.verificar(chave) {
+            return Ok(());
+        }
+
+        let doc_summary = map.entry(chave).or_default();
+
+        // Acumulação do valor total
+        doc_summary.item_valor_total += item.valor;
+
+        // Contador de itens
+        doc_summary.num_de_itens += 1;
+
+        doc_summary.item_valor_maximo = doc_summary.item_valor_maximo.max(item.valor);
+
+        // Itens das NF-es para o rateio do frete e dos tributos dos CT-es
+        if self.criterios.itens_das_nfes && modelo.is_nota() {
+            doc_summary.itens.push((item.posicao, item.valor));
+        }
+
+        // Tributos destacados nos itens dos CT-es
+        if let Some(tributos) = item.tributos.filter(|t| !t.is_zero()) {
+            *doc_summary.tributos.get_or_insert_default() += tributos;
+        }
+
+        // Modalidade do frete: prevalece o primeiro item com tomador informado
+        if doc_summary.modalidade.is_none() {
+            doc_summary.modalidade = item.modalidade;
+        }
+
+        // Documentos, datas e UFs para a verificação de consistência
+        if let Some(dados) = item.consistencia {
+            match &mut doc_summary.consistencia {
+                Some(atual) => atual.merge(*dados),
+                None => doc_summary.consistencia = Some(dados),
             }
         }
-        // Mesclar o mapa de NF-es
-        for (k, v) in other.nfes {
-            match self.nfes.entry(k) {
-                Entry::Occupied(mut entry) => entry.get_mut().merge(v),
-                Entry::Vacant(entry) => {
-                    entry.insert(v);
+
+        // Valores distintos de NCM, CFOP, CST e natureza, ponderados pelo valor do item
+        if let Some(campos) = &item.campos {
+            doc_summary.distribuicao.get_or_insert_default().registrar(
+                campos,
+                item.valor,
+                self.criterios.max_valores_distintos,
+            );
+        }
+
+        let estrategia = self.criterios.item_representativo;
+
+        let candidato = ItemCandidato {
+            valor: item.valor,
+            numero: item.numero_item,
+            posicao: item.posicao,
+        };
+
+        if estrategia.por_grupo() {
+            doc_summary.agrupar(estrategia, item.cfop_ncm, candidato);
+            return Ok(());
+        }
+
+        // Lógica de seleção do item representativo
+        if !doc_summary.supera(estrategia, &candidato) {
+            return Ok(());
+        }
+
+        doc_summary.definir_representante(candidato);
+        doc_summary.metadata = Some(ler_metadata(modelo)?);
+
+        Ok(())
+    }
+
+    /// Estratégias por grupo: define o representante de cada documento após
+    /// a leitura de todos os itens (ver `DocSummary::escolher_representante_do_grupo`).
+    ///
+    /// `ler_metadata` lê os metadados da linha iniciada na posição informada.
+    fn escolher_representantes<F>(&mut self, ler_metadata: F) -> SpedResult<()>
+    where
+        F: Fn(usize, Modelo) -> SpedResult<DocMetadata> + Sync,
+    {
+        if !self.criterios.item_representativo.por_grupo() {
+            return Ok(());
+        }
+
+        self.ctes
+            .par_iter_mut()
+            .chain(self.nfes.par_iter_mut())
+            .try_for_each(|(chave, resumo)| {
+                if let Some(item) = resumo.escolher_representante_do_grupo() {
+                    resumo.metadata = Some(ler_metadata(item.posicao, chave.modelo())?);
+                }
+                Ok(())
+            })
+    }
+
+    /// Acrescenta aos resumos do arquivo principal os resumos de referência
+    /// (`--referencia`) das chaves ausentes no arquivo principal.
+    ///
+    /// O arquivo principal prevalece: uma chave presente nos dois arquivos
+    /// não tem seus itens contados em duplicidade.
+    /// Retorna o número de chaves acrescentadas.
+    pub fn acrescentar_ausentes(
+        &self,
+        cte_info: &mut HashMap<Chave, DocSummary>,
+        nfe_info: &mut HashMap<Chave, DocSummary>,
+    ) -> usize {
+        let mut acrescentadas = 0;
+
+        for (destino, origem) in [(cte_info, &self.ctes), (nfe_info, &self.nfes)] {
+            for (chave, resumo) in origem {
+                if let Entry::Vacant(entry) = destino.entry(*chave) {
+                    entry.insert(resumo.clone());
+                    acrescentadas += 1;
                 }
             }
         }
-        self
+
+        acrescentadas
     }
 }
 
-/// Reter informações (DocSummary) do item de valor máximo da chave (NF-e ou CT-e).
+/// Coleta os resumos dos arquivos de referência (`--referencia`).
 ///
-/// Uso de Processamento em Paralelo.
-pub fn get_summaries_parallel(
-    path: &Path,
-    config: &Config,
-) -> SpedResult<(HashMap<Chave, DocSummary>, HashMap<Chave, DocSummary>)> {
-    // 1. Abertura do arquivo com tratamento de erro de I/O
-    let file = File::open(path).map_err(|e| SpedError::IoReader {
-        source: e,
-        arquivo: path.to_path_buf(),
-    })?;
-
-    // 2. Configuração do Reader CSV
-    // Buffer de 4MB para reduzir syscalls de leitura
-    let mut rdr = ReaderBuilder::new()
-        .delimiter(b';')
-        .has_headers(true) // O crate gerencia o cabeçalho automaticamente
-        .flexible(false) // Garante integridade (erro se o num de colunas variar)
-        .trim(csv::Trim::All) // Remove espaços nas extremidades
-        .quoting(true)
-        .double_quote(true)
-        .buffer_capacity(BUFFER) // Buffer de 4MB para performance
-        .from_reader(BufReader::new(file));
-
-    // 3. Processamento Paralelo (Rayon Pipeline)
-    let final_pair = rdr
-        .byte_records() // Usando ByteRecords para velocidade
-        .par_bridge() // Transforma o iterador sequencial em ParallelIterator
-        .try_fold(
-            SummaryPair::default, // Inicializador local por thread
-            |mut acc, result| -> SpedResult<SummaryPair> {
-                let record: ByteRecord = result.map_err(SpedError::Csv)?;
-
-                // Deserialização com captura detalhada de erro
-                let mut row: Colunas =
-                    record
-                        .deserialize(None)
-                        .map_err(|e| SpedError::CsvDetailed {
-                            arquivo: path.to_path_buf(),
-                            linha_numero: record.position().map(|p| p.line()).unwrap_or(0),
-                            conteudo: record
-                                .iter()
-                                .map(|b| String::from_utf8_lossy(b))
-                                .collect::<Vec<_>>()
-                                .join(";"),
-                            erro: e.to_string(),
-                        })?;
-
-                // Aplicação de Filtro de Notas Canceladas
-                if row.chave_cancelada() {
-                    return Ok(acc);
+/// Os arquivos de referência têm o mesmo layout (`Colunas`) do arquivo principal,
+/// mas apenas alimentam os resumos: nunca são reescritos.
+/// Um documento presente em mais de um arquivo de referência não é somado:
+/// prevalece o resumo do primeiro arquivo informado. Os resumos do arquivo
+/// principal, por sua vez, prevalecem sobre os de referência (`acrescentar_ausentes`).
+pub fn get_summaries_de_referencia(paths: &[PathBuf], config: &Config) -> SpedResult<SummaryPair> {
+    println!("--- Coletando resumos dos arquivos de referência ---");
+
+    let referencia = paths
+        .par_iter()
+        .map(|path| -> SpedResult<SummaryPair> {
+            let (ctes, nfes) = get_summaries(path, config)?;
+            println!(
+                " -> Arquivo de referência <{}>: {} CTes e {} NFes",
+                path.display(),
+                fmt_milhares(ctes.len()),
+                fmt_milhares(nfes.len())
+            );
+            Ok(SummaryPair {
+                ctes,
+                nfes,
+                criterios: config.into(),
+                ..Default::default()
+            })
+        })
+        .try_reduce(
+            || SummaryPair {
+                criterios: config.into(),
+                ..Default::default()
+            },
+            |mut a, b| {
+                // try_reduce preserva a ordem: `a` provém dos arquivos anteriores
+                for (chave, resumo) in b.ctes {
+                    a.ctes.entry(chave).or_insert(resumo);
                 }
-
-                // Valor do Item (f64 parseado) >= DELTA
-                let valor = match row.get_valor_do_item() {
-                    Some(v) if v.abs() >= DELTA => v.abs(),
-                    _ => return Ok(acc), // Ignora ruído
-                };
-
-                // Obtenção da chave de 44 dígitos (Chave já é um tipo forte)
-                let chave = row.chave;
-
-                // Decide em qual mapa usar com base no tipo da chave
-                let map = match (chave.is_cte(), chave.is_nfe()) {
-                    (true, _) => &mut acc.ctes,
-                    (_, true) => &mut acc.nfes,
-                    // Ignora se não for documento de interesse (Modelo 55 ou 57)
-                    // Pula para a próxima linha do CSV se não for nenhum dos dois
-                    _ => return Ok(acc),
-                };
-
-                let doc_summary = map.entry(chave).or_default();
-
-                // Acumulação do valor total
-                doc_summary.item_valor_total += valor;
-
-                // Contador de itens
-                doc_summary.num_de_itens += 1;
-
-                // Lógica de seleção do item de valor máximo
-                // Atualiza se:
-                // a) For o primeiro item encontrado (is_none)
-                // b) OU (o item atual tem valor estritamente maior que o máximo anterior)
-                if doc_summary.metadata.is_none() || valor > doc_summary.item_valor_maximo {
-                    doc_summary.item_valor_maximo = valor;
-
-                    // Sanitização Lazy: Limpa apenas o que vai ser guardado na RAM
-                    if chave.is_nfe() {
-                        Colunas::sanitizar_campo(&mut row.descricao_mercadoria);
-
-                        // Guarda apenas os 10 campos da NF-e, descartando o resto da linha
-                        doc_summary.metadata =
-                            Some(DocMetadata::Nfe(Box::new(row.extrair_nfe_metadata())));
-                    } else {
-                        Colunas::sanitizar_campo(&mut row.descricao_natureza);
-                        Colunas::sanitizar_campo(&mut row.observacoes_gerais);
-
-                        // Guarda apenas os 16 campos do CT-e, descartando o resto da linha
-                        doc_summary.metadata =
-                            Some(DocMetadata::Cte(Box::new(row.extrair_cte_metadata())));
-                    }
+                for (chave, resumo) in b.nfes {
+                    a.nfes.entry(chave).or_insert(resumo);
                 }
-
-                Ok(acc)
+                a.invalidas.merge(b.invalidas);
+                Ok(a)
             },
-        )
-        // 4. Redução: Combina os SummaryPair de todas as threads em um único resultado
-        .try_reduce(SummaryPair::default, |a, b| Ok(a.merge(b)))?;
+        )?;
 
-    // 5. Logs e Estatísticas (se verbose estiver ativado)
-    if config.verbose {
-        println!("--- Resumo do Processamento Paralelo ---");
-        println!(
-            " -> CT-es Processados: {}",
-            fmt_milhares(final_pair.ctes.len())
-        );
-        println!(
-            " -> NF-es Processadas: {}",
-            fmt_milhares(final_pair.nfes.len())
-        );
-    }
+    println!(
+        " -> Total de resumos de referência: {} CTes e {} NFes\n",
+        fmt_milhares(referencia.ctes.len()),
+        fmt_milhares(referencia.nfes.len())
+    );
 
-    // Retorna a tupla de mapas
-    Ok((final_pair.ctes, final_pair.nfes))
+    Ok(referencia)
 }
 
-/// Reter informações (DocSummary) do item de valor máximo da chave (NF-e ou CT-e).
+/// Dados de uma linha necessários ao resumo, extraídos na etapa paralela.
+struct ItemDoResumo {
+    chave: Chave,
+    valor: Decimal,
+    numero_item: u64,
+    /// Posição (byte) do início da linha no arquivo mapeado.
+    posicao: usize,
+    /// "CFOP/NCM" do item (vazio se a estratégia não agrupa os itens).
+    cfop_ncm: String,
+    /// Campos da `Distribuicao` (apenas com `--max-valores-distintos` > 0).
+    campos: Option<Box<CamposDistintos>>,
+    /// Tributos do item (apenas CT-es).
+    tributos: Option<TributosDoCte>,
+    /// Modalidade do frete do item (apenas CT-es).
+    modalidade: Option<ModalidadeDoFrete>,
+    /// Dados da verificação de consistência (CT-es e NF-es, com `--consistencia`).
+    consistencia: Option<Box<DadosDeConsistencia>>,
+}
+
+/// Resultado da leitura de uma fatia do arquivo (etapa paralela).
+#[derive(Default)]
+struct FatiaLida {
+    itens: Vec<ItemDoResumo>,
+    indice: IndiceDeLinhas,
+    qualidade: QualidadeNumerica,
+}
+
+/// Etapa paralela: interpreta as linhas da fatia `faixa` do arquivo mapeado.
 ///
-/// - O arquivo é lido.
-/// - Linhas inválidas/canceladas são puladas.
-/// - Os dados são bifurcados em dois destinos.
-/// - O "melhor" item (valor máximo) é preservado.
-pub fn get_summaries(
-    path: &Path,
+/// - Linhas canceladas ou com valor desprezível (< DELTA) não geram itens.
+/// - Os valores (e os tributos dos CT-es) são interpretados em `config.formato_numerico`;
+///   valores inválidos ou ambíguos são contabilizados em `QualidadeNumerica`.
+/// - Com `info`, registra as posições das linhas candidatas à Passagem 2
+///   (ver `pode_ser_enriquecida`) e conta as linhas já enriquecidas; com
+///   `--modalidade-frete`, todas as linhas de CT-e não canceladas são candidatas.
+///   As linhas já enriquecidas são candidatas apenas se houver colunas
+///   acrescentadas (ver `Config::acrescenta_colunas`).
+fn ler_fatia(
+    arquivo: &ArquivoMapeado,
+    mapa: &MapaDeColunas,
     config: &Config,
-) -> SpedResult<(HashMap<Chave, DocSummary>, HashMap<Chave, DocSummary>)> {
-    let mut cte_summaries: HashMap<Chave, DocSummary> = HashMap::new();
-    let mut nfe_summaries: HashMap<Chave, DocSummary> = HashMap::new();
+    info: Option<&Informacoes>,
+    faixa: Range<usize>,
+) -> SpedResult<FatiaLida> {
+    let base = faixa.start;
+    let fim_da_fatia = faixa.end;
+    let mut rdr = leitor(&arquivo.bytes()[faixa]);
+    let mut record = ByteRecord::new();
+    let mut buf = ByteRecord::new();
+    let mut lida = FatiaLida::default();
+    // A última candidata termina onde começa a linha seguinte
+    let mut candidata_aberta = false;
+
+    while rdr.read_byte_record(&mut record)? {
+        let posicao = base + record.position().map_or(0, |p| p.byte() as usize);
+
+        if candidata_aberta {
+            if let Some(ultima) = lida.indice.candidatas.last_mut() {
+                ultima.end = posicao;
+            }
+            candidata_aberta = false;
+        }
 
-    let file = File::open(path).map_err(|e| SpedError::IoReader {
-        source: e,
-        arquivo: path.to_path_buf(),
-    })?;
+        lida.indice.linhas += 1;
+
+        if record.len() != mapa.num_colunas() {
+            return Err(arquivo.erro_detalhado(
+                posicao,
+                &record,
+                format!(
+                    "{} campos; o cabeçalho possui {} colunas",
+                    record.len(),
+                    mapa.num_colunas()
+                ),
+            ));
+        }
+
+        let row = mapa
+            .deserializar_bytes(&record, &mut buf)
+            .map_err(|e| arquivo.erro_detalhado(posicao, &record, e))?;
 
-    let mut rdr = ReaderBuilder::new()
-        .delimiter(b';')
-        .has_headers(true) // O crate gerencia o cabeçalho automaticamente
-        .flexible(false) // Garante integridade (erro se o num de colunas variar)
-        .trim(csv::Trim::All) // Remove espaços nas extremidades
-        .quoting(true)
-        .double_quote(true)
-        .buffer_capacity(BUFFER)
-        .from_reader(BufReader::new(file));
-
-    // Usamos records() em vez de deserialize() para ter acesso à linha bruta em caso de erro
-    // for result in rdr.deserialize::<Colunas>() {
-    // let mut row: Colunas = result?;
-    for result in rdr.records() {
-        let record = result.map_err(SpedError::Csv)?;
-
-        // Deserialização com captura detalhada de erro
-        let mut row: Colunas = record
-            .deserialize(None)
-            .map_err(|e| SpedError::CsvDetailed {
-                arquivo: path.to_path_buf(),
-                linha_numero: record.position().map(|p| p.line()).unwrap_or(0),
-                conteudo: record.iter().collect::<Vec<_>>().join(";"),
-                erro: e.to_string(),
-            })?;
+        // Posições para a Passagem 2
+        if let Some(info) = info {
+            let ja_enriquecida = row.ja_enriquecida();
+            if ja_enriquecida {
+                lida.indice.ja_enriquecidas += 1;
+            }
+            // Linhas já enriquecidas são candidatas apenas às colunas acrescentadas
+            if (!ja_enriquecida || config.acrescenta_colunas())
+                && (pode_ser_enriquecida(&row, info)
+                    || (config.modalidade_frete
+                        && row.chave.modelo().is_conhecimento()
+                        && !row.chave_cancelada()))
+            {
+                lida.indice.candidatas.push(posicao..posicao);
+                candidata_aberta = true;
+            }
+        }
 
         // Aplicação de Filtro de Notas Canceladas
         if row.chave_cancelada() {
             continue;
         }
 
-        // Valor do Item (f64 parseado) >= DELTA
-        let valor = match row.get_valor_do_item() {
+        let numero = row.valor_do_item(config.formato_numerico);
+        lida.qualidade
+            .registrar(COLUNA_VALOR_ITEM, &row.valor_item, &numero);
+
+        // Valor do Item (decimal exato) >= DELTA
+        let valor = match numero.valor() {
             Some(v) if v.abs() >= DELTA => v.abs(),
-            _ => continue, // Ignora ruído
+            _ => continue, // Ignora ruído (os inválidos constam do relatório de qualidade)
         };
 
-        // Obtenção da chave de 44 dígitos (Chave já é um tipo forte)
-        let chave = row.chave;
+        let is_conhecimento = row.chave.modelo().is_conhecimento();
+        let tributos = is_conhecimento
+            .then(|| TributosDoCte::ler(&row, config.formato_numerico, &mut lida.qualidade));
+        let modalidade = is_conhecimento
+            .then(|| ModalidadeDoFrete::da_linha(&row))
+            .flatten();
+        let consistencia = (config.consistencia
+            && (is_conhecimento || row.chave.modelo().is_nota()))
+        .then(|| Box::new(DadosDeConsistencia::ler(&row)));
+
+        lida.itens.push(ItemDoResumo {
+            chave: row.chave,
+            valor,
+            // Itens sem número ficam por último no desempate
+            numero_item: row.numero_item.trim().parse().unwrap_or(u64::MAX),
+            posicao,
+            cfop_ncm: if config.item_representativo.por_grupo() {
+                format!("{}/{}", row.cfop.trim(), row.ncm.trim())
+            } else {
+                String::new()
+            },
+            campos: (config.max_valores_distintos > 0)
+                .then(|| Box::new(CamposDistintos::new(&row))),
+            tributos,
+            modalidade,
+            consistencia,
+        });
+    }
 
-        // Decide em qual mapa usar com base no tipo da chave
-        let map = match (chave.is_cte(), chave.is_nfe()) {
-            (true, _) => &mut cte_summaries,
-            (_, true) => &mut nfe_summaries,
-            _ => continue, // Pula para a próxima linha do CSV se não for nenhum dos dois
-        };
+    if candidata_aberta && let Some(ultima) = lida.indice.candidatas.last_mut() {
+        ultima.end = fim_da_fatia;
+    }
 
-        let doc_summary = map.entry(chave).or_default();
+    Ok(lida)
+}
 
-        // Acumulação do valor total
-        doc_summary.item_valor_total += valor;
+/// Reter informações (DocSummary) do item representativo da chave (NF-e ou CT-e).
+///
+/// Ver `get_summaries_e_indice`.
+pub fn get_summaries(
+    path: &Path,
+    config: &Config,
+) -> SpedResult<(HashMap<Chave, DocSummary>, HashMap<Chave, DocSummary>)> {
+    let (pair, _) = get_summaries_e_indice(path, config, None)?;
+    Ok((pair.ctes, pair.nfes))
+}
 
-        // Contador de itens
-        doc_summary.num_de_itens += 1;
+/// Passagem 1: resumos (DocSummary) do item representativo da chave (NF-e ou CT-e)
+/// e, com `info`, o índice das linhas candidatas à Passagem 2.
+///
+/// - O arquivo é mapeado em memória (mmap) e dividido em fatias alinhadas às linhas.
+/// - Cada bloco de `config.linhas_por_bloco()` linhas é interpretado em paralelo
+///   (Rayon), uma fatia por tarefa, diretamente sobre o arquivo mapeado.
+/// - Os itens são acumulados na ordem do arquivo: somas e desempates não
+///   dependem do número de threads (`--threads`).
+/// - Linhas inválidas/canceladas são puladas.
+/// - Os dados são bifurcados em dois destinos.
+/// - O item representativo é escolhido por `config.item_representativo`; em caso
+///   de empate, prevalece o menor número de item e, depois, a linha anterior
+///   (ver `ItemRepresentativo::precede`).
+pub fn get_summaries_e_indice(
+    path: &Path,
+    config: &Config,
+    info: Option<&Informacoes>,
+) -> SpedResult<(SummaryPair, IndiceDeLinhas)> {
+    // 1. Mapeamento do arquivo em memória
+    let arquivo = ArquivoMapeado::abrir(path, config.codificacao)?;
+
+    // Correspondência entre o cabeçalho do arquivo e os campos de Colunas
+    let mapa = MapaDeColunas::new(&arquivo.cabecalho()?, config, path)?;
+    mapa.relatar(path);
+
+    // Os itens das NF-es são guardados apenas para o arquivo a ser enriquecido (`info`):
+    // nos arquivos de referência, as posições das linhas não têm utilidade
+    let criterios = CriteriosDoResumo::from(config);
+    let mut pair = SummaryPair {
+        criterios: CriteriosDoResumo {
+            itens_das_nfes: criterios.itens_das_nfes && info.is_some(),
+            ..criterios
+        },
+        ..Default::default()
+    };
+    let mut indice = IndiceDeLinhas::default();
+    let mut qualidade = QualidadeNumerica::default();
+
+    // 2. Fatias alinhadas às linhas, agrupadas em blocos (memória limitada)
+    let linhas_por_bloco = config.linhas_por_bloco();
+    let linhas_por_fatia = LINHAS_POR_FATIA.min(linhas_por_bloco);
+    let fatias = arquivo.fatias(linhas_por_fatia);
+
+    let mut record = ByteRecord::new();
+    let mut buf = ByteRecord::new();
+
+    for bloco in fatias.chunks((linhas_por_bloco / linhas_por_fatia).max(1)) {
+        // 3. Etapa paralela: deserialização e filtros (ordem preservada no collect)
+        let lidas: Vec<FatiaLida> = bloco
+            .par_iter()
+            .map(|faixa| ler_fatia(&arquivo, &mapa, config, info, faixa.clone()))
+            .collect::<SpedResult<_>>()?;
+
+        // 4. Etapa sequencial: acumulação na ordem do arquivo
+        for lida in lidas {
+            for item in lida.itens {
+                let posicao = item.posicao;
+                // A linha é lida novamente apenas se o item for o novo representante
+                pair.acumular(item, |modelo| {
+                    let bytes = &arquivo.bytes()[posicao..];
+                    ler_registro(bytes, &mut record)?;
+                    let row = mapa
+                        .deserializar_bytes(&record, &mut buf)
+                        .map_err(|e| arquivo.erro_detalhado(posicao, &record, e))?;
+                    Ok(extrair_metadata(&row, modelo, &config.perfil))
+                })?;
+            }
 
-        // Lógica de seleção do item de valor máximo
-        // Atualiza se:
-        // a) For o primeiro item encontrado (is_none)
-        // b) OU (o item atual tem valor estritamente maior que o máximo anterior)
-        if doc_summary.metadata.is_none() || valor > doc_summary.item_valor_maximo {
-            doc_summary.item_valor_maximo = valor;
+            indice.linhas += lida.indice.linhas;
+            indice.ja_enriquecidas += lida.indice.ja_enriquecidas;
+            indice.candidatas.extend(lida.indice.candidatas);
+            qualidade.merge(lida.qualidade);
+        }
+    }
 
-            // Sanitização Lazy: Limpa apenas o que vai ser guardado na RAM
-            if chave.is_nfe() {
-                Colunas::sanitizar_campo(&mut row.descricao_mercadoria);
+    // 5. Estratégias por grupo CFOP/NCM: o representante é conhecido apenas agora
+    pair.escolher_representantes(|posicao, modelo| {
+        let (mut record, mut buf) = (ByteRecord::new(), ByteRecord::new());
+        ler_registro(&arquivo.bytes()[posicao..], &mut record)?;
+        let row = mapa
+            .deserializar_bytes(&record, &mut buf)
+            .map_err(|e| arquivo.erro_detalhado(posicao, &record, e))?;
+        Ok(extrair_metadata(&row, modelo, &config.perfil))
+    })?;
 
-                // Guarda apenas os 10 campos da NF-e, descartando o resto da linha
-                doc_summary.metadata = Some(DocMetadata::Nfe(Box::new(row.extrair_nfe_metadata())));
-            } else {
-                Colunas::sanitizar_campo(&mut row.descricao_natureza);
-                Colunas::sanitizar_campo(&mut row.observacoes_gerais);
+    pair.invalidas.print_log(&path.display().to_string());
+    qualidade.imprimir(path);
 
-                // Guarda apenas os 16 campos do CT-e, descartando o resto da linha
-                doc_summary.metadata = Some(DocMetadata::Cte(Box::new(row.extrair_cte_metadata())));
-            }
+    // 6. Logs e Estatísticas (se verbose estiver ativado)
+    if config.verbose {
+        println!(" -> CT-es Processados: {}", fmt_milhares(pair.ctes.len()));
+        println!(" -> NF-es Processadas: {}", fmt_milhares(pair.nfes.len()));
+        if info.is_some() {
+            println!(
+                " -> Linhas com documentos relacionados: {}",
+                fmt_milhares(indice.candidatas.len())
+            );
         }
     }
 
-    if config.verbose {
-        println!(
-            " -> CT-es Processados: {}",
-            fmt_milhares(cte_summaries.len()),
-        );
-        println!(
-            " -> NF-es Processadas: {}",
-            fmt_milhares(nfe_summaries.len()),
-        );
-    }
+    Ok((pair, indice))
+}
+
+/// Filtra os documentos relacionados que possuem resumo e os ordena:
+/// 1º Valor Máximo (Desc), 2º Valor Total (Desc), 3º Chave (Asc)
+pub fn documentos_com_resumo<'b>(
+    relacionados: &'b HashSet<Chave>,
+    resumos: &'b HashMap<Chave, DocSummary>,
+) -> Vec<(&'b Chave, &'b DocSummary)> {
+    let mut validos: Vec<(&Chave, &DocSummary)> = relacionados
+        .iter()
+        .filter_map(|c| resumos.get(c).map(|info| (c, info)))
+        .collect();
+
+    validos.sort_unstable_by(|a, b| {
+        b.1.item_valor_maximo
+            .cmp(&a.1.item_valor_maximo)
+            .then_with(|| b.1.item_valor_total.cmp(&a.1.item_valor_total))
+            .then_with(|| a.0.cmp(b.0))
+    });
 
-    Ok((cte_summaries, nfe_summaries))
+    validos
+}
+
+/// Formata a lista de documentos relacionados.
+///
+/// Exemplo: "2 CTes: [chave1, chave2] de valor total = 1500.00"
+fn descrever_relacionados(documentos: &[(&Chave, &DocSummary)], rotulo: &str) -> String {
+    let soma_total: Decimal = documentos.iter().map(|d| d.1.item_valor_total).sum();
+    let lista_chaves = documentos
+        .iter()
+        .map(|d| d.0.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let num_docs = documentos.len();
+    let plural = if num_docs > 1 { "s" } else { "" };
+
+    format!(
+        "{} {}{}: [{}] de valor total = {}",
+        num_docs,
+        rotulo,
+        plural,
+        lista_chaves,
+        decimal_to_str(soma_total)
+    )
 }
 
 /// Adiciona informações de CT-es relacionados diretamente na struct Colunas da NF-e.
@@ -338,141 +828,176 @@ pub fn adicionar_info_de_ctes_em_nfe(
     config: &Config,
     info: &Informacoes,
     cte_info: &HashMap<Chave, DocSummary>,
-) -> bool {
+) -> Alteracao {
     // 1. A chave da NFe é obtida da própria struct
     let chave_nfe = row_nfe.chave;
 
     // 2. Busca os CT-es relacionados à esta NF-e no índice de transitividade
     let Some(ctes_relacionados) = info.nfe_ctes.get(&chave_nfe) else {
         // row_nfe.chave_de_acesso = format!("NFe: {}, 0 CTe: [] de valor total = 0", chave_nfe).into();
-        return false; // Não houve alteração
+        return Alteracao::Nenhuma;
     };
 
-    // 3. Filtra CT-es que possuem resumo e mapeia para referências
-    let mut valid_ctes: Vec<(&Chave, &DocSummary)> = ctes_relacionados
-        .iter()
-        .filter_map(|c| cte_info.get(c).map(|info| (c, info)))
-        .collect();
+    // 3. Filtra CT-es que possuem resumo e ordena
+    let valid_ctes = documentos_com_resumo(ctes_relacionados, cte_info);
 
     if valid_ctes.is_empty() {
-        return false; // Não houve alteração
+        return Alteracao::Nenhuma;
     }
 
-    // 4. Ordenação:
-    // 1º Valor Máximo (Desc), 2º Valor Total (Desc), 3º Chave (Asc)
-    valid_ctes.sort_unstable_by(|a, b| {
-        b.1.item_valor_maximo
-            .partial_cmp(&a.1.item_valor_maximo)
-            .unwrap_or(std::cmp::Ordering::Equal)
-            .then_with(|| {
-                b.1.item_valor_total
-                    .partial_cmp(&a.1.item_valor_total)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            })
-            .then_with(|| a.0.cmp(b.0))
-    });
-
-    // 5. Formatação da string de resumo para a coluna "Chave de Acesso"
-    let soma_total: f64 = valid_ctes.iter().map(|c| c.1.item_valor_total).sum();
-    let lista_chaves = valid_ctes
-        .iter()
-        .map(|c| c.0.as_str())
-        .collect::<Vec<_>>()
-        .join(", ");
-    let num_ctes = valid_ctes.len();
-    let plural = if num_ctes > 1 { "s" } else { "" };
-
-    // 6. Atualização do campo "chave_de_acesso" diretamente na struct
+    // 4. Atualização do campo "chave_de_acesso" diretamente na struct
     row_nfe.chave_de_acesso = format!(
-        "NFe: {}, {} CTe{}: [{}] de valor total = {}",
+        "NFe: {}, {}",
         chave_nfe,
-        num_ctes,
-        plural,
-        lista_chaves,
-        f64_to_str(soma_total)
+        descrever_relacionados(&valid_ctes, "CTe")
     )
     .into();
 
-    // 7. Injeção de Metadados (As 16 colunas do CT-e injetadas na NF-e)
-    // take(config.max_info) limita a quantidade de documentos cujos dados serão concatenados
-    for (_, summary) in valid_ctes.iter().take(config.max_info) {
+    // 5. Injeção de Metadados (colunas do CT-e definidas no perfil de injeção)
+    // take(limite) limita a quantidade de documentos cujos dados serão concatenados
+    let direcao = &config.perfil.cte_para_nfe;
+    let mut completo = true;
+    for (ordem, (_, summary)) in valid_ctes
+        .iter()
+        .take(direcao.limite(config.max_info))
+        .enumerate()
+    {
         // Pattern match para garantir que estamos extraindo metadados de CT-e
         if let Some(DocMetadata::Cte(c)) = &summary.metadata {
-            row_nfe.injetar_metadata_cte(config, c);
+            completo &= row_nfe.injetar_metadados(config, direcao, c, ordem);
+        }
+        if let Some(d) = &summary.distribuicao {
+            completo &= row_nfe.injetar_distribuicao(config, d, direcao.rotulo);
         }
     }
 
-    true
+    Alteracao::de_injecao(completo)
 }
 
-/// Adiciona informações de NF-es relacionadas em um CT-e seguindo estritamente
-/// as 10 colunas de interesse na ordem definida.
+/// Adiciona informações de NF-es relacionadas em um CT-e seguindo as colunas
+/// de `PerfilDeInjecao::nfe_para_cte`.
 pub fn adicionar_info_de_nfes_em_cte(
     row_cte: &mut Colunas,
     config: &Config,
     info: &Informacoes,
     nfe_info: &HashMap<Chave, DocSummary>,
-) -> bool {
+) -> Alteracao {
     let chave_cte = row_cte.chave;
 
     // 1. Busca as NF-es relacionadas a este CT-e no índice de transitividade
     let Some(nfes_relacionadas) = info.cte_nfes.get(&chave_cte) else {
-        return false; // Não houve alteração
+        return Alteracao::Nenhuma;
     };
 
-    // 2. Filtra NF-es que possuem resumo válido e mapeia para referências
-    let mut valid_nfes: Vec<(&Chave, &DocSummary)> = nfes_relacionadas
-        .iter()
-        .filter_map(|n| nfe_info.get(n).map(|info| (n, info)))
-        .collect();
+    // 2. Filtra NF-es que possuem resumo válido e ordena (Paridade com Perl)
+    let valid_nfes = documentos_com_resumo(nfes_relacionadas, nfe_info);
 
     if valid_nfes.is_empty() {
-        return false; // Não houve alteração
+        return Alteracao::Nenhuma;
     }
 
-    // 3. Ordenação (Paridade com Perl):
-    // 1º Valor Máximo (Desc), 2º Valor Total (Desc), 3º Chave (Asc)
-    valid_nfes.sort_unstable_by(|a, b| {
-        b.1.item_valor_maximo
-            .partial_cmp(&a.1.item_valor_maximo)
-            .unwrap_or(std::cmp::Ordering::Equal)
-            .then_with(|| {
-                b.1.item_valor_total
-                    .partial_cmp(&a.1.item_valor_total)
-                    .unwrap_or(std::cmp::Ordering::Equal)
-            })
-            .then_with(|| a.0.cmp(b.0))
-    });
-
-    // 4. Atualiza o cabeçalho da célula "Chave de Acesso"
-    let soma_total: f64 = valid_nfes.iter().map(|n| n.1.item_valor_total).sum();
-    let lista_chaves = valid_nfes
-        .iter()
-        .map(|n| n.0.as_str())
-        .collect::<Vec<_>>()
-        .join(", ");
-    let num_nfes = valid_nfes.len();
-    let plural = if num_nfes > 1 { "s" } else { "" };
-
+    // 3. Atualiza o cabeçalho da célula "Chave de Acesso"
     row_cte.chave_de_acesso = format!(
-        "CTe: {}, {} NFe{}: [{}] de valor total = {}",
+        "CTe: {}, {}",
         chave_cte,
-        num_nfes,
-        plural,
-        lista_chaves,
-        f64_to_str(soma_total)
+        descrever_relacionados(&valid_nfes, "NFe")
     )
     .into();
 
-    // 5. Injeção dos metadados das NF-es (10 colunas específicas)
-    for (_, summary) in valid_nfes.iter().take(config.max_info) {
+    // 4. Injeção dos metadados das NF-es (colunas definidas no perfil de injeção)
+    let direcao = &config.perfil.nfe_para_cte;
+    let mut completo = true;
+    for (ordem, (_, summary)) in valid_nfes
+        .iter()
+        .take(direcao.limite(config.max_info))
+        .enumerate()
+    {
         // Pattern match para extrair especificamente os metadados de NF-e
         if let Some(DocMetadata::Nfe(n)) = &summary.metadata {
-            row_cte.injetar_metadata_nfe(config, n);
+            completo &= row_cte.injetar_metadados(config, direcao, n, ordem);
+        }
+        if let Some(d) = &summary.distribuicao {
+            completo &= row_cte.injetar_distribuicao(config, d, direcao.rotulo);
+        }
+    }
+
+    Alteracao::de_injecao(completo)
+}
+
+/// Adiciona informações dos CT-es manifestados em um MDF-e.
+///
+/// A coluna "Chave de Acesso" recebe a lista de CT-es e os metadados
+/// de CT-e (`PerfilDeInjecao::cte_para_nfe`) são injetados como em uma NF-e.
+pub fn adicionar_info_de_ctes_em_mdfe(
+    row_mdfe: &mut Colunas,
+    config: &Config,
+    info: &Informacoes,
+    cte_info: &HashMap<Chave, DocSummary>,
+) -> Alteracao {
+    let chave_mdfe = row_mdfe.chave;
+
+    let Some(ctes_relacionados) = info.mdfe_ctes.get(&chave_mdfe) else {
+        return Alteracao::Nenhuma;
+    };
+
+    let valid_ctes = documentos_com_resumo(ctes_relacionados, cte_info);
+
+    if valid_ctes.is_empty() {
+        return Alteracao::Nenhuma;
+    }
+
+    row_mdfe.chave_de_acesso = format!(
+        "MDFe: {}, {}",
+        chave_mdfe,
+        descrever_relacionados(&valid_ctes, "CTe")
+    )
+    .into();
+
+    let direcao = &config.perfil.cte_para_nfe;
+    let mut completo = true;
+    for (ordem, (_, summary)) in valid_ctes
+        .iter()
+        .take(direcao.limite(config.max_info))
+        .enumerate()
+    {
+        if let Some(DocMetadata::Cte(c)) = &summary.metadata {
+            completo &= row_mdfe.injetar_metadados(config, direcao, c, ordem);
+        }
+        if let Some(d) = &summary.distribuicao {
+            completo &= row_mdfe.injetar_distribuicao(config, d, direcao.rotulo);
         }
     }
 
-    true
+    Alteracao::de_injecao(completo)
+}
+
+/// Adiciona ao CT-e a lista dos MDF-es que o manifestam.
+///
+/// Os resumos de MDF-e ficam no mesmo mapa dos CT-es (documentos de transporte).
+/// A informação é acrescentada à coluna "Chave de Acesso" como " [Info do MDF-e: ...]".
+pub fn adicionar_info_de_mdfes_em_cte(
+    row_cte: &mut Colunas,
+    config: &Config,
+    info: &Informacoes,
+    cte_info: &HashMap<Chave, DocSummary>,
+) -> Alteracao {
+    let Some(mdfes_relacionados) = info.cte_mdfes.get(&row_cte.chave) else {
+        return Alteracao::Nenhuma;
+    };
+
+    let valid_mdfes = documentos_com_resumo(mdfes_relacionados, cte_info);
+
+    if valid_mdfes.is_empty() {
+        return Alteracao::Nenhuma;
+    }
+
+    let resumo = descrever_relacionados(&valid_mdfes, "MDFe");
+
+    if config.append(&mut row_cte.chave_de_acesso, &resumo, "MDF-e") {
+        Alteracao::Enriquecida
+    } else {
+        Alteracao::Truncada
+    }
 }
 
 //----------------------------------------------------------------------------//
@@ -488,3 +1013,9 @@ pub fn adicionar_info_de_nfes_em_cte(
 #[cfg(test)]
 #[path = "tests/info_adicionadas.rs"]
 mod info_adicionadas;
+
+/// Run tests with:
+/// cargo test -- --show-output resumos_tests
+#[cfg(test)]
+#[path = "tests/resumos_tests.rs"]
+mod resumos_tests;
@@ -0,0 +1,198 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use std::{collections::HashMap, ops::AddAssign};
+
+use crate::{Chave, DocSummary, Informacoes, fmt_milhares};
+
+/// Nome da coluna do frete rateado por item de NF-e (`--rateio-frete`).
+pub const COLUNA_FRETE_RATEADO: &str = "Frete rateado (CT-e)";
+
+/// Um centavo: unidade do rateio.
+const CENTAVO: Decimal = Decimal::from_parts(1, 0, 0, false, 2);
+
+/// Rateia `total` proporcionalmente aos `pesos`, em centavos.
+///
+/// O total é arredondado a 2 casas e a soma das parcelas é exatamente igual a ele
+/// (método do maior resto): cada parcela é truncada em centavos e os centavos
+/// restantes vão para as parcelas com as maiores frações descartadas
+/// (empate: a primeira parcela). Totais e pesos negativos são aceitos: os
+/// centavos são então subtraídos. Pesos de soma nula recebem partes iguais.
+pub fn ratear(total: Decimal, pesos: &[Decimal]) -> Vec<Decimal> {
+    if pesos.is_empty() {
+        return Vec::new();
+    }
+
+    let total = total.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero);
+    let soma: Decimal = pesos.iter().sum();
+
+    let exatas: Vec<Decimal> = if soma.is_zero() {
+        vec![total / Decimal::from(pesos.len()); pesos.len()]
+    } else {
+        pesos.iter().map(|peso| total * peso / soma).collect()
+    };
+
+    let mut parcelas: Vec<Decimal> = exatas
+        .iter()
+        .map(|exata| exata.round_dp_with_strategy(2, RoundingStrategy::ToZero))
+        .collect();
+
+    // Centavos restantes: positivos ou negativos, conforme o sinal do total
+    // e dos pesos. Cada parcela é ajustada no máximo uma vez.
+    let mut restante = total - parcelas.iter().sum::<Decimal>();
+    let passo = if restante.is_sign_negative() {
+        -CENTAVO
+    } else {
+        CENTAVO
+    };
+
+    // Maiores frações descartadas (no sentido do ajuste) primeiro
+    let fracao = |i: usize| (exatas[i] - parcelas[i]) * passo;
+    let mut ordem: Vec<usize> = (0..parcelas.len()).collect();
+    ordem.sort_by(|&a, &b| fracao(b).cmp(&fracao(a)).then(a.cmp(&b)));
+
+    for i in ordem {
+        if restante.abs() < CENTAVO {
+            break;
+        }
+        parcelas[i] += passo;
+        restante -= passo;
+    }
+
+    parcelas
+}
+
+/// Valor que pode ser rateado proporcionalmente a pesos (ver `ratear`).
+pub trait Rateavel: Copy + Default + AddAssign {
+    /// Parcelas proporcionais aos `pesos`, cuja soma é igual a `self`.
+    fn ratear(&self, pesos: &[Decimal]) -> Vec<Self>;
+}
+
+impl Rateavel for Decimal {
+    fn ratear(&self, pesos: &[Decimal]) -> Vec<Self> {
+        ratear(*self, pesos)
+    }
+}
+
+/// Valores dos CT-es rateados entre os itens das NF-es:
+///
+/// 1. O valor de cada CT-e é rateado entre as suas NF-es com resumo,
+///    proporcionalmente ao valor total de cada NF-e;
+/// 2. O valor de cada NF-e (soma das parcelas dos seus CT-es) é rateado entre
+///    os seus itens, proporcionalmente ao valor de cada item.
+///
+/// As NF-es dos arquivos de referência participam do rateio dos CT-es,
+/// mas apenas os itens do arquivo em processamento recebem parcelas.
+/// Assim, a soma das parcelas dos itens de todas as NF-es nunca excede o valor do CT-e.
+#[derive(Debug, Default, Clone)]
+pub struct RateioPorItem<T> {
+    /// Parcela do item, pela posição (byte) do início da linha.
+    por_item: HashMap<usize, T>,
+    /// Número de NF-es com itens rateados.
+    nfes: usize,
+}
+
+/// Etapa 1 de `RateioPorItem`: parcela de cada NF-e com resumo em `valor_do_cte`
+/// dos seus CT-es (`None`: CT-e sem valor a ratear).
+///
+/// A soma das parcelas dos itens de uma NF-e é igual à parcela da NF-e.
+pub fn ratear_por_nfe<T: Rateavel>(
+    info: &Informacoes,
+    cte_info: &HashMap<Chave, DocSummary>,
+    nfe_info: &HashMap<Chave, DocSummary>,
+    valor_do_cte: impl Fn(&DocSummary) -> Option<T>,
+) -> HashMap<Chave, T> {
+    let mut valor_por_nfe: HashMap<Chave, T> = HashMap::new();
+    for (cte, nfes) in &info.cte_nfes {
+        let Some(valor) = cte_info.get(cte).and_then(&valor_do_cte) else {
+            continue;
+        };
+
+        // Ordem das chaves: rateio independente da ordem do HashSet
+        let mut nfes: Vec<(&Chave, &DocSummary)> = nfes
+            .iter()
+            .filter_map(|nfe| nfe_info.get(nfe).map(|resumo| (nfe, resumo)))
+            .collect();
+        nfes.sort_unstable_by_key(|(nfe, _)| *nfe);
+
+        let pesos: Vec<Decimal> = nfes.iter().map(|(_, r)| r.item_valor_total).collect();
+        for ((nfe, _), parcela) in nfes.iter().zip(valor.ratear(&pesos)) {
+            *valor_por_nfe.entry(**nfe).or_default() += parcela;
+        }
+    }
+    valor_por_nfe
+}
+
+impl<T: Rateavel> RateioPorItem<T> {
+    /// Rateia `valor_do_cte` (`None`: CT-e sem valor a ratear).
+    pub fn ratear(
+        info: &Informacoes,
+        cte_info: &HashMap<Chave, DocSummary>,
+        nfe_info: &HashMap<Chave, DocSummary>,
+        valor_do_cte: impl Fn(&DocSummary) -> Option<T>,
+    ) -> Self {
+        // 1. CT-e -> NF-es
+        let valor_por_nfe = ratear_por_nfe(info, cte_info, nfe_info, valor_do_cte);
+
+        // 2. NF-e -> itens
+        let mut por_item = HashMap::new();
+        let mut nfes_com_itens = 0;
+        for (nfe, valor) in &valor_por_nfe {
+            let itens = &nfe_info[nfe].itens;
+            if itens.is_empty() {
+                continue; // NF-e de arquivo de referência
+            }
+            nfes_com_itens += 1;
+
+            let pesos: Vec<Decimal> = itens.iter().map(|(_, valor)| *valor).collect();
+            let posicoes = itens.iter().map(|(posicao, _)| *posicao);
+            por_item.extend(posicoes.zip(valor.ratear(&pesos)));
+        }
+
+        RateioPorItem {
+            por_item,
+            nfes: nfes_com_itens,
+        }
+    }
+
+    /// Parcela do item da linha iniciada em `posicao` (`None`: sem parcela).
+    pub fn valor(&self, posicao: usize) -> Option<T> {
+        self.por_item.get(&posicao).copied()
+    }
+
+    pub fn imprimir(&self, descricao: &str) {
+        println!(
+            " -> {descricao}: {} itens de {} NF-es",
+            fmt_milhares(self.por_item.len()),
+            fmt_milhares(self.nfes)
+        );
+    }
+}
+
+/// Frete dos CT-es (valor total) rateado entre os itens das NF-es (`--rateio-frete`).
+pub type RateioDeFrete = RateioPorItem<Decimal>;
+
+impl RateioDeFrete {
+    pub fn new(
+        info: &Informacoes,
+        cte_info: &HashMap<Chave, DocSummary>,
+        nfe_info: &HashMap<Chave, DocSummary>,
+    ) -> Self {
+        Self::ratear(info, cte_info, nfe_info, |resumo| {
+            Some(resumo.item_valor_total)
+        })
+    }
+
+    /// Frete do item da linha iniciada em `posicao` (`None`: sem frete).
+    pub fn frete(&self, posicao: usize) -> Option<Decimal> {
+        self.valor(posicao)
+    }
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output rateio_tests
+#[cfg(test)]
+#[path = "tests/rateio_tests.rs"]
+mod rateio_tests;
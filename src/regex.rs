@@ -5,3 +5,14 @@ use std::sync::LazyLock;
 pub static RE_MULTISPACE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s{2,}").unwrap());
 pub static RE_NON_DIGITS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\D").unwrap());
 pub static RE_CHAVE_44: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d{44})$").unwrap());
+
+// Anotações inseridas por Config::append: " [Info do CT-e: ...]", " [Info da NF-e: ...]", etc.
+// Em colunas originalmente vazias, o espaço inicial é removido pelo csv::Trim::All.
+pub static RE_ANOTACAO: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:^| )\[Info d[ao] [A-Z][A-Za-z-]*: ").unwrap());
+
+// Coluna "Chave de Acesso" reescrita: "NFe: <chave>, 2 CTes: [...] de valor total = 10.00"
+pub static RE_CHAVE_DE_ACESSO_ENRIQUECIDA: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:NFe|CTe|MDFe): \d{44}, \d+ (?:CTe|NFe)s?: \[[\d, ]*\] de valor total = ")
+        .unwrap()
+});
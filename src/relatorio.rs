@@ -0,0 +1,185 @@
+use std::collections::HashMap;
+
+use crate::{
+    Alteracao, Chave, DocSummary, Informacoes, Modelo, TributosDoCte, decimal_com_sinal_to_str,
+    fmt_milhares, tributos_por_nfe,
+};
+
+/// Estatísticas da Passagem 2 (enriquecimento).
+///
+/// No modo `--dry-run` é o único resultado produzido: nenhum CSV é gravado.
+#[derive(Debug, Default, Clone)]
+pub struct Relatorio {
+    /// Número total de linhas do arquivo (incluindo o cabeçalho).
+    pub linhas: usize,
+    /// Linhas de NF-e (ou NFC-e) enriquecidas com informações de CT-es.
+    pub nfes_enriquecidas: usize,
+    /// Linhas de CT-e (ou CT-e OS) enriquecidas com informações de NF-es/MDF-es.
+    pub ctes_enriquecidos: usize,
+    /// Linhas de MDF-e enriquecidas com informações de CT-es.
+    pub mdfes_enriquecidos: usize,
+    /// Linhas restauradas pelo modo `--limpar`.
+    pub linhas_restauradas: usize,
+    /// Linhas já enriquecidas em execução anterior (mantidas sem alteração).
+    pub ja_enriquecidas: usize,
+    /// Linhas em que alguma informação foi descartada pelo limite `max_char`.
+    pub linhas_truncadas: usize,
+    /// Relações NF-e -> CT-e cujo CT-e não possui resumo em `cte_info`.
+    pub ctes_sem_resumo: usize,
+    /// Relações CT-e -> NF-e cuja NF-e não possui resumo em `nfe_info`.
+    pub nfes_sem_resumo: usize,
+    /// NF-es com parcela nos tributos dos CT-es relacionados (ver `tributos_por_nfe`).
+    pub nfes_com_tributos: usize,
+    /// Soma das parcelas das NF-es nos tributos dos CT-es (cada CT-e é contado uma vez).
+    pub tributos_ctes: TributosDoCte,
+    /// Chaves do arquivo com mais documentos relacionados (em ordem decrescente).
+    pub top_chaves: Vec<(Chave, usize)>,
+}
+
+impl Relatorio {
+    /// Número total de linhas alteradas.
+    pub fn alteracoes(&self) -> usize {
+        self.nfes_enriquecidas
+            + self.ctes_enriquecidos
+            + self.mdfes_enriquecidos
+            + self.linhas_restauradas
+    }
+
+    /// Contabiliza o resultado do enriquecimento de uma linha.
+    pub fn registrar(&mut self, modelo: Modelo, alteracao: Alteracao) {
+        if !alteracao.mudou() {
+            return;
+        }
+
+        if modelo.is_nota() {
+            self.nfes_enriquecidas += 1;
+        } else if modelo.is_conhecimento() {
+            self.ctes_enriquecidos += 1;
+        } else if modelo == Modelo::Mdfe {
+            self.mdfes_enriquecidos += 1;
+        }
+
+        if alteracao == Alteracao::Truncada {
+            self.linhas_truncadas += 1;
+        }
+    }
+
+    /// Analisa as relações dos documentos presentes no arquivo:
+    /// - conta relações sem resumo do documento relacionado;
+    /// - soma as parcelas das NF-es nos tributos dos CT-es (`tributos_por_nfe`);
+    /// - seleciona as `top` chaves com mais documentos relacionados.
+    pub fn analisar_relacoes(
+        &mut self,
+        info: &Informacoes,
+        cte_info: &HashMap<Chave, DocSummary>,
+        nfe_info: &HashMap<Chave, DocSummary>,
+        top: usize,
+    ) {
+        let mut contagem: Vec<(Chave, usize)> = Vec::new();
+
+        for chave in nfe_info.keys() {
+            if let Some(ctes) = info.nfe_ctes.get(chave) {
+                self.ctes_sem_resumo += ctes.iter().filter(|c| !cte_info.contains_key(c)).count();
+                contagem.push((*chave, ctes.len()));
+            }
+        }
+
+        let tributos = tributos_por_nfe(info, cte_info, nfe_info);
+        self.nfes_com_tributos = tributos.len();
+        self.tributos_ctes = TributosDoCte::default();
+        for parcela in tributos.into_values() {
+            self.tributos_ctes += parcela;
+        }
+
+        for chave in cte_info.keys() {
+            let relacionados = match chave.modelo() {
+                Modelo::Mdfe => info.mdfe_ctes.get(chave).map_or(0, |ctes| ctes.len()),
+                _ => {
+                    let nfes = info.cte_nfes.get(chave);
+                    if let Some(nfes) = nfes {
+                        self.nfes_sem_resumo +=
+                            nfes.iter().filter(|n| !nfe_info.contains_key(n)).count();
+                    }
+                    nfes.map_or(0, |nfes| nfes.len())
+                        + info.cte_mdfes.get(chave).map_or(0, |mdfes| mdfes.len())
+                }
+            };
+
+            if relacionados > 0 {
+                contagem.push((*chave, relacionados));
+            }
+        }
+
+        // 1º Número de relacionados (Desc), 2º Chave (Asc)
+        contagem.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        contagem.truncate(top);
+        self.top_chaves = contagem;
+    }
+
+    /// Imprime o relatório no terminal.
+    pub fn imprimir(&self) {
+        println!("--- Relatório de Enriquecimento ---");
+        println!(
+            " -> Linhas de NF-e enriquecidas: {}",
+            fmt_milhares(self.nfes_enriquecidas)
+        );
+        println!(
+            " -> Linhas de CT-e enriquecidas: {}",
+            fmt_milhares(self.ctes_enriquecidos)
+        );
+        if self.mdfes_enriquecidos > 0 {
+            println!(
+                " -> Linhas de MDF-e enriquecidas: {}",
+                fmt_milhares(self.mdfes_enriquecidos)
+            );
+        }
+        if self.ja_enriquecidas > 0 {
+            println!(
+                " -> Linhas já enriquecidas anteriormente (ignoradas): {}",
+                fmt_milhares(self.ja_enriquecidas)
+            );
+        }
+        println!(
+            " -> Linhas truncadas por --max-char: {}",
+            fmt_milhares(self.linhas_truncadas)
+        );
+        println!(
+            " -> Relações NF-e -> CT-e sem resumo do CT-e: {}",
+            fmt_milhares(self.ctes_sem_resumo)
+        );
+        println!(
+            " -> Relações CT-e -> NF-e sem resumo da NF-e: {}",
+            fmt_milhares(self.nfes_sem_resumo)
+        );
+        if self.nfes_com_tributos > 0 {
+            let t = &self.tributos_ctes;
+            println!(
+                " -> NF-es com tributos rateados dos CT-es: {}",
+                fmt_milhares(self.nfes_com_tributos)
+            );
+            println!(
+                " -> Tributos dos CT-es rateados: PIS {} | COFINS {} | BC ICMS {} | ICMS {}",
+                decimal_com_sinal_to_str(t.valor_pis),
+                decimal_com_sinal_to_str(t.valor_cofins),
+                decimal_com_sinal_to_str(t.valor_bc_icms),
+                decimal_com_sinal_to_str(t.valor_icms)
+            );
+        }
+
+        if !self.top_chaves.is_empty() {
+            println!(
+                "\n--- {} chaves com mais documentos relacionados ---",
+                self.top_chaves.len()
+            );
+            for (chave, num) in &self.top_chaves {
+                println!(
+                    " {} ({:>7}): {:>6}",
+                    chave,
+                    chave.modelo(),
+                    fmt_milhares(*num)
+                );
+            }
+        }
+        println!();
+    }
+}
@@ -0,0 +1,77 @@
+use super::*;
+use std::fs;
+
+/// Diretório temporário `nome` com o arquivo de documentos e os arquivos `relacoes`:
+/// retorna o caminho do arquivo de documentos.
+fn diretorio(nome: &str, relacoes: &[&str]) -> PathBuf {
+    let dir = std::env::temp_dir().join(nome);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for arquivo in relacoes {
+        fs::write(dir.join(arquivo), "").unwrap();
+    }
+    let doc_path = dir.join("docs.csv");
+    fs::write(&doc_path, "").unwrap();
+    doc_path
+}
+
+fn config(args: &[&str]) -> SpedResult<Config> {
+    let args = ["adicionar_info_de_ctes_em_nfes"].iter().chain(args);
+    configurar(Arguments::try_parse_from(args).unwrap())
+}
+
+#[test]
+fn test_arquivos_de_relacionamento_ao_lado_dos_documentos() {
+    let relacoes = [ARQUIVO_CTE_NFES, ARQUIVO_COMPLEMENTARES, ARQUIVO_MDFE_CTES];
+    let doc_path = diretorio("args_tests_descoberta", &relacoes);
+    let dir = doc_path.parent().unwrap();
+
+    for nome in relacoes {
+        assert_eq!(localizar_arquivo(&doc_path, nome), Some(dir.join(nome)));
+    }
+    assert_eq!(localizar_arquivo(&doc_path, "inexistente.txt"), None);
+
+    let config = config(&["-d", doc_path.to_str().unwrap()]).unwrap();
+    assert_eq!(config.cte_nfes_path, dir.join(ARQUIVO_CTE_NFES));
+    assert_eq!(
+        config.complementares_path,
+        Some(dir.join(ARQUIVO_COMPLEMENTARES))
+    );
+    assert_eq!(config.mdfe_ctes_path, Some(dir.join(ARQUIVO_MDFE_CTES)));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_arquivo_de_relacionamento_ausente() {
+    // Sem cte_nfes.txt: erro com o nome do arquivo procurado
+    let doc_path = diretorio("args_tests_ausente", &[]);
+    let doc = doc_path.to_str().unwrap();
+    match config(&["-d", doc]) {
+        Err(SpedError::RelationFileNotFound { opcao, arquivo, .. }) => {
+            assert_eq!(opcao, "cte-nfes");
+            assert_eq!(arquivo, PathBuf::from(ARQUIVO_CTE_NFES));
+        }
+        outro => panic!("esperado RelationFileNotFound: {outro:?}"),
+    }
+
+    // Os arquivos de complementares e de MDF-es são opcionais
+    let dir = doc_path.parent().unwrap();
+    fs::write(dir.join(ARQUIVO_CTE_NFES), "").unwrap();
+    let config_sem_opcionais = config(&["-d", doc]).unwrap();
+    assert_eq!(config_sem_opcionais.complementares_path, None);
+    assert_eq!(config_sem_opcionais.mdfe_ctes_path, None);
+
+    // Caminho explícito inexistente: erro com o próprio caminho e a opção informada
+    let inexistente = dir.join("complementares.txt");
+    let args = ["-d", doc, "--complementares", inexistente.to_str().unwrap()];
+    match config(&args) {
+        Err(SpedError::RelationFileNotFound { opcao, arquivo, .. }) => {
+            assert_eq!(opcao, "complementares");
+            assert_eq!(arquivo, inexistente);
+        }
+        outro => panic!("esperado RelationFileNotFound: {outro:?}"),
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,131 @@
+use super::*;
+use crate::comum::mock_chave;
+use std::io::Cursor;
+
+#[test]
+fn test_cache_ida_e_volta() {
+    let nfe = mock_chave("1111111111111111111155");
+    let cte = mock_chave("2222222222222222222257");
+    let comp = mock_chave("3333333333333333333357");
+    let mdfe = mock_chave("4444444444444444444458");
+
+    let mut info = Informacoes::default();
+    info.cte_nfes.entry(cte).or_default().insert(nfe);
+    info.cte_complementar.entry(cte).or_default().insert(comp);
+    info.mdfe_ctes.entry(mdfe).or_default().insert(cte);
+
+    let fontes = vec![
+        Some(Fonte {
+            tamanho: 10,
+            mtime_ns: 123_456_789,
+            hash: 42,
+        }),
+        None,
+        None,
+    ];
+
+    let mut buf = Vec::new();
+    escrever(&mut buf, &fontes, &info).unwrap();
+
+    let tamanho = buf.len() as u64;
+    let (fontes_lidas, lido) = ler(Cursor::new(buf), tamanho).unwrap();
+
+    assert_eq!(fontes_lidas, fontes);
+    assert_eq!(lido.cte_nfes, info.cte_nfes);
+    assert_eq!(lido.cte_complementar, info.cte_complementar);
+    assert_eq!(lido.mdfe_ctes, info.mdfe_ctes);
+
+    // Índices invertidos recalculados na leitura
+    assert!(lido.nfe_ctes[&nfe].contains(&cte));
+    assert!(lido.cte_mdfes[&cte].contains(&mdfe));
+}
+
+#[test]
+fn test_cache_corrompido() {
+    let mut buf = Vec::new();
+    escrever(&mut buf, &[None], &Informacoes::default()).unwrap();
+
+    // Formato desconhecido
+    let mut invalido = buf.clone();
+    invalido[0] = b'X';
+    assert!(ler(invalido.as_slice(), invalido.len() as u64).is_err());
+
+    // Arquivo truncado
+    let truncado = &buf[..buf.len() - 1];
+    assert!(ler(truncado, truncado.len() as u64).is_err());
+}
+
+#[test]
+fn test_cache_com_quantidades_corrompidas() {
+    let mut buf = Vec::new();
+    escrever(&mut buf, &[None], &Informacoes::default()).unwrap();
+
+    // Cabeçalho: magic (4) + versão (4) + número de fontes (4) + fonte ausente (1)
+    let inicio_do_mapa = 13;
+    assert_eq!(buf[inicio_do_mapa..inicio_do_mapa + 8], 0u64.to_le_bytes());
+
+    // Mapa com u64::MAX entradas: erro, sem tentar alocar
+    let mut mapa_enorme = buf.clone();
+    mapa_enorme[inicio_do_mapa..inicio_do_mapa + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+    let erro = ler(mapa_enorme.as_slice(), mapa_enorme.len() as u64).unwrap_err();
+    assert_eq!(erro.kind(), io::ErrorKind::InvalidData);
+
+    // Número de fontes maior que o arquivo
+    let mut fontes = buf.clone();
+    fontes[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+    let erro = ler(fontes.as_slice(), fontes.len() as u64).unwrap_err();
+    assert_eq!(erro.kind(), io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_fonte_corresponde() {
+    let path = std::env::temp_dir().join("cache_tests_fonte.txt");
+    std::fs::write(&path, "conteúdo").unwrap();
+
+    let fonte = Fonte::from_path(&path).unwrap();
+    assert!(fonte.corresponde(&path).unwrap());
+
+    // Mesmo tamanho e mtime diferente: decide pelo hash
+    let outro_mtime = Fonte {
+        mtime_ns: fonte.mtime_ns + 1,
+        ..fonte
+    };
+    assert!(outro_mtime.corresponde(&path).unwrap());
+
+    let outro_hash = Fonte {
+        mtime_ns: fonte.mtime_ns + 1,
+        hash: fonte.hash + 1,
+        ..fonte
+    };
+    assert!(!outro_hash.corresponde(&path).unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_dry_run_nao_grava_o_cache() {
+    let cte_nfes_path = std::env::temp_dir().join("cache_tests_dry_run.txt");
+    std::fs::write(&cte_nfes_path, "").unwrap();
+
+    let config = Config {
+        cte_nfes_path: cte_nfes_path.clone(),
+        dry_run: true,
+        ..Default::default()
+    };
+    let cache_path = caminho_do_cache(&config);
+    let _ = std::fs::remove_file(&cache_path);
+
+    carregar_informacoes(&config).unwrap();
+    assert!(!cache_path.exists());
+
+    // Fora do modo simulação, o cache é gravado
+    carregar_informacoes(&Config {
+        dry_run: false,
+        ..config
+    })
+    .unwrap();
+    assert!(cache_path.is_file());
+
+    std::fs::remove_file(&cte_nfes_path).unwrap();
+    std::fs::remove_file(&cache_path).unwrap();
+}
@@ -0,0 +1,107 @@
+use super::*;
+
+// NF-e (modelo 55) e CT-e (modelo 57) com dígito verificador correto
+const NFE: &str = "35200714200166000187550010000000071000000077";
+const CTE: &str = "41231112345678000190570010000001231100001237";
+
+// Cria uma chave cujo DV (44º dígito) certamente não confere
+fn chave_invalida(i: usize) -> Chave {
+    let prefixo = format!("{:0>43}", i);
+    let dv = Chave::new(&format!("{prefixo}0")).unwrap().calcular_dv();
+    Chave::new(&format!("{prefixo}{}", (dv + 1) % 10)).unwrap()
+}
+
+#[test]
+fn test_campos_da_chave() {
+    let chave = Chave::new(NFE).unwrap();
+
+    assert_eq!(chave.cuf(), 35);
+    assert_eq!(chave.uf(), Some("SP"));
+    assert_eq!(chave.aamm(), "2007");
+    assert_eq!(chave.ano(), 20);
+    assert_eq!(chave.mes(), 7);
+    assert_eq!(chave.cnpj_cpf_emitente(), "14200166000187");
+    assert_eq!(chave.codigo_modelo(), 55);
+    assert_eq!(chave.serie(), 1);
+    assert_eq!(chave.numero(), 7);
+    assert_eq!(chave.tp_emis(), 1);
+    assert_eq!(chave.cnf(), 7);
+    assert_eq!(chave.dv(), 7);
+    assert!(chave.is_nfe());
+
+    assert_eq!(Chave::new(CTE).unwrap().uf(), Some("PR"));
+    assert_eq!(chave_invalida(1).uf(), None);
+}
+
+#[test]
+fn test_digito_verificador() {
+    assert!(Chave::new(NFE).unwrap().dv_valido());
+    assert!(Chave::new(CTE).unwrap().dv_valido());
+
+    // Altera apenas o último dígito
+    let invalida = format!("{}8", &NFE[..43]);
+    let chave = Chave::new(&invalida).unwrap();
+    assert_eq!(chave.calcular_dv(), 7);
+    assert!(!chave.dv_valido());
+}
+
+#[test]
+fn test_dv_resto_zero_ou_um() {
+    // Soma múltipla de 11 (resto 0): todos os dígitos iguais a zero
+    let zeros = Chave::new(&"0".repeat(44)).unwrap();
+    assert_eq!(zeros.calcular_dv(), 0);
+    assert!(zeros.dv_valido());
+
+    // Soma = 4 * 3 = 12 (resto 1): o DV também é zero, e não 11 - 1 = 10
+    let resto_um = Chave::new(&format!("{}40{}", "0".repeat(41), 0)).unwrap();
+    assert_eq!(resto_um.calcular_dv(), 0);
+    assert!(resto_um.dv_valido());
+
+    let dv_um = Chave::new(&format!("{}401", "0".repeat(41))).unwrap();
+    assert!(!dv_um.dv_valido());
+}
+
+#[test]
+fn test_registro_de_chaves_invalidas() {
+    let mut invalidas = ChavesInvalidas::default();
+
+    assert!(invalidas.verificar(Chave::new(NFE).unwrap()));
+    assert!(invalidas.is_empty());
+
+    for i in 0..15 {
+        assert!(!invalidas.verificar(chave_invalida(i)));
+    }
+
+    assert_eq!(invalidas.total, 15);
+    assert_eq!(invalidas.amostras.len(), 10);
+
+    let mut outro = ChavesInvalidas::default();
+    outro.verificar(chave_invalida(99));
+    invalidas.merge(outro);
+
+    assert_eq!(invalidas.total, 16);
+    assert_eq!(invalidas.amostras.len(), 10);
+}
+
+#[test]
+fn test_modelos() {
+    let modelo = |codigo: &str| {
+        let s = format!("{:0<20}{}{:0<22}", "35", codigo, "");
+        Chave::new(&s).unwrap().modelo()
+    };
+
+    assert_eq!(modelo("55"), Modelo::Nfe);
+    assert_eq!(modelo("57"), Modelo::Cte);
+    assert_eq!(modelo("58"), Modelo::Mdfe);
+    assert_eq!(modelo("65"), Modelo::Nfce);
+    assert_eq!(modelo("66"), Modelo::Nf3e);
+    assert_eq!(modelo("67"), Modelo::CteOs);
+    assert_eq!(modelo("99"), Modelo::Outro(99));
+
+    assert!(Modelo::Nfce.is_nota());
+    assert!(Modelo::CteOs.is_conhecimento());
+    assert!(Modelo::Mdfe.is_transporte() && !Modelo::Mdfe.is_conhecimento());
+    assert!(!Modelo::Nf3e.is_nota() && !Modelo::Nf3e.is_transporte());
+    assert_eq!(Modelo::from(67).codigo(), 67);
+    assert_eq!(Modelo::CteOs.to_string(), "CT-e OS");
+}
@@ -0,0 +1,104 @@
+use super::*;
+use crate::ArquivoMapeado;
+use std::fs;
+
+/// "SÃO PAULO;Ação" em Windows-1252.
+const WINDOWS_1252: &[u8] = b"S\xC3O PAULO;A\xE7\xE3o\n";
+
+fn codificar(codificacao: Codificacao, partes: &[&[u8]]) -> Vec<u8> {
+    let mut wtr = Codificador::new(Vec::new(), codificacao).unwrap();
+    for parte in partes {
+        wtr.write_all(parte).unwrap();
+    }
+    wtr.destino
+}
+
+#[test]
+fn test_detectar_codificacao() {
+    assert_eq!(
+        Codificacao::Auto.detectar(b"\xEF\xBB\xBFa;b"),
+        Codificacao::Utf8Bom
+    );
+    assert_eq!(
+        Codificacao::Auto.detectar("SÃO;b".as_bytes()),
+        Codificacao::Utf8
+    );
+    assert_eq!(
+        Codificacao::Auto.detectar(WINDOWS_1252),
+        Codificacao::Windows1252
+    );
+    assert_eq!(Codificacao::Auto.detectar(b""), Codificacao::Utf8);
+
+    // Codificação informada em --encoding não é alterada
+    assert_eq!(Codificacao::Utf8.detectar(WINDOWS_1252), Codificacao::Utf8);
+}
+
+#[test]
+fn test_decodificar_windows_1252() {
+    let utf8 = Codificacao::Windows1252.decodificar(WINDOWS_1252);
+    assert_eq!(utf8.as_ref(), "SÃO PAULO;Ação\n".as_bytes());
+
+    // UTF-8: sem cópia
+    assert!(matches!(
+        Codificacao::Utf8.decodificar(b"abc"),
+        Cow::Borrowed(_)
+    ));
+}
+
+#[test]
+fn test_codificador() {
+    let texto = "SÃO PAULO;Ação\n".as_bytes();
+
+    // Ida e volta em Windows-1252, inclusive com caracteres divididos entre escritas
+    assert_eq!(codificar(Codificacao::Windows1252, &[texto]), WINDOWS_1252);
+    assert_eq!(
+        codificar(
+            Codificacao::Windows1252,
+            &[&texto[..2], &texto[2..13], &texto[13..]]
+        ),
+        WINDOWS_1252
+    );
+
+    // Sem representação em Windows-1252
+    assert_eq!(
+        codificar(Codificacao::Windows1252, &["a✓b".as_bytes()]),
+        b"a?b"
+    );
+
+    // UTF-8: BOM apenas em Utf8Bom
+    assert_eq!(codificar(Codificacao::Utf8, &[texto]), texto);
+    assert_eq!(
+        codificar(Codificacao::Utf8Bom, &[texto]),
+        [BOM_UTF8, texto].concat()
+    );
+}
+
+#[test]
+fn test_arquivo_windows_1252_e_bom() {
+    let path = std::env::temp_dir().join("codificacao_tests.csv");
+
+    fs::write(
+        &path,
+        [b"Munic\xEDpio;UF\n".as_slice(), WINDOWS_1252].concat(),
+    )
+    .unwrap();
+    let arquivo = ArquivoMapeado::abrir(&path, Codificacao::Auto).unwrap();
+    assert_eq!(arquivo.codificacao(), Codificacao::Windows1252);
+    assert_eq!(arquivo.cabecalho().unwrap(), vec!["Município", "UF"]);
+
+    // O conteúdo convertido volta idêntico ao original
+    let original = fs::read(&path).unwrap();
+    assert_eq!(
+        codificar(Codificacao::Windows1252, &[arquivo.bytes()]),
+        original
+    );
+
+    // O BOM não faz parte do nome da primeira coluna
+    fs::write(&path, [BOM_UTF8, b"Chave;UF\n1;SP\n"].concat()).unwrap();
+    let arquivo = ArquivoMapeado::abrir(&path, Codificacao::Auto).unwrap();
+    assert_eq!(arquivo.codificacao(), Codificacao::Utf8Bom);
+    assert_eq!(arquivo.cabecalho().unwrap(), vec!["Chave", "UF"]);
+    assert_eq!(arquivo.cabecalho_bruto(), b"Chave;UF\n");
+
+    fs::remove_file(path).unwrap();
+}
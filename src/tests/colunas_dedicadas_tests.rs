@@ -0,0 +1,174 @@
+use super::*;
+use crate::comum::mock_chave;
+use crate::{CamposDistintos, Distribuicao, extrair_metadata};
+
+fn mock_config() -> Config {
+    Config {
+        max_char: 1000,
+        max_info: 10,
+        separador: " | ".to_string(),
+        ..Default::default()
+    }
+}
+
+/// Resumo de um documento com os metadados de `colunas`.
+fn resumo(colunas: &Colunas, valor: i64) -> DocSummary {
+    let modelo = colunas.chave.modelo();
+    DocSummary {
+        num_de_itens: 1,
+        item_valor_total: Decimal::from(valor),
+        item_valor_maximo: Decimal::from(valor),
+        metadata: Some(extrair_metadata(
+            colunas,
+            modelo,
+            &PerfilDeInjecao::default(),
+        )),
+        ..Default::default()
+    }
+}
+
+fn posicao(dedicadas: &ColunasDedicadas, nome: &str) -> usize {
+    dedicadas.nomes.iter().position(|n| n == nome).unwrap()
+}
+
+#[test]
+fn test_nomes_das_colunas_dedicadas() {
+    let dedicadas = ColunasDedicadas::new(&PerfilDeInjecao::default()).unwrap();
+
+    // 6 colunas de documentos + 16 (CT-e) + 10 (NF-e) + 2 distribuições
+    assert_eq!(dedicadas.nomes.len(), 34);
+    assert_eq!(dedicadas.nomes[0], "CT-es relacionados");
+    assert_eq!(dedicadas.nomes[1], "Valor total CT-es");
+    assert!(dedicadas.nomes.iter().any(|n| n == "UF início (CT-e)"));
+    assert!(dedicadas.nomes.iter().any(|n| n == "NCM (NF-e)"));
+    assert_eq!(dedicadas.nomes.last().unwrap(), "Distribuição (NF-e)");
+
+    // Arquivo que já possui as colunas dedicadas
+    let cabecalho = StringRecord::from(vec!["Chave", " ct-es RELACIONADOS "]);
+    let erro = verificar_colunas_acrescentadas(&dedicadas.nomes, &cabecalho, Path::new("a.csv"));
+    assert!(matches!(erro, Err(SpedError::DuplicateColumnName { .. })));
+}
+
+#[test]
+fn test_nomes_repetidos_no_perfil() {
+    let mut perfil = PerfilDeInjecao::default();
+    let mut repetida = perfil.cte_para_nfe.colunas[0].clone();
+    repetida.nome = "remetente cnpj/cpf 1".to_string();
+    perfil.cte_para_nfe.colunas.push(repetida);
+
+    let erro = ColunasDedicadas::new(&perfil).unwrap_err().to_string();
+    assert!(erro.contains("Coluna dedicada repetida"), "{erro}");
+}
+
+#[test]
+fn test_preencher_nfe_com_ctes() {
+    let config = mock_config();
+    let dedicadas = ColunasDedicadas::new(&config.perfil).unwrap();
+
+    let nfe = mock_chave("1111111111111111111155");
+    let cte1 = mock_chave("2222222222222222222257");
+    let cte2 = mock_chave("3333333333333333333357");
+
+    let mut info = Informacoes::default();
+    info.nfe_ctes.entry(nfe).or_default().extend([cte1, cte2]);
+
+    let cte = |chave, uf: &'static str, municipio: &'static str| Colunas {
+        chave,
+        inicio_estado: uf.into(),
+        inicio_municipio: municipio.into(),
+        ..Default::default()
+    };
+
+    let cte_info = HashMap::from([
+        (cte1, resumo(&cte(cte1, "SP", "SÃO PAULO"), 300)),
+        (cte2, resumo(&cte(cte2, "SP", "CAMPINAS"), 200)),
+    ]);
+
+    let row = Colunas {
+        chave: nfe,
+        inicio_estado: "MG".into(),
+        ..Default::default()
+    };
+    let (alteracao, valores) =
+        dedicadas.preencher(&row, &config, &info, &cte_info, &HashMap::new());
+
+    assert_eq!(alteracao, Alteracao::Enriquecida);
+    assert_eq!(valores.len(), dedicadas.nomes.len());
+    // Documentos ordenados pelo valor (desc)
+    assert_eq!(valores[0], format!("{cte1} | {cte2}"));
+    assert_eq!(valores[1], "500.00");
+    // Valores distintos, na ordem dos documentos
+    assert_eq!(valores[posicao(&dedicadas, "UF início (CT-e)")], "SP");
+    assert_eq!(
+        valores[posicao(&dedicadas, "Município início (CT-e)")],
+        "SÃO PAULO | CAMPINAS"
+    );
+    // Colunas das NF-es vazias; a linha original não é alterada
+    assert!(valores[posicao(&dedicadas, "NCM (NF-e)")].is_empty());
+    assert_eq!(row.inicio_estado, "MG");
+
+    // max_char: o valor que excede o limite é descartado
+    let config = Config {
+        max_char: 15,
+        ..config
+    };
+    let (alteracao, valores) =
+        dedicadas.preencher(&row, &config, &info, &cte_info, &HashMap::new());
+    assert_eq!(alteracao, Alteracao::Truncada);
+    assert_eq!(
+        valores[posicao(&dedicadas, "Município início (CT-e)")],
+        "SÃO PAULO"
+    );
+}
+
+#[test]
+fn test_preencher_cte_com_nfes() {
+    let config = mock_config();
+    let dedicadas = ColunasDedicadas::new(&config.perfil).unwrap();
+
+    let cte = mock_chave("2222222222222222222257");
+    let nfe1 = mock_chave("1111111111111111111155");
+    let nfe2 = mock_chave("3333333333333333333355");
+
+    let mut info = Informacoes::default();
+    info.cte_nfes.entry(cte).or_default().extend([nfe1, nfe2]);
+
+    let nfe = |chave, ncm: &'static str| Colunas {
+        chave,
+        ncm: ncm.into(),
+        ..Default::default()
+    };
+
+    let mut distribuicao = Distribuicao::default();
+    for (ncm, valor) in [("84713012", 70), ("87654321", 30)] {
+        let campos = CamposDistintos {
+            ncm: ncm.to_string(),
+            ..Default::default()
+        };
+        distribuicao.registrar(&campos, Decimal::from(valor), 5);
+    }
+
+    let mut resumo1 = resumo(&nfe(nfe1, "84713012"), 100);
+    resumo1.distribuicao = Some(Box::new(distribuicao));
+
+    let nfe_info = HashMap::from([(nfe1, resumo1), (nfe2, resumo(&nfe(nfe2, "00000000"), 50))]);
+
+    let row = Colunas {
+        chave: cte,
+        ..Default::default()
+    };
+    let (alteracao, valores) =
+        dedicadas.preencher(&row, &config, &info, &HashMap::new(), &nfe_info);
+
+    assert_eq!(alteracao, Alteracao::Enriquecida);
+    assert_eq!(valores[2], format!("{nfe1} | {nfe2}"));
+    assert_eq!(valores[3], "150.00");
+    // Modo "sobrescrever": o NCM sem valor significativo não é informado
+    assert_eq!(valores[posicao(&dedicadas, "NCM (NF-e)")], "84713012");
+    assert_eq!(
+        valores[posicao(&dedicadas, "Distribuição (NF-e)")],
+        "NCM 84713012 (70%), 87654321 (30%)"
+    );
+    // Sem MDF-es relacionados
+    assert!(valores[4].is_empty());
+}
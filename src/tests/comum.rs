@@ -0,0 +1,22 @@
+//! Funções auxiliares compartilhadas pelos módulos de teste.
+
+use crate::Chave;
+use rust_decimal::Decimal;
+use std::str::FromStr;
+
+/// Chave de teste: `prefixo` completado com zeros até 44 dígitos (DV não verificado).
+pub fn mock_chave(prefixo: &str) -> Chave {
+    Chave::new(&format!("{:0<44}", prefixo)).expect("Falha ao criar chave de teste")
+}
+
+/// Chave com DV válido: UF 35, AAMM 2401, CNPJ, modelo, série e número `i`.
+pub fn chave_valida(modelo: u8, i: usize) -> Chave {
+    let prefixo = format!("35240111111111000191{modelo:02}001{i:09}1{i:08}");
+    let dv = Chave::new(&format!("{prefixo}0")).unwrap().calcular_dv();
+    Chave::new(&format!("{prefixo}{dv}")).unwrap()
+}
+
+/// Decimal exato a partir do texto (ex: "10.05").
+pub fn dec(valor: &str) -> Decimal {
+    Decimal::from_str(valor).unwrap()
+}
@@ -0,0 +1,185 @@
+use super::*;
+
+const EMITENTE: &str = "11111111000191";
+const DESTINATARIO: &str = "22222222000191";
+
+/// Chave com UF, ano/mês, emitente e modelo informados.
+fn chave(cuf: &str, aamm: &str, emitente: &str, modelo: &str) -> Chave {
+    Chave::new(&format!(
+        "{:0<44}",
+        format!("{cuf}{aamm}{emitente}{modelo}")
+    ))
+    .unwrap()
+}
+
+fn nfe() -> Chave {
+    chave("35", "2401", EMITENTE, "55")
+}
+
+fn cte() -> Chave {
+    chave("35", "2401", "33333333000191", "57")
+}
+
+/// Linha de CT-e consistente com a NF-e de `nfe()` (saída de SP para PR).
+fn linha_de_cte() -> Colunas<'static> {
+    Colunas {
+        chave: cte(),
+        dia_emissao: "12/01/2024".into(),
+        remetente_cnpj1: "11.111.111/0001-91".into(),
+        destinatario_cnpj: "22.222.222/0001-91".into(),
+        inicio_estado: "SP".into(),
+        termino_estado: "PR".into(),
+        ..Default::default()
+    }
+}
+
+fn linha_de_nfe() -> Colunas<'static> {
+    Colunas {
+        chave: nfe(),
+        dia_emissao: "10/01/2024".into(),
+        entrada_ou_saida: "Saída".into(),
+        participante_cnpj: "22.222.222/0001-91".into(),
+        ..Default::default()
+    }
+}
+
+fn inconsistencias(cte: &Colunas, nfe: Option<&Colunas>) -> Vec<Inconsistencia> {
+    let dados_nfe = nfe.map(DadosDeConsistencia::ler);
+    verificar(
+        &cte.chave,
+        &DadosDeConsistencia::ler(cte),
+        &nfe.map_or(self::nfe(), |n| n.chave),
+        dados_nfe.as_ref(),
+    )
+    .into_iter()
+    .map(|achado| achado.inconsistencia)
+    .collect()
+}
+
+#[test]
+fn test_interpretar_data() {
+    let data = DataDeEmissao {
+        ano: 2024,
+        mes: 1,
+        dia: 12,
+    };
+    assert_eq!(DataDeEmissao::interpretar("12/01/2024"), Some(data));
+    assert_eq!(
+        DataDeEmissao::interpretar(" 2024-01-12 10:30:00"),
+        Some(data)
+    );
+    assert_eq!(DataDeEmissao::interpretar("12/13/2024"), None);
+    assert_eq!(DataDeEmissao::interpretar("12/01"), None);
+    assert_eq!(DataDeEmissao::da_chave(&nfe()).to_string(), "01/01/2024");
+}
+
+#[test]
+fn test_ler_dados() {
+    let cte = DadosDeConsistencia::ler(&linha_de_cte());
+    assert_eq!(cte.remetentes, BTreeSet::from([EMITENTE.to_string()]));
+    assert_eq!(cte.inicio, BTreeSet::from(["SP".to_string()]));
+
+    // NF-e de entrada: o participante é o emitente e o contribuinte, o destinatário
+    let entrada = Colunas {
+        entrada_ou_saida: "Entrada".into(),
+        contribuinte_cnpj: "123.456.789-09".into(),
+        ..linha_de_nfe()
+    };
+    let dados = DadosDeConsistencia::ler(&entrada);
+    assert_eq!(dados.remetentes, BTreeSet::from([DESTINATARIO.to_string()]));
+    assert_eq!(
+        dados.destinatarios,
+        BTreeSet::from(["00012345678909".to_string()])
+    );
+
+    // Anotações de execuções anteriores são desconsideradas
+    let anotada = Colunas {
+        inicio_estado: "SP [Info do CT-e: RJ]".into(),
+        ..linha_de_cte()
+    };
+    assert_eq!(DadosDeConsistencia::ler(&anotada).inicio, cte.inicio);
+}
+
+#[test]
+fn test_documentos_consistentes() {
+    assert!(inconsistencias(&linha_de_cte(), Some(&linha_de_nfe())).is_empty());
+    // NF-e ausente dos arquivos: emitente, UF e mês obtidos da chave
+    assert!(inconsistencias(&linha_de_cte(), None).is_empty());
+}
+
+#[test]
+fn test_inconsistencias() {
+    let cte = Colunas {
+        dia_emissao: "09/01/2024".into(),
+        remetente_cnpj1: "44.444.444/0001-91".into(),
+        destinatario_cnpj: "55.555.555/0001-91".into(),
+        inicio_estado: "RJ".into(),
+        ..linha_de_cte()
+    };
+    assert_eq!(
+        inconsistencias(&cte, Some(&linha_de_nfe())),
+        [
+            Inconsistencia::Remetente,
+            Inconsistencia::Destinatario,
+            Inconsistencia::DataDeEmissao,
+            Inconsistencia::Uf,
+        ]
+    );
+
+    // Sem a NF-e, a data é comparada ao mês da chave e o destinatário não é verificado
+    let anterior_ao_mes = Colunas {
+        dia_emissao: "31/12/2023".into(),
+        ..cte.clone()
+    };
+    let achados = verificar(
+        &anterior_ao_mes.chave,
+        &DadosDeConsistencia::ler(&anterior_ao_mes),
+        &nfe(),
+        None,
+    );
+    assert_eq!(achados.len(), 3);
+    assert_eq!(achados[1].inconsistencia, Inconsistencia::DataDeEmissao);
+    assert_eq!(achados[1].valor_nfe, "01/2024 (chave)");
+    assert_eq!(inconsistencias(&cte, None).len(), 2);
+}
+
+#[test]
+fn test_diagnostico() {
+    let nfe_ok = nfe();
+    let nfe_rj = chave("33", "2401", EMITENTE, "55");
+    let cte = cte();
+
+    let mut info = Informacoes::default();
+    info.cte_nfes
+        .entry(cte)
+        .or_default()
+        .extend([nfe_ok, nfe_rj]);
+    info.get_nfe_ctes();
+
+    let resumo = |row: &Colunas| DocSummary {
+        consistencia: Some(Box::new(DadosDeConsistencia::ler(row))),
+        ..Default::default()
+    };
+    let cte_info = HashMap::from([(cte, resumo(&linha_de_cte()))]);
+    let nfe_info = HashMap::from([(nfe_ok, resumo(&linha_de_nfe()))]);
+
+    let diagnostico = Diagnostico::new(&info, &cte_info, &nfe_info);
+    assert_eq!(diagnostico.pares, 2);
+    assert_eq!(diagnostico.achados.len(), 1);
+    assert_eq!(diagnostico.achados[0].nfe, nfe_rj);
+    assert_eq!(diagnostico.achados[0].valor_cte, "SP -> PR");
+
+    assert_eq!(diagnostico.coluna(&cte, " | "), "UF");
+    assert_eq!(diagnostico.coluna(&nfe_rj, " | "), "UF");
+    assert_eq!(diagnostico.coluna(&nfe_ok, " | "), "");
+
+    let path = std::env::temp_dir().join("consistencia_tests.consistencia.csv");
+    diagnostico.gravar(&path).unwrap();
+    let conteudo = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let linhas: Vec<&str> = conteudo.lines().collect();
+    assert_eq!(linhas.len(), 2);
+    assert!(linhas[0].starts_with("Chave do CT-e;Chave da NF-e;Inconsistência"));
+    assert!(linhas[1].ends_with(";SP -> PR;RJ"));
+}
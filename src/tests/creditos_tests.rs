@@ -0,0 +1,170 @@
+use super::*;
+
+const CONTRIBUINTE: &str = "11.111.111/0001-91";
+
+/// Linha de CT-e de entrada com crédito: CST 50, natureza 03 e o contribuinte
+/// (remetente) como tomador.
+fn linha_de_cte() -> Colunas<'static> {
+    Colunas {
+        contribuinte_cnpj: CONTRIBUINTE.into(),
+        entrada_ou_saida: "Entrada".into(),
+        natureza_bc: "03 - Aquisição de Serviços Utilizados como Insumo".into(),
+        cst_descricao_pis: "50 - Operação com Direito a Crédito".into(),
+        cst_descricao_cofins: "50 - Operação com Direito a Crédito".into(),
+        tomador_papel1: "Remetente".into(),
+        // Outro estabelecimento do mesmo contribuinte
+        remetente_cnpj1: "11.111.111/0002-72".into(),
+        valor_item: "150,00".into(),
+        ..Default::default()
+    }
+}
+
+fn dados(row: &Colunas) -> DadosDoCte {
+    let mut cte = DadosDoCte::default();
+    cte.registrar(row, FormatoNumerico::Br);
+    cte
+}
+
+fn nfe(cst: &str, cfop: &str) -> DadosDaNfe {
+    DadosDaNfe {
+        cst: BTreeSet::from([cst.to_string()]),
+        cfop: BTreeSet::from([cfop.to_string()]),
+    }
+}
+
+#[test]
+fn test_codigo_inicial() {
+    assert_eq!(
+        codigo_inicial(" 50 - Operação com Direito a Crédito"),
+        Some(50)
+    );
+    assert_eq!(codigo_inicial("06"), Some(6));
+    assert_eq!(codigo_inicial("6152"), Some(6152));
+    assert_eq!(codigo_inicial("Outras"), None);
+    assert_eq!(codigo_inicial(""), None);
+}
+
+#[test]
+fn test_registrar_cte() {
+    let cte = dados(&linha_de_cte());
+
+    assert_eq!(cte.valor, Decimal::new(15000, 2));
+    assert_eq!(cte.contribuintes, BTreeSet::from(["11111111".to_string()]));
+    assert_eq!(cte.tomadores, cte.contribuintes);
+    assert_eq!(cte.papeis_do_tomador.len(), 1);
+}
+
+#[test]
+fn test_credito_permitido() {
+    let cte = dados(&linha_de_cte());
+    let tributada = nfe("01 - Operação Tributável", "5102");
+
+    assert_eq!(
+        classificar(&cte, &[&tributada]),
+        RegraDeCredito::CreditoPermitido
+    );
+    assert_eq!(
+        RegraDeCredito::CreditoPermitido.classe(),
+        ClasseDeCredito::Permitido
+    );
+}
+
+#[test]
+fn test_credito_nao_permitido() {
+    let tributada = nfe("01 - Operação Tributável", "5102");
+    let aliquota_zero = nfe("06 - Operação Tributável a Alíquota Zero", "5102");
+
+    let classificar_linha = |row: Colunas, nfes: &[&DadosDaNfe]| {
+        let regra = classificar(&dados(&row), nfes);
+        assert_eq!(regra.classe(), ClasseDeCredito::NaoPermitido, "{regra:?}");
+        regra
+    };
+
+    let saida = Colunas {
+        entrada_ou_saida: "Saída".into(),
+        ..linha_de_cte()
+    };
+    assert_eq!(
+        classificar_linha(saida, &[&tributada]),
+        RegraDeCredito::CteDeSaida
+    );
+
+    let sem_credito = Colunas {
+        cst_descricao_pis: "70 - Operação de Aquisição sem Direito a Crédito".into(),
+        ..linha_de_cte()
+    };
+    assert_eq!(
+        classificar_linha(sem_credito, &[&tributada]),
+        RegraDeCredito::CstSemDireitoACredito
+    );
+
+    let outro_tomador = Colunas {
+        tomador_papel1: "Outros".into(),
+        tomador_cnpj1: "22.222.222/0001-91".into(),
+        ..linha_de_cte()
+    };
+    assert_eq!(
+        classificar_linha(outro_tomador, &[&tributada]),
+        RegraDeCredito::TomadorDiverso
+    );
+
+    assert_eq!(
+        classificar_linha(linha_de_cte(), &[&aliquota_zero]),
+        RegraDeCredito::MercadoriasSemTributacao
+    );
+}
+
+#[test]
+fn test_credito_duvidoso() {
+    let tributada = nfe("01 - Operação Tributável", "5102");
+    let suspensao = nfe("09 - Operação com Suspensão da Contribuição", "5102");
+    let transferencia = nfe("01 - Operação Tributável", "6152");
+
+    let classificar_linha = |row: Colunas, nfes: &[&DadosDaNfe]| {
+        let regra = classificar(&dados(&row), nfes);
+        assert_eq!(regra.classe(), ClasseDeCredito::Duvidoso, "{regra:?}");
+        regra
+    };
+
+    assert_eq!(
+        classificar_linha(linha_de_cte(), &[&tributada, &suspensao]),
+        RegraDeCredito::MercadoriasParcialmenteSemTributacao
+    );
+    assert_eq!(
+        classificar_linha(linha_de_cte(), &[&transferencia]),
+        RegraDeCredito::TransferenciaEntreEstabelecimentos
+    );
+
+    let expedidor = Colunas {
+        tomador_papel1: "Expedidor".into(),
+        ..linha_de_cte()
+    };
+    assert_eq!(
+        classificar_linha(expedidor, &[&tributada]),
+        RegraDeCredito::TomadorNaoIdentificado
+    );
+
+    // NF-es sem CST e CFOP no arquivo
+    assert_eq!(
+        classificar_linha(linha_de_cte(), &[&DadosDaNfe::default()]),
+        RegraDeCredito::NfesSemDados
+    );
+
+    let outras_operacoes = Colunas {
+        cst_descricao_cofins: "99 - Outras Operações".into(),
+        ..linha_de_cte()
+    };
+    assert_eq!(
+        classificar_linha(outras_operacoes, &[&tributada]),
+        RegraDeCredito::CstIndefinido
+    );
+
+    let natureza = Colunas {
+        natureza_bc: "01 - Aquisição de Bens para Revenda".into(),
+        ..linha_de_cte()
+    };
+    assert_eq!(
+        classificar_linha(natureza, &[&tributada]),
+        RegraDeCredito::NaturezaIncompativel
+    );
+}
@@ -0,0 +1,58 @@
+use super::*;
+
+#[test]
+fn test_formatar_percentuais() {
+    let mut ncm = ValoresDistintos::default();
+    ncm.registrar("5678", Decimal::from(20), 5);
+    ncm.registrar(" 1234 ", Decimal::from(50), 5);
+    ncm.registrar("5678", Decimal::from(10), 5);
+    ncm.registrar("1234", Decimal::from(20), 5);
+    ncm.registrar("", Decimal::from(1000), 5); // Campo vazio não é contabilizado
+
+    assert_eq!(ncm.len(), 2);
+    assert_eq!(ncm.formatar("NCM"), "NCM 1234 (70%), 5678 (30%)");
+}
+
+#[test]
+fn test_limite_de_valores_distintos() {
+    let mut cfop = ValoresDistintos::default();
+    for (valor, peso) in [("5102", 50), ("6102", 30), ("5405", 15), ("6405", 5)] {
+        cfop.registrar(valor, Decimal::from(peso), 2);
+    }
+    // Valor já registrado continua a acumular após o limite
+    cfop.registrar("6102", Decimal::from(0), 2);
+
+    assert_eq!(cfop.valores.len(), 2);
+    assert_eq!(cfop.outros, Decimal::from(20));
+    assert_eq!(
+        cfop.formatar("CFOP"),
+        "CFOP 5102 (50%), 6102 (30%), outros (20%)"
+    );
+}
+
+#[test]
+fn test_registrar_e_campos_do_item() {
+    let item = |ncm: &str, cfop: &str| CamposDistintos {
+        ncm: ncm.to_string(),
+        cfop: cfop.to_string(),
+        ..Default::default()
+    };
+
+    let mut a = Distribuicao::default();
+    a.registrar(&item("1234", "5102"), Decimal::from(60), 5);
+    a.registrar(&item("5678", "5102"), Decimal::from(40), 5);
+
+    assert_eq!(a.ncm.formatar("NCM"), "NCM 1234 (60%), 5678 (40%)");
+    assert_eq!(a.cfop.len(), 1);
+    assert!(a.natureza.is_empty());
+
+    // NCM sem dígito significativo não é considerado
+    let row = Colunas {
+        ncm: "00000000".into(),
+        cfop: "5102".into(),
+        ..Default::default()
+    };
+    let campos = CamposDistintos::new(&row);
+    assert!(campos.ncm.is_empty());
+    assert_eq!(campos.cfop, "5102");
+}
@@ -0,0 +1,102 @@
+use super::*;
+use crate::comum::mock_chave;
+use rust_decimal::Decimal;
+
+#[test]
+fn test_exportar_jsonl() {
+    let nfe = mock_chave("1111111111111111111155");
+    let cte = mock_chave("2222222222222222222257");
+
+    let mut info = Informacoes::default();
+    info.cte_nfes.entry(cte).or_default().insert(nfe);
+    info.get_nfe_ctes();
+
+    let mut nfe_info = HashMap::new();
+    nfe_info.insert(
+        nfe,
+        DocSummary {
+            num_de_itens: 3,
+            item_valor_total: Decimal::new(3_000_000_000_000_000_001, 2),
+            item_valor_maximo: Decimal::from(15),
+            item_numero: 1,
+            item_posicao: 1,
+            metadata: None,
+            ..Default::default()
+        },
+    );
+
+    let path = std::env::temp_dir().join("exportacao_tests.relacoes.jsonl");
+    let registros = exportar_jsonl(&path, &info, &HashMap::new(), &nfe_info).unwrap();
+    let conteudo = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(registros, 2);
+
+    let linhas: Vec<serde_json::Value> = conteudo
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+
+    // Ordem crescente de chave: NF-e (111...) antes do CT-e (222...)
+    assert_eq!(linhas[0]["modelo"], "NF-e");
+    assert_eq!(linhas[0]["num_de_itens"], 3);
+    // Decimais como texto: sem arredondamento de ponto flutuante
+    assert_eq!(linhas[0]["item_valor_total"], "30000000000000000.01");
+    assert_eq!(linhas[0]["item_valor_maximo"], "15");
+    assert_eq!(linhas[0]["ctes"][0], format!("'{cte}'"));
+
+    // CT-e sem resumo: apenas as relações
+    assert_eq!(linhas[1]["modelo"], "CT-e");
+    assert_eq!(linhas[1]["nfes"][0], format!("'{nfe}'"));
+    assert!(linhas[1].get("num_de_itens").is_none());
+}
+
+#[test]
+fn test_exportar_tributos_dos_ctes() {
+    let nfe = mock_chave("1111111111111111111155");
+    let cte = mock_chave("2222222222222222222257");
+
+    let mut info = Informacoes::default();
+    info.cte_nfes.entry(cte).or_default().insert(nfe);
+    info.get_nfe_ctes();
+
+    let tributos = TributosDoCte {
+        valor_pis: Decimal::new(165, 2),
+        valor_icms: Decimal::from(12),
+        ..Default::default()
+    };
+    let cte_info = HashMap::from([(
+        cte,
+        DocSummary {
+            num_de_itens: 1,
+            tributos: Some(tributos),
+            ..Default::default()
+        },
+    )]);
+
+    let path = std::env::temp_dir().join("exportacao_tests.tributos.jsonl");
+    let nfe_info = HashMap::from([(
+        nfe,
+        DocSummary {
+            num_de_itens: 1,
+            item_valor_total: Decimal::ONE_HUNDRED,
+            ..Default::default()
+        },
+    )]);
+
+    exportar_jsonl(&path, &info, &cte_info, &nfe_info).unwrap();
+    let conteudo = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let linhas: Vec<serde_json::Value> = conteudo
+        .lines()
+        .map(|l| serde_json::from_str(l).unwrap())
+        .collect();
+
+    // NF-e: parcela dos tributos do CT-e (única NF-e transportada)
+    assert_eq!(linhas[0]["tributos_ctes_rateados"]["valor_pis"], "1.65");
+    assert_eq!(linhas[0]["tributos_ctes_rateados"]["valor_icms"], "12");
+    // CT-e: tributos do próprio resumo
+    assert_eq!(linhas[1]["tributos"]["valor_pis"], "1.65");
+    assert!(linhas[1].get("tributos_ctes_rateados").is_none());
+}
@@ -1,11 +1,7 @@
 use super::*;
-use std::collections::{HashMap, HashSet};
-
-// Helper para criar uma Chave válida rapidamente
-fn mock_chave(prefixo: &str) -> Chave {
-    let s = format!("{:0<44}", prefixo);
-    Chave::new(&s).expect("Falha ao criar chave de teste")
-}
+use crate::campo_de_texto;
+use crate::comum::mock_chave;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 // Config padrão simplificada com Default
 fn mock_config_padrao() -> Config {
@@ -59,11 +55,16 @@ fn teste_enriquecimento_nfe_com_cte() {
         chave_cte,
         DocSummary {
             num_de_itens: 1,
-            item_valor_total: 500.0,
-            item_valor_maximo: 500.0,
-            metadata: Some(DocMetadata::Cte(Box::new(
-                colunas_cte.extrair_cte_metadata(),
-            ))),
+            item_valor_total: Decimal::from(500),
+            item_valor_maximo: Decimal::from(500),
+            item_numero: 1,
+            item_posicao: 1,
+            metadata: Some(extrair_metadata(
+                &colunas_cte,
+                Modelo::Cte,
+                &PerfilDeInjecao::default(),
+            )),
+            ..Default::default()
         },
     );
 
@@ -108,11 +109,16 @@ fn teste_sobreposicao_ncm_no_cte() {
         chave_nfe,
         DocSummary {
             num_de_itens: 1,
-            item_valor_total: 1000.0,
-            item_valor_maximo: 1000.0,
-            metadata: Some(DocMetadata::Nfe(Box::new(
-                colunas_nfe.extrair_nfe_metadata(),
-            ))),
+            item_valor_total: Decimal::from(1000),
+            item_valor_maximo: Decimal::from(1000),
+            item_numero: 1,
+            item_posicao: 1,
+            metadata: Some(extrair_metadata(
+                &colunas_nfe,
+                Modelo::Nfe,
+                &PerfilDeInjecao::default(),
+            )),
+            ..Default::default()
         },
     );
 
@@ -126,3 +132,527 @@ fn teste_sobreposicao_ncm_no_cte() {
     assert_eq!(row_cte.ncm, "84713012");
     assert!(row_cte.contribuinte_nome.contains("FORNECEDOR LTDA"));
 }
+
+#[test]
+fn teste_mdfe_e_cte_relacionados() {
+    let config = mock_config_padrao();
+
+    let chave_mdfe = mock_chave("3333333333333333333358");
+    let chave_cte = mock_chave("2222222222222222222257");
+
+    // 1. Relações MDF-e -> CT-e e o índice invertido
+    let mut info = Informacoes::default();
+    info.mdfe_ctes
+        .entry(chave_mdfe)
+        .or_default()
+        .insert(chave_cte);
+    info.get_cte_mdfes();
+
+    // 2. Resumos de documentos de transporte (CT-e e MDF-e no mesmo mapa)
+    let colunas_cte = Colunas {
+        inicio_estado: "SP".into(),
+        ..mock_colunas(chave_cte)
+    };
+
+    let mut transporte_map = HashMap::new();
+    transporte_map.insert(
+        chave_cte,
+        DocSummary {
+            num_de_itens: 1,
+            item_valor_total: Decimal::from(250),
+            item_valor_maximo: Decimal::from(250),
+            item_numero: 1,
+            item_posicao: 1,
+            metadata: Some(extrair_metadata(
+                &colunas_cte,
+                Modelo::Cte,
+                &PerfilDeInjecao::default(),
+            )),
+            ..Default::default()
+        },
+    );
+    transporte_map.insert(
+        chave_mdfe,
+        DocSummary {
+            num_de_itens: 1,
+            item_valor_total: Decimal::from(80),
+            item_valor_maximo: Decimal::from(80),
+            item_numero: 1,
+            item_posicao: 1,
+            metadata: Some(extrair_metadata(
+                &mock_colunas(chave_mdfe),
+                Modelo::Cte,
+                &PerfilDeInjecao::default(),
+            )),
+            ..Default::default()
+        },
+    );
+
+    // 3. MDF-e recebe informações do CT-e
+    let mut row_mdfe = mock_colunas(chave_mdfe);
+    let alteracao = adicionar_info_de_ctes_em_mdfe(&mut row_mdfe, &config, &info, &transporte_map);
+    assert_eq!(alteracao, Alteracao::Enriquecida);
+    assert!(row_mdfe.chave_de_acesso.starts_with("MDFe: "));
+    assert!(row_mdfe.chave_de_acesso.contains("1 CTe: ["));
+    assert!(row_mdfe.inicio_estado.contains("[Info do CT-e: SP]"));
+
+    // 4. CT-e recebe a lista de MDF-es
+    let mut row_cte = mock_colunas(chave_cte);
+    let alteracao = adicionar_info_de_mdfes_em_cte(&mut row_cte, &config, &info, &transporte_map);
+    assert_eq!(alteracao, Alteracao::Enriquecida);
+    assert!(
+        row_cte
+            .chave_de_acesso
+            .contains("[Info do MDF-e: 1 MDFe: [")
+    );
+    assert!(row_cte.chave_de_acesso.contains("80.00"));
+}
+
+#[test]
+fn teste_cte_os_recebe_info_de_nfe() {
+    let config = mock_config_padrao();
+
+    let chave_cte_os = mock_chave("2222222222222222222267");
+    let chave_nfe = mock_chave("1111111111111111111155");
+
+    let mut info = Informacoes::default();
+    info.cte_nfes
+        .entry(chave_cte_os)
+        .or_default()
+        .insert(chave_nfe);
+
+    let colunas_nfe = Colunas {
+        descricao_mercadoria: "PRODUTO".into(),
+        ..mock_colunas(chave_nfe)
+    };
+
+    let mut nfe_resumo_map = HashMap::new();
+    nfe_resumo_map.insert(
+        chave_nfe,
+        DocSummary {
+            num_de_itens: 1,
+            item_valor_total: Decimal::from(10),
+            item_valor_maximo: Decimal::from(10),
+            item_numero: 1,
+            item_posicao: 1,
+            metadata: Some(extrair_metadata(
+                &colunas_nfe,
+                Modelo::Nfe,
+                &PerfilDeInjecao::default(),
+            )),
+            ..Default::default()
+        },
+    );
+
+    let mut row = mock_colunas(chave_cte_os);
+    assert!(row.chave.modelo().is_conhecimento());
+    let alteracao = adicionar_info_de_nfes_em_cte(&mut row, &config, &info, &nfe_resumo_map);
+    assert!(alteracao.mudou());
+    assert!(row.descricao_mercadoria.contains("[Info da NF-e: PRODUTO]"));
+}
+
+#[test]
+fn teste_truncamento_por_max_char() {
+    let config = Config {
+        max_char: 30,
+        ..mock_config_padrao()
+    };
+
+    let chave_nfe = mock_chave("1111111111111111111155");
+    let chave_cte = mock_chave("2222222222222222222257");
+
+    let mut info = Informacoes::default();
+    info.nfe_ctes
+        .entry(chave_nfe)
+        .or_default()
+        .insert(chave_cte);
+
+    let colunas_cte = Colunas {
+        inicio_municipio: "MUNICÍPIO COM UM NOME MUITO LONGO".into(),
+        ..mock_colunas(chave_cte)
+    };
+
+    let mut cte_resumo_map = HashMap::new();
+    cte_resumo_map.insert(
+        chave_cte,
+        DocSummary {
+            num_de_itens: 1,
+            item_valor_total: Decimal::from(1),
+            item_valor_maximo: Decimal::from(1),
+            item_numero: 1,
+            item_posicao: 1,
+            metadata: Some(extrair_metadata(
+                &colunas_cte,
+                Modelo::Cte,
+                &PerfilDeInjecao::default(),
+            )),
+            ..Default::default()
+        },
+    );
+
+    let mut row_nfe = mock_colunas(chave_nfe);
+    let alteracao = adicionar_info_de_ctes_em_nfe(&mut row_nfe, &config, &info, &cte_resumo_map);
+
+    assert_eq!(alteracao, Alteracao::Truncada);
+    assert!(alteracao.mudou());
+    assert!(row_nfe.inicio_municipio.is_empty());
+}
+
+#[test]
+fn teste_limpar_anotacoes_restaura_colunas() {
+    let config = mock_config_padrao();
+
+    let chave_nfe = mock_chave("1111111111111111111155");
+    let chave_cte = mock_chave("2222222222222222222257");
+
+    let mut info = Informacoes::default();
+    info.nfe_ctes
+        .entry(chave_nfe)
+        .or_default()
+        .insert(chave_cte);
+
+    let colunas_cte = Colunas {
+        inicio_estado: "SP".into(),
+        descricao_cfop: "Prestação de serviço de transporte".into(),
+        ..mock_colunas(chave_cte)
+    };
+
+    let mut cte_resumo_map = HashMap::new();
+    cte_resumo_map.insert(
+        chave_cte,
+        DocSummary {
+            num_de_itens: 1,
+            item_valor_total: Decimal::from(42),
+            item_valor_maximo: Decimal::from(42),
+            item_numero: 1,
+            item_posicao: 1,
+            metadata: Some(extrair_metadata(
+                &colunas_cte,
+                Modelo::Cte,
+                &PerfilDeInjecao::default(),
+            )),
+            ..Default::default()
+        },
+    );
+
+    let original = Colunas {
+        descricao_cfop: "Venda de mercadoria".into(),
+        ..mock_colunas(chave_nfe)
+    };
+
+    // 1. Enriquecimento
+    let mut row = original.clone();
+    adicionar_info_de_ctes_em_nfe(&mut row, &config, &info, &cte_resumo_map);
+    assert!(row.ja_enriquecida());
+    assert!(!original.ja_enriquecida());
+
+    // 2. Limpeza (sem o registro dos valores originais): as anotações são removidas
+    assert!(row.limpar_anotacoes());
+    assert_eq!(row.descricao_cfop, original.descricao_cfop);
+    assert_eq!(row.inicio_estado, original.inicio_estado);
+    assert_eq!(row.chave_de_acesso, "");
+    assert!(!row.ja_enriquecida());
+
+    // 3. Limpar uma linha já limpa não altera nada
+    assert!(!row.limpar_anotacoes());
+}
+
+#[test]
+fn teste_limpar_anotacao_em_coluna_vazia_apos_trim() {
+    // Ao reler o CSV com Trim::All, a anotação de uma coluna vazia perde o espaço inicial
+    let mut row = Colunas {
+        inicio_estado: "[Info do CT-e: SP] [Info do CT-e: RJ]".into(),
+        ..mock_colunas(mock_chave("1111111111111111111155"))
+    };
+
+    assert!(row.limpar_anotacoes());
+    assert_eq!(row.inicio_estado, "");
+}
+
+#[test]
+fn teste_restaurar_valores_originais() {
+    let original = Colunas {
+        chave_de_acesso: "35240111111111000191550010000000011000000018".into(),
+        ncm: "99".into(),
+        observacoes: "Frete [FOB]".into(),
+        ..mock_colunas(mock_chave("2222222222222222222257"))
+    };
+
+    // Reescrita da "Chave de Acesso", NCM sobrescrito e anotação
+    let mut row = Colunas {
+        chave_de_acesso: format!(
+            "CTe: {}, 1 NFe: [{}] de valor total = 10.00",
+            "2".repeat(44),
+            "1".repeat(44)
+        )
+        .into(),
+        ncm: "84713012".into(),
+        observacoes: "Frete [FOB] [Info da NF-e: obs]".into(),
+        ..original.clone()
+    };
+    assert!(row.ja_enriquecida());
+
+    let originais = BTreeMap::from([
+        (
+            "Inf. NFe - Chave de acesso da NF-e : ConhecimentoInformacaoNFe".to_string(),
+            original.chave_de_acesso.to_string(),
+        ),
+        ("Código NCM : NF Item (Todos)".to_string(), "99".to_string()),
+        ("Coluna desconhecida".to_string(), "x".to_string()),
+    ]);
+    assert!(row.restaurar(&originais));
+    assert_eq!(row.chave_de_acesso, original.chave_de_acesso);
+    assert_eq!(row.ncm, original.ncm);
+    assert!(!row.restaurar(&originais));
+
+    // Sem os valores originais, apenas as anotações ao final da coluna são removidas
+    assert!(row.limpar_anotacoes());
+    assert_eq!(row.observacoes, original.observacoes);
+
+    let mut row = Colunas {
+        observacoes: "Ver [Info do CT-e: x] abaixo [Info do CT-e: SP] [Info do CT-e: RJ]".into(),
+        ..original.clone()
+    };
+    assert!(row.limpar_anotacoes());
+    assert_eq!(row.observacoes, "Ver [Info do CT-e: x] abaixo");
+    assert_eq!(row.chave_de_acesso, original.chave_de_acesso);
+}
+
+#[test]
+fn teste_resumos_de_referencia() {
+    let nfe_local = mock_chave("1111111111111111111155");
+    let nfe_externa = mock_chave("3333333333333333333355");
+    let cte = mock_chave("2222222222222222222257");
+
+    let resumo = |num_de_itens, valor: i64| DocSummary {
+        num_de_itens,
+        item_valor_total: Decimal::from(valor),
+        item_valor_maximo: Decimal::from(valor),
+        item_numero: 1,
+        item_posicao: 1,
+        metadata: None,
+        ..Default::default()
+    };
+
+    let mut cte_info = HashMap::new();
+    let mut nfe_info = HashMap::from([(nfe_local, resumo(1, 100))]);
+
+    // Referência com a mesma NF-e local (não deve duplicar) e uma NF-e externa
+    let referencia = SummaryPair {
+        nfes: HashMap::from([(nfe_local, resumo(1, 100)), (nfe_externa, resumo(2, 50))]),
+        ..Default::default()
+    };
+
+    let acrescentadas = referencia.acrescentar_ausentes(&mut cte_info, &mut nfe_info);
+
+    assert_eq!(acrescentadas, 1);
+    assert_eq!(nfe_info[&nfe_local].num_de_itens, 1);
+    assert_eq!(nfe_info[&nfe_externa].item_valor_total, Decimal::from(50));
+
+    // A NF-e externa passa a enriquecer o CT-e do arquivo principal
+    let mut info = Informacoes::default();
+    info.cte_nfes
+        .entry(cte)
+        .or_default()
+        .extend([nfe_local, nfe_externa]);
+
+    let config = mock_config_padrao();
+    let mut row = mock_colunas(cte);
+    adicionar_info_de_nfes_em_cte(&mut row, &config, &info, &nfe_info);
+
+    assert!(row.chave_de_acesso.contains("2 NFes"));
+    assert!(row.chave_de_acesso.contains("150"));
+}
+
+#[test]
+fn teste_decimal_to_str() {
+    let dec = |valor: &str| valor.parse::<Decimal>().unwrap();
+
+    assert_eq!(decimal_to_str(dec("1234.706")), "1234.71");
+    // Arredondamento comercial (meio para cima), sem erro de representação binária
+    assert_eq!(decimal_to_str(dec("1234.705")), "1234.71");
+    assert_eq!(decimal_to_str(dec("0.125")), "0.13");
+    assert_eq!(decimal_to_str(dec("-10")), "10.00");
+}
+
+/// NF-e com DV válido (os resumos descartam chaves com DV inválido).
+fn chave_nfe_valida() -> Chave {
+    let prefixo = "3524011111111100019155001000000001100000001";
+    let dv = Chave::new(&format!("{prefixo}0")).unwrap().calcular_dv();
+    Chave::new(&format!("{prefixo}{dv}")).unwrap()
+}
+
+const COLUNA_DESCRICAO: &str = "Descrição da Mercadoria/Serviço : NF Item (Todos)";
+
+/// Acumula os itens `(número, valor, CFOP, NCM)` de uma NF-e, na ordem informada,
+/// e retorna o resumo com a descrição do item representativo.
+fn resumir_itens(
+    estrategia: ItemRepresentativo,
+    itens: &[(u64, i64, &str, &str)],
+) -> (DocSummary, String) {
+    let chave = chave_nfe_valida();
+    let linhas: Vec<Colunas<'static>> = itens
+        .iter()
+        .map(|(numero, _, cfop, ncm)| Colunas {
+            numero_item: numero.to_string().into(),
+            cfop: cfop.to_string().into(),
+            ncm: ncm.to_string().into(),
+            descricao_mercadoria: format!("ITEM {numero}").into(),
+            ..mock_colunas(chave)
+        })
+        .collect();
+
+    let perfil = PerfilDeInjecao::default();
+    let mut pair = SummaryPair {
+        criterios: CriteriosDoResumo {
+            item_representativo: estrategia,
+            max_valores_distintos: 0,
+            itens_das_nfes: false,
+        },
+        ..Default::default()
+    };
+
+    for (i, (numero, valor, cfop, ncm)) in itens.iter().enumerate() {
+        let item = ItemDoResumo {
+            chave,
+            valor: Decimal::from(*valor),
+            numero_item: *numero,
+            posicao: i,
+            cfop_ncm: if estrategia.por_grupo() {
+                format!("{cfop}/{ncm}")
+            } else {
+                String::new()
+            },
+            campos: None,
+            tributos: None,
+            modalidade: None,
+            consistencia: None,
+        };
+        pair.acumular(item, |modelo| {
+            Ok(extrair_metadata(&linhas[i], modelo, &perfil))
+        })
+        .unwrap();
+    }
+
+    pair.escolher_representantes(|posicao, modelo| {
+        Ok(extrair_metadata(&linhas[posicao], modelo, &perfil))
+    })
+    .unwrap();
+
+    let resumo = pair.nfes.remove(&chave).unwrap();
+    let descricao = match &resumo.metadata {
+        Some(DocMetadata::Nfe(n)) => n
+            .valor(campo_de_texto(COLUNA_DESCRICAO).unwrap())
+            .to_string(),
+        _ => panic!("resumo sem metadados de NF-e"),
+    };
+    (resumo, descricao)
+}
+
+/// Grupos: 5102/A = 60 (itens 1 e 5), 5102/B = 100 (item 2), 5405/C = 130 (itens 3 e 4).
+const ITENS: [(u64, i64, &str, &str); 5] = [
+    (4, 70, "5405", "C"),
+    (2, 100, "5102", "B"),
+    (5, 50, "5102", "A"),
+    (1, 10, "5102", "A"),
+    (3, 60, "5405", "C"),
+];
+
+#[test]
+fn teste_item_representativo_maior_valor() {
+    let (resumo, descricao) = resumir_itens(ItemRepresentativo::MaiorValor, &ITENS);
+    assert_eq!(descricao, "ITEM 2");
+    assert_eq!(resumo.item_numero, 2);
+    assert_eq!(resumo.num_de_itens, 5);
+    assert_eq!(resumo.item_valor_total, Decimal::from(290));
+    assert_eq!(resumo.item_valor_maximo, Decimal::from(100));
+}
+
+#[test]
+fn teste_item_representativo_primeiro_item() {
+    let (resumo, descricao) = resumir_itens(ItemRepresentativo::PrimeiroItem, &ITENS);
+    assert_eq!(descricao, "ITEM 1");
+    // O valor máximo do documento não depende do item representativo
+    assert_eq!(resumo.item_valor_maximo, Decimal::from(100));
+    assert_eq!(resumo.item_valor, Decimal::from(10));
+}
+
+#[test]
+fn teste_item_representativo_cfop_ncm() {
+    let (resumo, descricao) = resumir_itens(ItemRepresentativo::CfopNcm, &ITENS);
+    // Grupo 5405/C (130) supera o item isolado de maior valor (100)
+    assert_eq!(descricao, "ITEM 3");
+    assert!(resumo.grupos.is_empty(), "grupos liberados após a escolha");
+    assert_eq!(resumo.item_valor_total, Decimal::from(290));
+
+    // Empate no valor somado: prevalece o grupo com mais itens
+    let empate = [
+        (1, 100, "5102", "B"),
+        (2, 40, "5405", "C"),
+        (3, 60, "5405", "C"),
+    ];
+    let (_, descricao) = resumir_itens(ItemRepresentativo::CfopNcm, &empate);
+    assert_eq!(descricao, "ITEM 2");
+}
+
+#[test]
+fn teste_item_representativo_cfop_ncm_maior_valor() {
+    let (_, descricao) = resumir_itens(ItemRepresentativo::CfopNcmMaiorValor, &ITENS);
+    assert_eq!(descricao, "ITEM 4");
+
+    // A ordem dos itens no arquivo não altera o resultado
+    let mut invertidos = ITENS;
+    invertidos.reverse();
+    for estrategia in ItemRepresentativo::value_variants() {
+        assert_eq!(
+            resumir_itens(*estrategia, &ITENS).1,
+            resumir_itens(*estrategia, &invertidos).1
+        );
+    }
+}
+
+#[test]
+fn teste_distribuicao_de_ncm_injetada_no_cte() {
+    let nfe = mock_chave("1111111111111111111155");
+    let cte = mock_chave("2222222222222222222257");
+
+    let mut distribuicao = Distribuicao::default();
+    for (ncm, valor) in [("12345678", 70), ("87654321", 30)] {
+        let campos = CamposDistintos {
+            ncm: ncm.to_string(),
+            cfop: "5102".to_string(),
+            ..Default::default()
+        };
+        distribuicao.registrar(&campos, Decimal::from(valor), 5);
+    }
+
+    let nfe_info = HashMap::from([(
+        nfe,
+        DocSummary {
+            num_de_itens: 2,
+            item_valor_total: Decimal::from(100),
+            item_valor_maximo: Decimal::from(70),
+            distribuicao: Some(Box::new(distribuicao)),
+            ..Default::default()
+        },
+    )]);
+
+    let mut info = Informacoes::default();
+    info.cte_nfes.entry(cte).or_default().insert(nfe);
+
+    let mut row = mock_colunas(cte);
+    let alteracao =
+        adicionar_info_de_nfes_em_cte(&mut row, &mock_config_padrao(), &info, &nfe_info);
+
+    assert!(alteracao.mudou());
+    assert_eq!(
+        row.descricao_ncm,
+        " [Info da NF-e: NCM 12345678 (70%), 87654321 (30%)]"
+    );
+    // CFOP único: já informado pelos metadados do item representativo
+    assert!(row.descricao_cfop.is_empty());
+
+    // As anotações são removidas por --limpar
+    assert!(row.limpar_anotacoes());
+    assert!(row.descricao_ncm.is_empty());
+}
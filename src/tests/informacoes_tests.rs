@@ -0,0 +1,33 @@
+use super::*;
+use crate::comum::chave_valida;
+use std::fs;
+
+#[test]
+fn test_transitividade_sem_arquivo_de_complementares() {
+    let dir = std::env::temp_dir();
+    let (path_cte_nfes, path_complementares) = (
+        dir.join("informacoes_tests_cte_nfes.txt"),
+        dir.join("informacoes_tests_complementares.txt"),
+    );
+    let (nfe, cte, complementar) = (
+        chave_valida(55, 0),
+        chave_valida(57, 1),
+        chave_valida(57, 3),
+    );
+    fs::write(&path_cte_nfes, format!("{cte} {nfe}\n")).unwrap();
+    fs::write(&path_complementares, format!("{cte} {complementar}\n")).unwrap();
+
+    // Com o arquivo: a NF-e do CT-e é propagada ao complementar
+    let info = Informacoes::from_files(&path_cte_nfes, Some(&path_complementares), None).unwrap();
+    assert!(info.cte_nfes[&complementar].contains(&nfe));
+    assert_eq!(info.nfe_ctes[&nfe].len(), 2);
+
+    // Sem o arquivo: apenas as relações diretas
+    let info = Informacoes::from_files(&path_cte_nfes, None, None).unwrap();
+    assert!(info.cte_complementar.is_empty());
+    assert!(!info.cte_nfes.contains_key(&complementar));
+    assert_eq!(info.nfe_ctes[&nfe], HashSet::from([cte]));
+
+    fs::remove_file(path_cte_nfes).unwrap();
+    fs::remove_file(path_complementares).unwrap();
+}
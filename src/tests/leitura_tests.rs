@@ -0,0 +1,87 @@
+use super::*;
+use std::fs;
+
+fn mapear(nome: &str, conteudo: &str) -> (PathBuf, ArquivoMapeado) {
+    let path = std::env::temp_dir().join(nome);
+    fs::write(&path, conteudo).unwrap();
+    let arquivo = ArquivoMapeado::abrir(&path, Codificacao::Auto).unwrap();
+    (path, arquivo)
+}
+
+#[test]
+fn test_fim_do_registro_com_aspas() {
+    // Aspas no meio do campo são literais; entre aspas, '\n' e ';' fazem parte do campo
+    let bytes = b"TUBO 1/2\";\"A;B\nC\";\"x\"\"\ny\"\nfim\n";
+    let fim = fim_do_registro(bytes, 0);
+    assert_eq!(&bytes[fim..], b"fim\n");
+    assert_eq!(fim_do_registro(bytes, fim), bytes.len());
+
+    // Última linha sem terminador
+    assert_eq!(fim_do_registro(b"a;b", 0), 3);
+}
+
+#[test]
+fn test_fatias_alinhadas_aos_registros() {
+    let conteudo = "c1;c2\n1;\"a\nb\"\n2;x\n3;\"y\"\"\n\"\n4;z\n5;w";
+    let (path, arquivo) = mapear("leitura_tests_fatias.csv", conteudo);
+
+    assert_eq!(arquivo.cabecalho().unwrap(), vec!["c1", "c2"]);
+    assert_eq!(arquivo.cabecalho_bruto(), b"c1;c2\n");
+
+    for linhas_por_fatia in 1..=6 {
+        let fatias = arquivo.fatias(linhas_por_fatia);
+        assert_eq!(fatias.len(), 5_usize.div_ceil(linhas_por_fatia));
+
+        // As fatias são contíguas e cada uma contém registros completos
+        let mut registros = Vec::new();
+        let mut inicio = arquivo.inicio_dos_dados();
+        for faixa in fatias {
+            assert_eq!(faixa.start, inicio);
+            inicio = faixa.end;
+            for record in leitor(&arquivo.bytes()[faixa]).records() {
+                registros.push(record.unwrap()[0].to_string());
+            }
+        }
+        assert_eq!(inicio, conteudo.len());
+        assert_eq!(registros, vec!["1", "2", "3", "4", "5"]);
+    }
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_terminador_e_arquivo_vazio() {
+    let (path, arquivo) = mapear("leitura_tests_crlf.csv", "c1;c2\r\n1;2\r\n");
+    assert!(matches!(arquivo.terminador(), csv::Terminator::CRLF));
+    assert_eq!(arquivo.fatias(10), vec![7..12]);
+    fs::remove_file(path).unwrap();
+
+    let (path, arquivo) = mapear("leitura_tests_vazio.csv", "");
+    assert!(arquivo.bytes().is_empty());
+    assert!(arquivo.cabecalho().unwrap().is_empty());
+    assert!(arquivo.fatias(10).is_empty());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_erro_detalhado_com_numero_da_linha() {
+    let (path, arquivo) = mapear("leitura_tests_erro.csv", "c1;c2\n1;2\n3\n");
+    let mut record = ByteRecord::new();
+    ler_registro(&arquivo.bytes()[10..], &mut record).unwrap();
+
+    let erro = arquivo.erro_detalhado(10, &record, "campos");
+    assert!(matches!(
+        erro,
+        SpedError::CsvDetailed { linha_numero: 3, ref conteudo, .. } if conteudo == "3"
+    ));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_registros_com_quebra_de_linha_entre_aspas() {
+    let bytes = b"1;\"A\nB\"\r\n2;C\n\n3;D";
+    let faixas: Vec<_> = registros(bytes).collect();
+    assert_eq!(faixas, vec![0..9, 9..13, 13..14, 14..17]);
+    assert!(registros(b"").next().is_none());
+}
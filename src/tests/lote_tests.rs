@@ -0,0 +1,59 @@
+use super::*;
+
+fn diretorio_de_teste(nome: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(nome);
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    for arquivo in ["b.csv", "a.csv", "a.modificado.csv", "notas.txt"] {
+        fs::write(dir.join(arquivo), "").unwrap();
+    }
+    dir
+}
+
+#[test]
+fn test_expandir_diretorio() {
+    let dir = diretorio_de_teste("lote_tests_diretorio");
+
+    let arquivos = expandir_entradas(std::slice::from_ref(&dir)).unwrap();
+    assert_eq!(arquivos, vec![dir.join("a.csv"), dir.join("b.csv")]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_expandir_glob_e_repetidos() {
+    let dir = diretorio_de_teste("lote_tests_glob");
+
+    let entradas = vec![dir.join("b.csv"), dir.join("*.csv")];
+    let arquivos = expandir_entradas(&entradas).unwrap();
+
+    // Ordem de entrada preservada; "b.csv" não se repete
+    assert_eq!(arquivos, vec![dir.join("b.csv"), dir.join("a.csv")]);
+
+    // Padrão sem correspondência
+    assert!(expandir_entradas(&[dir.join("*.xlsx")]).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_processar_lote_preserva_ordem_e_falhas() {
+    let arquivos: Vec<PathBuf> = (0..5).map(|i| PathBuf::from(format!("{i}.csv"))).collect();
+
+    let resultados = processar_lote(&arquivos, 2, |path| {
+        let i: usize = path.file_stem().unwrap().to_string_lossy().parse().unwrap();
+        if i == 3 {
+            Err(SpedError::Config("falha simulada".to_string()))
+        } else {
+            Ok((path.with_extension("modificado.csv"), i * 10, i))
+        }
+    });
+
+    let nomes: Vec<_> = resultados.iter().map(|r| r.arquivo.clone()).collect();
+    assert_eq!(nomes, arquivos);
+    assert_eq!(resultados[2].linhas, 20);
+    assert_eq!(resultados[2].saida, Some(PathBuf::from("2.modificado.csv")));
+    assert!(resultados[3].falhou());
+    assert_eq!(resultados[3].saida, None);
+    assert_eq!(resultados.iter().filter(|r| r.falhou()).count(), 1);
+}
@@ -0,0 +1,134 @@
+use super::*;
+use crate::Chave;
+use rust_decimal::Decimal;
+
+const CHAVE: &str = "35240111111111000191550010000000011000000018";
+
+fn config_com_aliases(aliases: &[(&str, &str)]) -> Config {
+    let mut config = Config::default();
+    for (padrao, alias) in aliases {
+        config
+            .aliases_de_colunas
+            .entry(padrao.to_string())
+            .or_default()
+            .push(alias.to_string());
+    }
+    config
+}
+
+#[test]
+fn test_cabecalhos_padrao() {
+    assert_eq!(CABECALHOS.len(), 56);
+    assert!(CABECALHOS.iter().any(|c| c == COLUNA_CHAVE));
+    assert!(CABECALHOS.iter().any(|c| c == COLUNA_VALOR_ITEM));
+}
+
+#[test]
+fn test_normalizar_coluna() {
+    assert_eq!(
+        normalizar_coluna("\u{feff}Número da Nota : NF Item (Todos)"),
+        normalizar_coluna("número da nota :  NF Item")
+    );
+}
+
+#[test]
+fn test_cabecalho_padrao_e_identidade() {
+    let headers = StringRecord::from(CABECALHOS.clone());
+    let mapa = MapaDeColunas::new(&headers, &Config::default(), Path::new("x.csv")).unwrap();
+
+    assert!(mapa.is_identidade());
+    assert!(mapa.ausentes.is_empty());
+    assert!(mapa.extras.is_empty());
+}
+
+#[test]
+fn test_colunas_reordenadas_com_alias_e_extras() {
+    // Arquivo reduzido: ordem diferente, sufixo alterado, alias e coluna extra
+    let headers = StringRecord::from(vec![
+        "Coluna Extra",
+        "Valor do Item",
+        "Chave da Nota Fiscal Eletrônica : NF Item",
+        "Código NCM : NF Item (Todos)",
+    ]);
+    let config = config_com_aliases(&[(COLUNA_VALOR_ITEM, "Valor do Item")]);
+    let mapa = MapaDeColunas::new(&headers, &config, Path::new("x.csv")).unwrap();
+
+    assert!(!mapa.is_identidade());
+    assert_eq!(mapa.extras, vec!["Coluna Extra"]);
+    assert_eq!(mapa.ausentes.len(), 56 - 3);
+
+    let record = StringRecord::from(vec!["abc", "1.234,56", CHAVE, "84713012"]);
+    let mut buf = StringRecord::new();
+    let mut row = mapa.deserializar(&record, &mut buf).unwrap();
+
+    assert_eq!(row.chave, Chave::new(CHAVE).unwrap());
+    assert_eq!(row.get_valor_do_item(), Some(Decimal::new(123456, 2)));
+    assert_eq!(row.ncm, "84713012");
+    assert_eq!(row.descricao_ncm, "");
+
+    // A gravação preserva a ordem original e a coluna extra
+    row.ncm = "99999999".into();
+    let saida = mapa.registro_de_saida(&record, &row);
+
+    assert_eq!(saida.len(), 4);
+    assert_eq!(&saida[0], "abc");
+    assert_eq!(&saida[1], "1.234,56");
+    assert_eq!(&saida[2], CHAVE);
+    assert_eq!(&saida[3], "99999999");
+}
+
+#[test]
+fn test_erros_de_cabecalho() {
+    let config = Config::default();
+    let path = Path::new("x.csv");
+
+    let vazia = StringRecord::from(vec![COLUNA_CHAVE, COLUNA_VALOR_ITEM, ""]);
+    assert!(matches!(
+        MapaDeColunas::new(&vazia, &config, path),
+        Err(SpedError::EmptyColumnName { .. })
+    ));
+
+    let repetida = StringRecord::from(vec![COLUNA_CHAVE, COLUNA_VALOR_ITEM, COLUNA_CHAVE]);
+    assert!(matches!(
+        MapaDeColunas::new(&repetida, &config, path),
+        Err(SpedError::DuplicateColumnName { .. })
+    ));
+
+    // Dois nomes diferentes para o mesmo campo
+    let mesmo_campo = StringRecord::from(vec![
+        COLUNA_CHAVE,
+        COLUNA_VALOR_ITEM,
+        "Valor da Nota Proporcional : NF Item SOMA",
+    ]);
+    assert!(matches!(
+        MapaDeColunas::new(&mesmo_campo, &config, path),
+        Err(SpedError::DuplicateColumnName { .. })
+    ));
+
+    let sem_valor = StringRecord::from(vec![COLUNA_CHAVE]);
+    assert!(matches!(
+        MapaDeColunas::new(&sem_valor, &config, path),
+        Err(SpedError::MissingColumns { .. })
+    ));
+}
+
+#[test]
+fn test_ler_mapa_de_colunas() {
+    let path = std::env::temp_dir().join("mapeamento_tests.toml");
+
+    fs::write(
+        &path,
+        "[colunas]\n\
+         \"Código NCM : NF Item\" = \"NCM\"\n\
+         \"Valor da Nota Proporcional : NF Item (Todos) SOMA\" = [\"Valor\", \"Valor do Item\"]\n",
+    )
+    .unwrap();
+    let aliases = ler_mapa_de_colunas(&path).unwrap();
+    assert_eq!(aliases["Código NCM : NF Item (Todos)"], vec!["NCM"]);
+    assert_eq!(aliases[COLUNA_VALOR_ITEM].len(), 2);
+
+    fs::write(&path, "[colunas]\n\"Coluna Inexistente\" = \"X\"\n").unwrap();
+    assert!(ler_mapa_de_colunas(&path).is_err());
+
+    fs::remove_file(&path).unwrap();
+}
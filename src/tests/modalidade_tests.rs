@@ -0,0 +1,173 @@
+use super::*;
+use crate::comum::mock_chave;
+
+const REMETENTE: &str = "11.111.111/0001-91";
+const DESTINATARIO: &str = "22.222.222/0001-91";
+const TERCEIRO: &str = "33.333.333/0001-91";
+
+/// Linha de CT-e com remetente, destinatário e o papel do tomador.
+fn linha_de_cte(papel: &str) -> Colunas<'static> {
+    Colunas {
+        contribuinte_cnpj: DESTINATARIO.into(),
+        tomador_papel1: papel.to_string().into(),
+        remetente_cnpj1: REMETENTE.into(),
+        destinatario_cnpj: DESTINATARIO.into(),
+        ..Default::default()
+    }
+}
+
+fn modalidade(row: &Colunas) -> Option<Modalidade> {
+    ModalidadeDoFrete::da_linha(row).map(|m| m.modalidade)
+}
+
+#[test]
+fn test_interpretar_papel() {
+    assert_eq!(
+        PapelDoTomador::interpretar("3 - Destinatário"),
+        Some(PapelDoTomador::Destinatario)
+    );
+    assert_eq!(
+        PapelDoTomador::interpretar("REMETENTE"),
+        Some(PapelDoTomador::Remetente)
+    );
+    assert_eq!(
+        PapelDoTomador::interpretar("4 - Outros"),
+        Some(PapelDoTomador::Outros)
+    );
+    assert_eq!(PapelDoTomador::interpretar(""), None);
+}
+
+#[test]
+fn test_documentos() {
+    assert_eq!(cnpj_ou_cpf(REMETENTE).as_deref(), Some("11111111000191"));
+    assert_eq!(
+        cnpj_ou_cpf("123.456.789-09").as_deref(),
+        Some("12345678909")
+    );
+    assert_eq!(cnpj_ou_cpf("123"), None);
+    assert_eq!(raiz_do_documento(REMETENTE).as_deref(), Some("11111111"));
+    assert_eq!(
+        raiz_do_documento("123.456.789-09").as_deref(),
+        Some("12345678909")
+    );
+}
+
+#[test]
+fn test_modalidade_pelo_papel() {
+    assert_eq!(
+        modalidade(&linha_de_cte("Remetente")),
+        Some(Modalidade::Cif)
+    );
+    assert_eq!(
+        modalidade(&linha_de_cte("Destinatário")),
+        Some(Modalidade::Fob)
+    );
+    assert_eq!(
+        modalidade(&linha_de_cte("Expedidor")),
+        Some(Modalidade::Terceiros)
+    );
+
+    let outros = Colunas {
+        tomador_cnpj1: TERCEIRO.into(),
+        ..linha_de_cte("Outros")
+    };
+    assert_eq!(modalidade(&outros), Some(Modalidade::Terceiros));
+
+    // Papel não informado
+    assert_eq!(ModalidadeDoFrete::da_linha(&linha_de_cte("")), None);
+}
+
+#[test]
+fn test_modalidade_pelo_documento() {
+    // "Outros" com o documento do remetente ou do destinatário
+    let outros = Colunas {
+        tomador_cnpj1: REMETENTE.into(),
+        ..linha_de_cte("Outros")
+    };
+    assert_eq!(modalidade(&outros), Some(Modalidade::Cif));
+
+    let outros = Colunas {
+        tomador_cnpj1: "22222222000191".into(),
+        ..linha_de_cte("Outros")
+    };
+    assert_eq!(modalidade(&outros), Some(Modalidade::Fob));
+
+    // O primeiro tomador informado prevalece
+    let segundo = Colunas {
+        tomador_papel1: "".into(),
+        tomador_papel2: "Remetente".into(),
+        remetente_cnpj2: REMETENTE.into(),
+        ..linha_de_cte("")
+    };
+    assert_eq!(modalidade(&segundo), Some(Modalidade::Cif));
+}
+
+#[test]
+fn test_contribuinte_tomador() {
+    let fob = ModalidadeDoFrete::da_linha(&linha_de_cte("Destinatário")).unwrap();
+    assert!(fob.contribuinte_tomador);
+    assert_eq!(fob.to_string(), "FOB (contribuinte tomador)");
+
+    let cif = ModalidadeDoFrete::da_linha(&linha_de_cte("Remetente")).unwrap();
+    assert!(!cif.contribuinte_tomador);
+    assert_eq!(cif.to_string(), "CIF");
+
+    // Expedidor: documento do tomador desconhecido
+    let expedidor = ModalidadeDoFrete::da_linha(&linha_de_cte("Expedidor")).unwrap();
+    assert!(!expedidor.contribuinte_tomador);
+}
+
+#[test]
+fn test_coluna_modalidade() {
+    let nfe = mock_chave("1111111111111111111155");
+    let cte_cif = mock_chave("2222222222222222222257");
+    let cte_fob = mock_chave("3333333333333333333357");
+
+    let mut info = Informacoes::default();
+    info.cte_nfes.entry(cte_cif).or_default().insert(nfe);
+    info.cte_nfes.entry(cte_fob).or_default().insert(nfe);
+    info.get_nfe_ctes();
+
+    let resumo = |papel: &str| DocSummary {
+        modalidade: ModalidadeDoFrete::da_linha(&linha_de_cte(papel)),
+        ..Default::default()
+    };
+    let cte_info = HashMap::from([
+        (cte_cif, resumo("Remetente")),
+        (cte_fob, resumo("Destinatário")),
+    ]);
+    let config = Config {
+        separador: " | ".to_string(),
+        ..Default::default()
+    };
+
+    let coluna = |row: Colunas| coluna_modalidade(&row, &config, &info, &cte_info);
+
+    // NF-e: modalidades distintas dos CT-es, em ordem
+    let linha_nfe = Colunas {
+        chave: nfe,
+        ..Default::default()
+    };
+    assert_eq!(coluna(linha_nfe), "CIF | FOB (contribuinte tomador)");
+
+    // CT-e: a modalidade do resumo prevalece sobre a da linha
+    let linha_cte = Colunas {
+        chave: cte_cif,
+        ..linha_de_cte("Destinatário")
+    };
+    assert_eq!(coluna(linha_cte), "CIF");
+
+    // CT-e sem resumo: modalidade da própria linha
+    let sem_resumo = Colunas {
+        chave: mock_chave("4444444444444444444457"),
+        ..linha_de_cte("Expedidor")
+    };
+    assert_eq!(coluna(sem_resumo), "Terceiros");
+
+    let cancelada = Colunas {
+        chave: cte_cif,
+        cancelada: "Sim".into(),
+        ..Default::default()
+    };
+    assert_eq!(coluna(cancelada), "");
+}
@@ -0,0 +1,109 @@
+use super::*;
+use crate::comum::dec;
+
+fn valor(texto: &str, formato: FormatoNumerico) -> Numero {
+    interpretar_numero(texto, formato)
+}
+
+#[test]
+fn test_formato_brasileiro() {
+    let br = FormatoNumerico::Br;
+    assert_eq!(valor("1.234,56", br), Numero::Valor(dec("1234.56")));
+    assert_eq!(valor("1.234", br), Numero::Valor(dec("1234")));
+    assert_eq!(valor("-0,05", br), Numero::Valor(dec("-0.05")));
+    assert_eq!(valor("1.000.000", br), Numero::Valor(dec("1000000")));
+
+    // Grupos de milhar inválidos ou separadores trocados
+    assert_eq!(valor("1.23", br), Numero::Invalido);
+    assert_eq!(valor("1,234.56", br), Numero::Invalido);
+    assert_eq!(valor("1,2,3", br), Numero::Invalido);
+}
+
+#[test]
+fn test_formato_internacional() {
+    let int = FormatoNumerico::Internacional;
+    assert_eq!(valor("1,234.56", int), Numero::Valor(dec("1234.56")));
+    assert_eq!(valor("1.234", int), Numero::Valor(dec("1.234")));
+    assert_eq!(valor("1,234", int), Numero::Valor(dec("1234")));
+    assert_eq!(valor("1.234,56", int), Numero::Invalido);
+}
+
+#[test]
+fn test_formato_automatico() {
+    let auto = FormatoNumerico::Auto;
+
+    // Dois separadores: o último é o decimal
+    assert_eq!(valor("1.234,56", auto), Numero::Valor(dec("1234.56")));
+    assert_eq!(valor("1,234.56", auto), Numero::Valor(dec("1234.56")));
+
+    // Um separador sem ambiguidade
+    assert_eq!(valor("1234,5", auto), Numero::Valor(dec("1234.5")));
+    assert_eq!(valor("1234.56", auto), Numero::Valor(dec("1234.56")));
+    assert_eq!(valor("0.500", auto), Numero::Valor(dec("0.5")));
+    assert_eq!(valor("1.234.567", auto), Numero::Valor(dec("1234567")));
+    assert_eq!(valor("1,234,567", auto), Numero::Valor(dec("1234567")));
+
+    // Ruídos, notação científica, vazio e inválidos
+    assert_eq!(valor(" R$ 1.234,56 ", auto), Numero::Valor(dec("1234.56")));
+    assert_eq!(valor("-3.6e2", auto), Numero::Valor(dec("-360")));
+    assert_eq!(valor("  ", auto), Numero::Vazio);
+    assert_eq!(valor("abc", auto), Numero::Invalido);
+    assert_eq!(valor("1-2", auto), Numero::Invalido);
+}
+
+#[test]
+fn test_valores_ambiguos() {
+    let auto = FormatoNumerico::Auto;
+
+    // Prevalece a leitura brasileira
+    assert_eq!(
+        valor("1.234", auto),
+        Numero::Ambiguo {
+            valor: dec("1234"),
+            alternativa: dec("1.234")
+        }
+    );
+    assert_eq!(
+        valor("-12,500", auto),
+        Numero::Ambiguo {
+            valor: dec("-12.5"),
+            alternativa: dec("-12500")
+        }
+    );
+    assert_eq!(valor("1.234", auto).valor(), Some(dec("1234")));
+
+    // Com formato explícito não há ambiguidade
+    assert_eq!(
+        valor("1.234", FormatoNumerico::Br),
+        Numero::Valor(dec("1234"))
+    );
+}
+
+#[test]
+fn test_qualidade_numerica() {
+    let coluna = "Valor";
+    let mut parte_1 = QualidadeNumerica::default();
+    let mut parte_2 = QualidadeNumerica::default();
+
+    for texto in ["1,00", "", "1.234", "x"] {
+        parte_1.registrar(coluna, texto, &valor(texto, FormatoNumerico::Auto));
+    }
+    for i in 0..10 {
+        let texto = format!("inválido {}", "-".repeat(i + 2));
+        parte_2.registrar(coluna, &texto, &valor(&texto, FormatoNumerico::Auto));
+    }
+
+    assert!(parte_1.tem_problemas());
+    parte_1.merge(parte_2);
+
+    let e = &parte_1.colunas[coluna];
+    assert_eq!(
+        (e.validos, e.vazios, e.ambiguos, e.invalidos),
+        (1, 1, 1, 11)
+    );
+
+    // Exemplos limitados, na ordem do arquivo
+    assert_eq!(e.exemplos_invalidos.len(), MAX_EXEMPLOS);
+    assert_eq!(e.exemplos_invalidos[0], "x");
+    assert_eq!(e.exemplos_ambiguos[0].0, "1.234");
+}
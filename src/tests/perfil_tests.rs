@@ -0,0 +1,218 @@
+use super::*;
+use crate::{Colunas, Config};
+use std::borrow::Cow;
+
+fn campo(nome: &str) -> usize {
+    campo_de_texto(nome).expect("coluna conhecida")
+}
+
+const NCM: &str = "Código NCM : NF Item (Todos)";
+const UF_INICIO: &str = "CTe - UF do início da prestação de Conhecimento : ConhecimentoValoresPrestacaoServico-Componentes";
+const OBSERVACOES: &str = "Observações : NF (Todos)";
+
+#[test]
+fn test_perfil_padrao() {
+    let perfil = PerfilDeInjecao::default();
+
+    assert_eq!(perfil.cte_para_nfe.rotulo, "CT-e");
+    assert_eq!(perfil.cte_para_nfe.colunas.len(), 16);
+    assert_eq!(perfil.nfe_para_cte.rotulo, "NF-e");
+    assert_eq!(perfil.nfe_para_cte.colunas.len(), 10);
+    assert_eq!(perfil.cte_para_nfe.limite(10), 10);
+
+    // Colunas históricas: origem = destino; apenas o NCM da NF-e sobrescreve
+    for coluna in perfil
+        .cte_para_nfe
+        .colunas
+        .iter()
+        .chain(&perfil.nfe_para_cte.colunas)
+    {
+        assert_eq!(coluna.origem, coluna.destino);
+        assert_eq!(coluna.max_info, None);
+        let esperado = if coluna.origem == campo(NCM) {
+            ModoDeInjecao::Sobrescrever
+        } else {
+            ModoDeInjecao::Anotar
+        };
+        assert_eq!(coluna.modo, esperado, "{}", nome_do_campo(coluna.origem));
+    }
+}
+
+#[test]
+fn test_campos_de_texto_na_ordem_das_colunas() {
+    // Cada campo de texto recebe a sua posição; a serialização revela a coluna
+    let mut colunas = Colunas::default();
+    for (i, campo) in colunas.campos_de_texto_mut().into_iter().enumerate() {
+        *campo = Cow::Owned(i.to_string());
+    }
+
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.serialize(&colunas).unwrap();
+    let csv = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let registro = reader.records().next().unwrap().unwrap();
+
+    let mut campos_de_texto = 0;
+    for (nome, valor) in CABECALHOS.iter().zip(registro.iter()) {
+        if nome == COLUNA_CHAVE {
+            continue;
+        }
+        let posicao: usize = valor.parse().unwrap();
+        assert_eq!(nome_do_campo(posicao), nome);
+        assert_eq!(campo_de_texto(nome), Some(posicao));
+        campos_de_texto += 1;
+    }
+
+    assert_eq!(campos_de_texto, crate::NUM_CAMPOS_DE_TEXTO);
+    assert_eq!(campo_de_texto(COLUNA_CHAVE), None);
+}
+
+#[test]
+fn test_perfil_personalizado() {
+    let toml = format!(
+        r#"
+        [cte_para_nfe]
+        max_info = 3
+        colunas = [
+            {{ origem = "  cte - uf do INÍCIO da prestação de conhecimento : conhecimentovaloresprestacaoservico-componentes " }},
+            {{ nome = "UF", origem = "{UF_INICIO}", destino = "{OBSERVACOES}", max_info = 1, modo = "sobrescrever" }},
+        ]
+        "#
+    );
+
+    let perfil = interpretar_perfil(&toml, None).unwrap();
+    let direcao = &perfil.cte_para_nfe;
+
+    assert_eq!(direcao.limite(10), 3);
+    assert_eq!(
+        direcao.colunas,
+        [
+            ColunaInjetada {
+                nome: UF_INICIO.to_string(),
+                origem: campo(UF_INICIO),
+                destino: campo(UF_INICIO),
+                max_info: None,
+                modo: ModoDeInjecao::Anotar,
+            },
+            ColunaInjetada {
+                nome: "UF".to_string(),
+                origem: campo(UF_INICIO),
+                destino: campo(OBSERVACOES),
+                max_info: Some(1),
+                modo: ModoDeInjecao::Sobrescrever,
+            },
+        ]
+    );
+
+    // Direção omitida: mantém o perfil padrão
+    assert_eq!(perfil.nfe_para_cte, PerfilDeInjecao::default().nfe_para_cte);
+}
+
+#[test]
+fn test_injetar_metadados_do_perfil() {
+    let toml = format!(
+        r#"
+        [cte_para_nfe]
+        colunas = [
+            {{ origem = "{UF_INICIO}" }},
+            {{ origem = "{UF_INICIO}", destino = "{OBSERVACOES}", max_info = 1, modo = "sobrescrever" }},
+        ]
+        "#
+    );
+    let direcao = interpretar_perfil(&toml, None).unwrap().cte_para_nfe;
+    let config = Config {
+        max_char: 1000,
+        ..Default::default()
+    };
+
+    let documento = |uf: &'static str| {
+        let colunas = Colunas {
+            inicio_estado: uf.into(),
+            ..Default::default()
+        };
+        colunas.extrair_metadados(&direcao)
+    };
+
+    let mut row = Colunas {
+        observacoes: "original".into(),
+        ..Default::default()
+    };
+
+    for (ordem, uf) in ["SP", "RJ"].into_iter().enumerate() {
+        assert!(row.injetar_metadados(&config, &direcao, &documento(uf), ordem));
+    }
+
+    assert_eq!(row.inicio_estado, " [Info do CT-e: SP] [Info do CT-e: RJ]");
+    // max_info = 1: apenas o primeiro documento relacionado sobrescreve o destino
+    assert_eq!(row.observacoes, "SP");
+}
+
+#[test]
+fn test_erros_do_perfil() {
+    let erro = |toml: &str| interpretar_perfil(toml, None).unwrap_err().to_string();
+
+    let msg = erro(r#"cte_para_nfe = { colunas = [{ origem = "Coluna Inexistente" }] }"#);
+    assert!(msg.contains("coluna desconhecida"), "{msg}");
+
+    let msg = erro(&format!(
+        r#"cte_para_nfe = {{ colunas = [{{ origem = "{COLUNA_CHAVE}" }}] }}"#
+    ));
+    assert!(msg.contains("coluna desconhecida"), "{msg}");
+
+    // Colunas lidas pelo processamento não podem ser destino, em nenhuma direção
+    for coluna in COLUNAS_LIDAS {
+        assert!(campo_de_texto(coluna).is_some(), "{coluna}");
+        for direcao in ["cte_para_nfe", "nfe_para_cte"] {
+            let msg = erro(&format!(
+                r#"{direcao} = {{ colunas = [{{ origem = "{NCM}", destino = "{coluna}" }}] }}"#
+            ));
+            assert!(msg.contains("não pode ser destino"), "{msg}");
+            assert!(msg.contains(coluna), "{msg}");
+        }
+    }
+
+    // Colunas lidas nas linhas de CT-e: rejeitadas apenas como destino nos CT-es
+    for coluna in COLUNAS_LIDAS_NOS_CTES {
+        assert!(campo_de_texto(coluna).is_some(), "{coluna}");
+        let msg = erro(&format!(
+            r#"nfe_para_cte = {{ colunas = [{{ origem = "{NCM}", destino = "{coluna}" }}] }}"#
+        ));
+        assert!(msg.contains("não pode ser destino"), "{msg}");
+
+        let toml = format!(r#"cte_para_nfe = {{ colunas = [{{ origem = "{coluna}" }}] }}"#);
+        assert!(interpretar_perfil(&toml, None).is_ok(), "{coluna}");
+    }
+
+    // Campos desconhecidos e modos inválidos são rejeitados
+    assert!(interpretar_perfil("[cte_para_nfe]\ncolunas = []\nlimite = 2", None).is_err());
+    assert!(
+        interpretar_perfil(
+            &format!(r#"cte_para_nfe = {{ colunas = [{{ origem = "{NCM}", modo = "x" }}] }}"#),
+            None
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_valor_significativo() {
+    assert!(valor_significativo("84713012"));
+    assert!(valor_significativo("SP"));
+    assert!(!valor_significativo("00000000"));
+    assert!(!valor_significativo(" 0.0 "));
+    assert!(!valor_significativo(""));
+}
+
+#[test]
+fn test_serializar_metadados() {
+    let mut metadados = Metadados::default();
+    metadados.inserir(campo(UF_INICIO), " SP ");
+    metadados.inserir(campo(NCM), "");
+    metadados.inserir(campo(UF_INICIO), "RJ"); // Apenas o primeiro valor do campo
+
+    assert_eq!(metadados.valor(campo(UF_INICIO)), "SP");
+    assert_eq!(metadados.valor(campo(OBSERVACOES)), "");
+
+    let json = serde_json::to_string(&metadados).unwrap();
+    assert_eq!(json, format!(r#"{{"{UF_INICIO}":"SP","{NCM}":""}}"#));
+}
@@ -0,0 +1,109 @@
+use super::*;
+use crate::comum::{dec, mock_chave};
+
+fn resumo(valor: Decimal, itens: Vec<(usize, Decimal)>) -> DocSummary {
+    DocSummary {
+        num_de_itens: itens.len().max(1),
+        item_valor_total: valor,
+        itens,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_ratear_soma_exata() {
+    let parcelas = ratear(dec("100"), &[dec("1"), dec("1"), dec("1")]);
+    assert_eq!(parcelas, [dec("33.34"), dec("33.33"), dec("33.33")]);
+
+    // Os centavos restantes vão para as maiores frações descartadas
+    let parcelas = ratear(dec("10"), &[dec("1"), dec("2"), dec("4")]);
+    assert_eq!(parcelas, [dec("1.43"), dec("2.86"), dec("5.71")]);
+    assert_eq!(parcelas.iter().sum::<Decimal>(), dec("10"));
+
+    // O total é arredondado a 2 casas
+    let parcelas = ratear(dec("0.015"), &[dec("3"), dec("1")]);
+    assert_eq!(parcelas.iter().sum::<Decimal>(), dec("0.02"));
+}
+
+#[test]
+fn test_ratear_total_negativo() {
+    let parcelas = ratear(dec("-10"), &[dec("1"), dec("1"), dec("1")]);
+    assert_eq!(parcelas, [dec("-3.34"), dec("-3.33"), dec("-3.33")]);
+    assert_eq!(parcelas.iter().sum::<Decimal>(), dec("-10"));
+
+    let parcelas = ratear(dec("-10"), &[dec("1"), dec("2"), dec("4")]);
+    assert_eq!(parcelas, [dec("-1.43"), dec("-2.86"), dec("-5.71")]);
+}
+
+#[test]
+fn test_ratear_sinais_mistos() {
+    let pesos = [dec("2"), dec("2"), dec("2"), dec("-3")];
+
+    let parcelas = ratear(dec("1"), &pesos);
+    assert_eq!(parcelas, [dec("0.67"), dec("0.67"), dec("0.66"), dec("-1")]);
+    assert_eq!(parcelas.iter().sum::<Decimal>(), dec("1"));
+
+    let parcelas = ratear(dec("-1"), &pesos);
+    assert_eq!(
+        parcelas,
+        [dec("-0.67"), dec("-0.67"), dec("-0.66"), dec("1")]
+    );
+    assert_eq!(parcelas.iter().sum::<Decimal>(), dec("-1"));
+
+    // Pesos de sinais opostos com frações descartadas em ambos os sentidos
+    let pesos = [dec("1"), dec("1"), dec("-1"), dec("2")];
+    for total in ["10", "-10", "0.05", "-0.05"] {
+        let parcelas = ratear(dec(total), &pesos);
+        assert_eq!(
+            parcelas.iter().sum::<Decimal>(),
+            dec(total),
+            "total {total}"
+        );
+    }
+}
+
+#[test]
+fn test_ratear_pesos_nulos_ou_vazios() {
+    assert_eq!(
+        ratear(dec("1"), &[Decimal::ZERO, Decimal::ZERO]),
+        [dec("0.50"), dec("0.50")]
+    );
+    assert!(ratear(dec("1"), &[]).is_empty());
+}
+
+#[test]
+fn test_rateio_de_frete() {
+    let nfe_a = mock_chave("1111111111111111111155");
+    let nfe_b = mock_chave("2222222222222222222255");
+    let cte1 = mock_chave("3333333333333333333357");
+    let cte2 = mock_chave("4444444444444444444457");
+
+    // CT-e 1 transporta A e B; CT-e 2 transporta apenas B
+    let mut info = Informacoes::default();
+    info.cte_nfes
+        .entry(cte1)
+        .or_default()
+        .extend([nfe_a, nfe_b]);
+    info.cte_nfes.entry(cte2).or_default().insert(nfe_b);
+
+    let cte_info = HashMap::from([
+        (cte1, resumo(dec("90"), vec![])),
+        (cte2, resumo(dec("10.01"), vec![])),
+    ]);
+    let nfe_info = HashMap::from([
+        (
+            nfe_a,
+            resumo(dec("200"), vec![(0, dec("150")), (100, dec("50"))]),
+        ),
+        // NF-e de arquivo de referência: sem itens
+        (nfe_b, resumo(dec("100"), vec![])),
+    ]);
+
+    let rateio = RateioDeFrete::new(&info, &cte_info, &nfe_info);
+
+    // A recebe 2/3 do CT-e 1 (60.00), rateados entre os seus itens
+    assert_eq!(rateio.frete(0), Some(dec("45.00")));
+    assert_eq!(rateio.frete(100), Some(dec("15.00")));
+    assert_eq!(rateio.frete(50), None);
+    assert_eq!(rateio.nfes, 1);
+}
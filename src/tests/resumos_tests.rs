@@ -0,0 +1,436 @@
+use super::*;
+use crate::{
+    CABECALHOS, ModoDeSaida, Relatorio, caminho_dos_originais, comum::chave_valida,
+    enriquecer_arquivo, limpar_arquivo,
+};
+use std::{collections::BTreeMap, fs};
+
+fn indice(coluna: &str) -> usize {
+    CABECALHOS
+        .iter()
+        .position(|c| c.starts_with(coluna))
+        .unwrap()
+}
+
+/// Gera um CSV com NF-es e CT-es de muitos itens, com valores empatados
+/// e itens fora de ordem, ocupando mais de um bloco de leitura.
+fn gerar_csv(path: &Path, num_docs: usize, itens_por_doc: usize) {
+    let (i_chave, i_item, i_valor, i_descricao, i_natureza) = (
+        indice("Chave da Nota Fiscal"),
+        indice("Número do Item"),
+        indice("Valor da Nota Proporcional"),
+        indice("Descrição da Mercadoria"),
+        indice("Descrição da Natureza"),
+    );
+
+    let mut linhas = vec![CABECALHOS.join(";")];
+
+    for item in (1..=itens_por_doc).rev() {
+        for doc in 0..num_docs {
+            let modelo = if doc % 2 == 0 { 55 } else { 57 };
+            let mut campos = vec![String::new(); CABECALHOS.len()];
+            campos[i_chave] = chave_valida(modelo, doc).to_string();
+            campos[i_item] = item.to_string();
+            // Valores com decimais (a soma em outra ordem alteraria os últimos dígitos)
+            // e repetidos a cada 5 itens (empates no valor máximo)
+            let r = (item * 7 + doc) % 5;
+            campos[i_valor] = format!("{r},{:02}", r * 11);
+            campos[i_descricao] = format!("PRODUTO {doc}-{item}");
+            campos[i_natureza] = format!("NATUREZA {doc}-{item}");
+            linhas.push(campos.join(";"));
+        }
+    }
+
+    fs::write(path, linhas.join("\n") + "\n").unwrap();
+}
+
+/// Cada CT-e (ímpar) transporta as NF-es vizinhas (pares) de `gerar_csv`.
+fn relacoes(num_docs: usize) -> Informacoes {
+    let mut info = Informacoes::default();
+    for doc in (1..num_docs).step_by(2) {
+        let cte = chave_valida(57, doc);
+        let nfes = info.cte_nfes.entry(cte).or_default();
+        nfes.insert(chave_valida(55, doc - 1));
+        nfes.insert(chave_valida(55, (doc + 1) % num_docs));
+    }
+    info.get_nfe_ctes();
+    info
+}
+
+/// Passagens 1 e 2 sobre `config.doc_path`: retorna o caminho do arquivo gerado.
+fn enriquecer(config: &Config, info: &Informacoes) -> PathBuf {
+    let (pair, indice) = get_summaries_e_indice(&config.doc_path, config, Some(info)).unwrap();
+    let (output, _) = enriquecer_arquivo(config, info, &pair.ctes, &pair.nfes, &indice).unwrap();
+    output
+}
+
+/// Valores das colunas acrescentadas após as colunas de `CABECALHOS`, por linha de dados.
+fn colunas_acrescentadas(path: &Path) -> Vec<Vec<String>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(b';')
+        .from_path(path)
+        .unwrap();
+    rdr.records()
+        .map(|record| {
+            let record = record.unwrap();
+            record
+                .iter()
+                .skip(CABECALHOS.len())
+                .map(String::from)
+                .collect()
+        })
+        .collect()
+}
+
+fn resumos_com_threads(
+    path: &Path,
+    threads: usize,
+) -> (HashMap<Chave, DocSummary>, HashMap<Chave, DocSummary>) {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .build()
+        .unwrap();
+    pool.install(|| get_summaries(path, &Config::default()).unwrap())
+}
+
+/// Representação textual determinística dos resumos (inclui o item vencedor).
+fn descrever(resumos: &HashMap<Chave, DocSummary>) -> String {
+    let ordenados: BTreeMap<&Chave, String> = resumos
+        .iter()
+        .map(|(chave, resumo)| {
+            let texto = format!(
+                "{} {} {} #{} L{} {}",
+                resumo.num_de_itens,
+                resumo.item_valor_total,
+                resumo.item_valor_maximo,
+                resumo.item_numero,
+                resumo.item_posicao,
+                serde_json::to_string(&resumo.metadata).unwrap()
+            );
+            (chave, texto)
+        })
+        .collect();
+    format!("{ordenados:?}")
+}
+
+#[test]
+fn test_resumos_identicos_com_1_e_n_threads() {
+    let path = std::env::temp_dir().join("resumos_tests_paridade.csv");
+    gerar_csv(&path, 500, 80); // 40.000 linhas: mais de um bloco
+
+    let (ctes_1, nfes_1) = resumos_com_threads(&path, 1);
+    let (ctes_n, nfes_n) = resumos_com_threads(&path, 8);
+
+    assert_eq!(ctes_1.len(), 250);
+    assert_eq!(nfes_1.len(), 250);
+    assert_eq!(descrever(&ctes_1), descrever(&ctes_n));
+    assert_eq!(descrever(&nfes_1), descrever(&nfes_n));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_desempate_pelo_numero_do_item() {
+    let path = std::env::temp_dir().join("resumos_tests_desempate.csv");
+    // Itens gravados em ordem decrescente (80, 79, ..., 1): o primeiro item
+    // de valor máximo encontrado não é o de menor número.
+    gerar_csv(&path, 2, 80);
+
+    let (_, nfes) = resumos_com_threads(&path, 4);
+    let resumo = &nfes[&chave_valida(55, 0)];
+
+    // Entre os itens de valor máximo, prevalece o de menor número
+    let max_itens: Vec<usize> = (1..=80)
+        .filter(|item| {
+            let r = (item * 7) % 5;
+            Decimal::new((r * 111) as i64, 2) == resumo.item_valor_maximo
+        })
+        .collect();
+
+    assert!(max_itens.len() > 1, "o teste requer itens empatados");
+    assert_eq!(resumo.item_numero, max_itens[0] as u64);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_csv_enriquecido_identico_com_1_e_n_threads() {
+    let path = std::env::temp_dir().join("resumos_tests_enriquecido.csv");
+    gerar_csv(&path, 200, 20);
+
+    let info = relacoes(200);
+
+    let gerar = |threads: usize, chunk_size: usize| -> (Vec<u8>, Relatorio) {
+        let config = Config {
+            doc_path: path.clone(),
+            max_char: 3000,
+            max_info: 10,
+            chunk_size,
+            ..Default::default()
+        };
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap();
+        pool.install(|| {
+            let (pair, indice) = get_summaries_e_indice(&path, &config, Some(&info)).unwrap();
+            let (output, relatorio) =
+                enriquecer_arquivo(&config, &info, &pair.ctes, &pair.nfes, &indice).unwrap();
+            (fs::read(output).unwrap(), relatorio)
+        })
+    };
+
+    let (csv_1, relatorio_1) = gerar(1, 0);
+
+    assert_eq!(relatorio_1.linhas, 200 * 20 + 1);
+    assert_eq!(relatorio_1.alteracoes(), 200 * 20);
+
+    // Threads e tamanhos de bloco diferentes (inclusive blocos incompletos)
+    for (threads, chunk_size) in [(8, 0), (8, 7), (3, 1000), (2, 4000)] {
+        let (csv_n, relatorio_n) = gerar(threads, chunk_size);
+        assert_eq!(relatorio_1.linhas, relatorio_n.linhas);
+        assert_eq!(relatorio_1.alteracoes(), relatorio_n.alteracoes());
+        assert!(
+            csv_1 == csv_n,
+            "CSV enriquecido difere: {threads} threads, blocos de {chunk_size} linhas"
+        );
+    }
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(path.with_extension("modificado.csv")).unwrap();
+}
+
+#[test]
+fn test_documento_em_mais_de_um_arquivo_de_referencia() {
+    let dir = std::env::temp_dir();
+    let (a, b) = (
+        dir.join("resumos_tests_referencia_a.csv"),
+        dir.join("resumos_tests_referencia_b.csv"),
+    );
+    // Os mesmos documentos, com 3 itens em A e 5 itens em B
+    gerar_csv(&a, 4, 3);
+    gerar_csv(&b, 4, 5);
+
+    let config = Config::default();
+    let (_, nfes_a) = get_summaries(&a, &config).unwrap();
+
+    // O documento não é somado: prevalece o primeiro arquivo informado
+    let referencia = get_summaries_de_referencia(&[a.clone(), b.clone()], &config).unwrap();
+    assert_eq!(referencia.nfes.len(), 2);
+    assert_eq!(descrever(&referencia.nfes), descrever(&nfes_a));
+    assert_eq!(referencia.nfes[&chave_valida(55, 0)].num_de_itens, 3);
+
+    // O mesmo arquivo informado duas vezes
+    let duplicado = get_summaries_de_referencia(&[a.clone(), a.clone()], &config).unwrap();
+    assert_eq!(descrever(&duplicado.nfes), descrever(&nfes_a));
+
+    fs::remove_file(&a).unwrap();
+    fs::remove_file(&b).unwrap();
+}
+
+#[test]
+fn test_colunas_acrescentadas_em_linhas_ja_enriquecidas() {
+    let path = std::env::temp_dir().join("resumos_tests_ja_enriquecidas.csv");
+    gerar_csv(&path, 20, 3);
+    let info = relacoes(20);
+
+    let config = |doc_path: &Path| Config {
+        doc_path: doc_path.to_path_buf(),
+        max_char: 3000,
+        max_info: 10,
+        modo_saida: ModoDeSaida::Colunas,
+        ..Default::default()
+    };
+
+    // Execução anterior: todas as linhas de NF-e e CT-e anotadas
+    let anotado = path.with_extension("anotado.csv");
+    let anotacoes = Config {
+        modo_saida: ModoDeSaida::Anotacoes,
+        ..config(&path)
+    };
+    fs::rename(enriquecer(&anotacoes, &info), &anotado).unwrap();
+
+    // As colunas dedicadas são as mesmas com ou sem anotações prévias
+    let original = enriquecer(&config(&path), &info);
+    let reprocessado = enriquecer(&config(&anotado), &info);
+    let esperadas = colunas_acrescentadas(&original);
+    assert!(
+        esperadas
+            .iter()
+            .all(|valores| valores.iter().any(|v| !v.is_empty()))
+    );
+    assert_eq!(colunas_acrescentadas(&reprocessado), esperadas);
+
+    for arquivo in [path, anotado, original, reprocessado] {
+        fs::remove_file(arquivo).unwrap();
+    }
+}
+
+#[test]
+fn test_frete_rateado_em_linhas_ja_enriquecidas() {
+    let path = std::env::temp_dir().join("resumos_tests_frete_ja_enriquecidas.csv");
+    gerar_csv(&path, 20, 3);
+    let info = relacoes(20);
+
+    let config = |doc_path: &Path, rateio_frete: bool| Config {
+        doc_path: doc_path.to_path_buf(),
+        max_char: 3000,
+        max_info: 10,
+        rateio_frete,
+        ..Default::default()
+    };
+
+    // Execução anterior (apenas anotações)
+    let anotado = path.with_extension("anotado.csv");
+    fs::rename(enriquecer(&config(&path, false), &info), &anotado).unwrap();
+
+    let reprocessado = enriquecer(&config(&anotado, true), &info);
+    let fretes: Vec<String> = colunas_acrescentadas(&reprocessado)
+        .into_iter()
+        .map(|mut valores| valores.pop().unwrap())
+        .collect();
+
+    // Todos os itens de NF-e (de valor não nulo) recebem o frete...
+    let (ctes, nfes) = get_summaries(&path, &Config::default()).unwrap();
+    let itens: usize = nfes.values().map(|resumo| resumo.num_de_itens).sum();
+    let com_frete = fretes.iter().filter(|frete| !frete.is_empty()).count();
+    assert_eq!(com_frete, itens);
+
+    // ...e a soma das parcelas é igual ao valor total dos CT-es
+    let soma: Decimal = fretes
+        .iter()
+        .filter(|frete| !frete.is_empty())
+        .map(|frete| frete.parse::<Decimal>().unwrap())
+        .sum();
+    let total: Decimal = ctes.values().map(|resumo| resumo.item_valor_total).sum();
+    assert_eq!(soma, total);
+
+    for arquivo in [path, anotado, reprocessado] {
+        fs::remove_file(arquivo).unwrap();
+    }
+}
+
+#[test]
+fn test_limpar_restaura_o_arquivo_original() {
+    let path = std::env::temp_dir().join("resumos_tests_limpar.csv");
+    let (i_chave, i_item, i_valor, i_natureza, i_ncm, i_chave_de_acesso) = (
+        indice("Chave da Nota Fiscal"),
+        indice("Número do Item"),
+        indice("Valor da Nota Proporcional"),
+        indice("Descrição da Natureza"),
+        indice("Código NCM"),
+        indice("Inf. NFe - Chave de acesso"),
+    );
+
+    let mut linhas = vec![CABECALHOS.join(";")];
+    for doc in 0..6 {
+        for item in 1..=2 {
+            let modelo = if doc % 2 == 0 { 55 } else { 57 };
+            let mut campos = vec![String::new(); CABECALHOS.len()];
+            campos[i_chave] = chave_valida(modelo, doc).to_string();
+            campos[i_item] = item.to_string();
+            campos[i_valor] = format!("{item}0,50");
+            // Texto original com colchetes e uma anotação "falsa"
+            campos[i_natureza] = format!("VENDA [lote {doc}] [Info do CT-e: ?]");
+            if modelo == 55 {
+                campos[i_ncm] = "84713012".to_string();
+            } else {
+                // NCM sobrescrito e "Chave de Acesso" reescrita no CT-e
+                campos[i_ncm] = "99".to_string();
+                campos[i_chave_de_acesso] = chave_valida(55, doc - 1).to_string();
+            }
+            linhas.push(campos.join(";"));
+        }
+    }
+    let conteudo = linhas.join("\n") + "\n";
+    fs::write(&path, &conteudo).unwrap();
+
+    let config = Config {
+        doc_path: path.clone(),
+        max_char: 3000,
+        max_info: 10,
+        ..Default::default()
+    };
+    let anotado = path.with_extension("anotado.csv");
+    fs::rename(enriquecer(&config, &relacoes(6)), &anotado).unwrap();
+    let enriquecido = fs::read_to_string(&anotado).unwrap();
+    // Linhas de CT-e: "Chave de Acesso" reescrita e NCM da NF-e
+    let ctes: Vec<&str> = enriquecido
+        .lines()
+        .filter(|linha| linha.contains(";CTe: "))
+        .collect();
+    assert_eq!(ctes.len(), 6);
+    assert!(ctes.iter().all(|linha| linha.contains(";84713012;")));
+
+    let limpeza = Config {
+        doc_path: anotado.clone(),
+        limpar: true,
+        ..Default::default()
+    };
+    // Valores originais gravados ao lado da saída, não na própria planilha
+    assert!(!enriquecido.contains("[Valores originais"));
+    let originais = caminho_dos_originais(&anotado);
+    fs::rename(
+        caminho_dos_originais(&path.with_extension("modificado.csv")),
+        &originais,
+    )
+    .unwrap();
+    assert_eq!(fs::read_to_string(&originais).unwrap().lines().count(), 12);
+
+    let (limpo, relatorio) = limpar_arquivo(&limpeza).unwrap();
+    assert_eq!(relatorio.linhas_restauradas, 12);
+    assert_eq!(fs::read_to_string(&limpo).unwrap(), conteudo);
+
+    for arquivo in [path, anotado, originais, limpo] {
+        fs::remove_file(arquivo).unwrap();
+    }
+}
+
+#[test]
+fn test_dry_run_nao_grava_arquivos() {
+    let path = std::env::temp_dir().join("resumos_tests_dry_run.csv");
+    gerar_csv(&path, 6, 2);
+    let saida = path.with_extension("modificado.csv");
+    let _ = fs::remove_file(&saida);
+
+    let (nfe, cte) = (|i| chave_valida(55, i), |i| chave_valida(57, i));
+    // NF-e 90 e CT-e 91 não estão no arquivo (sem resumo); CT-e 5 sem relações
+    let mut info = Informacoes::default();
+    info.cte_nfes
+        .insert(cte(1), [nfe(0), nfe(2), nfe(90)].into_iter().collect());
+    info.cte_nfes.insert(cte(3), [nfe(2)].into_iter().collect());
+    info.cte_nfes
+        .insert(cte(91), [nfe(4)].into_iter().collect());
+    info.get_nfe_ctes();
+
+    // Com duas NF-es (CT-e 1) ou dois CT-es (NF-e 2), a segunda anotação excede max_char
+    let config = Config {
+        doc_path: path.clone(),
+        dry_run: true,
+        max_char: 60,
+        max_info: 10,
+        top: 3,
+        ..Default::default()
+    };
+    let (pair, indice) = get_summaries_e_indice(&path, &config, Some(&info)).unwrap();
+    let (_, relatorio) =
+        enriquecer_arquivo(&config, &info, &pair.ctes, &pair.nfes, &indice).unwrap();
+
+    assert!(!saida.exists());
+    assert!(!caminho_dos_originais(&saida).exists());
+
+    // NF-e 4 apenas com o CT-e 91 (sem resumo) não é enriquecida
+    assert_eq!(relatorio.nfes_enriquecidas, 4);
+    assert_eq!(relatorio.ctes_enriquecidos, 4);
+    assert_eq!(relatorio.linhas_truncadas, 4);
+    assert_eq!(relatorio.ctes_sem_resumo, 1);
+    assert_eq!(relatorio.nfes_sem_resumo, 1);
+
+    // Ordem: relacionados (desc), chave (asc)
+    assert_eq!(
+        relatorio.top_chaves,
+        [(cte(1), 3), (nfe(2), 2), (nfe(0), 1)]
+    );
+
+    fs::remove_file(&path).unwrap();
+}
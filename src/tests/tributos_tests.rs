@@ -0,0 +1,146 @@
+use super::*;
+use crate::Relatorio;
+use crate::comum::{dec, mock_chave};
+
+fn tributos(pis: &str, cofins: &str, bc_icms: &str, icms: &str) -> TributosDoCte {
+    TributosDoCte {
+        valor_pis: dec(pis),
+        valor_cofins: dec(cofins),
+        valor_bc_icms: dec(bc_icms),
+        valor_icms: dec(icms),
+    }
+}
+
+#[test]
+fn test_ler_tributos() {
+    let row = Colunas {
+        valor_pis: "16,50".into(),
+        valor_cofins: "76,00".into(),
+        valor_bc_icms: "1.000,00".into(),
+        valor_icms: "abc".into(),
+        ..Default::default()
+    };
+
+    let mut qualidade = QualidadeNumerica::default();
+    let lidos = TributosDoCte::ler(&row, FormatoNumerico::Br, &mut qualidade);
+
+    // Valores inválidos contam como zero e constam do relatório de qualidade
+    assert_eq!(lidos, tributos("16.50", "76.00", "1000", "0"));
+    assert_eq!(qualidade.colunas[COLUNA_ICMS].invalidos, 1);
+    assert_eq!(qualidade.colunas[COLUNA_PIS].validos, 1);
+
+    assert!(TributosDoCte::default().is_zero());
+    assert!(!lidos.is_zero());
+}
+
+#[test]
+fn test_valores_das_colunas() {
+    let t = tributos("1.65", "7.6", "1000", "120");
+    assert_eq!(t.aliquota_efetiva_icms(), Some(dec("12.00")));
+    assert_eq!(
+        t.valores(),
+        ["1.65", "7.60", "1000.00", "120.00", "12.00"].map(String::from)
+    );
+
+    // Valores negativos mantêm o sinal
+    let t = tributos("-1.65", "-7.6", "-1000", "-120");
+    assert_eq!(
+        t.valores(),
+        ["-1.65", "-7.60", "-1000.00", "-120.00", "12.00"].map(String::from)
+    );
+
+    // Sem base de cálculo: alíquota vazia
+    let t = tributos("1", "2", "0", "0");
+    assert_eq!(t.aliquota_efetiva_icms(), None);
+    assert_eq!(t.valores()[4], "");
+    assert_eq!(t.valores().len(), COLUNAS_DE_TRIBUTOS.len());
+}
+
+#[test]
+fn test_tributos_por_nfe() {
+    let nfe1 = mock_chave("1111111111111111111155");
+    let nfe2 = mock_chave("2222222222222222222255");
+    let cte1 = mock_chave("3333333333333333333357");
+    let cte2 = mock_chave("4444444444444444444457");
+    let cte_sem_tributos = mock_chave("5555555555555555555557");
+
+    // CT-e 1 transporta as duas NF-es; CT-e 2 apenas a NF-e 1
+    let mut info = Informacoes::default();
+    info.cte_nfes.entry(cte1).or_default().extend([nfe1, nfe2]);
+    info.cte_nfes.entry(cte2).or_default().insert(nfe1);
+    info.cte_nfes
+        .entry(cte_sem_tributos)
+        .or_default()
+        .insert(nfe2);
+    info.get_nfe_ctes();
+
+    let resumo = |tributos: Option<TributosDoCte>| DocSummary {
+        num_de_itens: 1,
+        item_valor_total: Decimal::ONE_HUNDRED,
+        tributos,
+        ..Default::default()
+    };
+
+    let cte_info = HashMap::from([
+        (cte1, resumo(Some(tributos("1", "2", "100", "12")))),
+        (cte2, resumo(Some(tributos("0.5", "1", "50", "6")))),
+        (cte_sem_tributos, resumo(None)),
+    ]);
+    let nfe_info = HashMap::from([(nfe1, resumo(None)), (nfe2, resumo(None))]);
+
+    // CT-e 1 rateado entre as duas NF-es (de mesmo valor); CT-e 2 apenas na NF-e 1
+    let parcelas = tributos_por_nfe(&info, &cte_info, &nfe_info);
+    assert_eq!(parcelas[&nfe1], tributos("1", "2", "100", "12"));
+    assert_eq!(parcelas[&nfe2], tributos("0.5", "1", "50", "6"));
+    assert!(!parcelas.contains_key(&cte1));
+
+    // Relatório: a soma das parcelas conta cada CT-e uma única vez
+    let mut relatorio = Relatorio::default();
+    relatorio.analisar_relacoes(&info, &cte_info, &nfe_info, 0);
+    assert_eq!(relatorio.nfes_com_tributos, 2);
+    assert_eq!(relatorio.tributos_ctes, tributos("1.5", "3", "150", "18"));
+}
+
+#[test]
+fn test_rateio_de_tributos() {
+    let nfe1 = mock_chave("1111111111111111111155");
+    let nfe2 = mock_chave("2222222222222222222255");
+    let cte1 = mock_chave("3333333333333333333357");
+    let cte2 = mock_chave("4444444444444444444457");
+
+    // CT-e 1 transporta as duas NF-es; CT-e 2 apenas a NF-e 1
+    let mut info = Informacoes::default();
+    info.cte_nfes.entry(cte1).or_default().extend([nfe1, nfe2]);
+    info.cte_nfes.entry(cte2).or_default().insert(nfe1);
+    info.get_nfe_ctes();
+
+    let resumo = |tributos: Option<TributosDoCte>, itens: Vec<(usize, Decimal)>| DocSummary {
+        num_de_itens: itens.len().max(1),
+        item_valor_total: Decimal::ONE_HUNDRED,
+        tributos,
+        itens,
+        ..Default::default()
+    };
+
+    let cte_info = HashMap::from([
+        (cte1, resumo(Some(tributos("1", "2", "100", "12")), vec![])),
+        (cte2, resumo(Some(tributos("0.5", "1", "50", "6")), vec![])),
+    ]);
+    let nfe_info = HashMap::from([
+        (nfe1, resumo(None, vec![(0, dec("75")), (100, dec("25"))])),
+        // NF-e de arquivo de referência: sem itens
+        (nfe2, resumo(None, vec![])),
+    ]);
+
+    let rateio = RateioDeTributos::new(&info, &cte_info, &nfe_info);
+
+    // NF-e 1: metade do CT-e 1 e todo o CT-e 2, rateados entre os itens (75% e 25%)
+    assert_eq!(rateio.valor(0), Some(tributos("0.75", "1.50", "75", "9")));
+    assert_eq!(rateio.valor(100), Some(tributos("0.25", "0.50", "25", "3")));
+    assert_eq!(rateio.valor(50), None);
+
+    // Cada item recebe apenas a sua parcela: a soma é a parte da NF-e 1
+    let mut soma = rateio.valor(0).unwrap();
+    soma += rateio.valor(100).unwrap();
+    assert_eq!(soma, tributos("1", "2", "100", "12"));
+}
@@ -1,4 +1,5 @@
 use super::*;
+use crate::comum::dec;
 
 // Helper para criar uma struct Colunas mínima para testes
 fn mock_colunas_com_valor(valor: &str) -> Colunas<'static> {
@@ -14,22 +15,22 @@ fn test_formatos_comuns() {
     // Padrão Brasileiro com ponto de milhar
     assert_eq!(
         mock_colunas_com_valor("1.234,56").get_valor_do_item(),
-        Some(1234.56)
+        Some(dec("1234.56"))
     );
     // Padrão Brasileiro sem milhar
     assert_eq!(
         mock_colunas_com_valor("1234,56").get_valor_do_item(),
-        Some(1234.56)
+        Some(dec("1234.56"))
     );
     // Padrão Internacional
     assert_eq!(
         mock_colunas_com_valor("1234.56").get_valor_do_item(),
-        Some(1234.56)
+        Some(dec("1234.56"))
     );
     // Inteiro
     assert_eq!(
         mock_colunas_com_valor("1000").get_valor_do_item(),
-        Some(1000.0)
+        Some(dec("1000.0"))
     );
 }
 
@@ -37,15 +38,15 @@ fn test_formatos_comuns() {
 fn test_valores_pequenos_e_negativos() {
     assert_eq!(
         mock_colunas_com_valor("0,05").get_valor_do_item(),
-        Some(0.05)
+        Some(dec("0.05"))
     );
     assert_eq!(
         mock_colunas_com_valor("-10,50").get_valor_do_item(),
-        Some(-10.5)
+        Some(dec("-10.5"))
     );
     assert_eq!(
         mock_colunas_com_valor("-1.500,00").get_valor_do_item(),
-        Some(-1500.0)
+        Some(dec("-1500.0"))
     );
 }
 
@@ -54,12 +55,12 @@ fn test_limpeza_de_ruido() {
     // Espaços e símbolos de moeda
     assert_eq!(
         mock_colunas_com_valor(" R$ 1.234,56 ").get_valor_do_item(),
-        Some(1234.56)
+        Some(dec("1234.56"))
     );
     // Texto misturado (comum em campos mal preenchidos)
     assert_eq!(
         mock_colunas_com_valor("valor: 100,00").get_valor_do_item(),
-        Some(100.0)
+        Some(dec("100.0"))
     );
 }
 
@@ -71,8 +72,8 @@ fn test_casos_vazios_e_invalidos() {
 }
 
 #[test]
-fn test_limite_do_buffer_64_bytes() {
-    // Caso Extremo: Valor dentro do limite (exatamente 64 chars de dígitos)
+fn test_valores_longos() {
+    // Sem limite de tamanho do texto: 64 zeros são um valor válido
     let longo_valido = "0".repeat(64);
     assert!(
         mock_colunas_com_valor(&longo_valido)
@@ -80,10 +81,12 @@ fn test_limite_do_buffer_64_bytes() {
             .is_some()
     );
 
-    // Caso Extremo: Estouro do buffer (65 caracteres)
-    // Deve imprimir a mensagem de erro no stderr e retornar None
+    // Valor além da precisão de Decimal (28 dígitos): inválido
     let estouro = "1".repeat(65);
-    assert_eq!(mock_colunas_com_valor(&estouro).get_valor_do_item(), None);
+    assert_eq!(
+        mock_colunas_com_valor(&estouro).valor_do_item(FormatoNumerico::Auto),
+        Numero::Invalido
+    );
 }
 
 #[test]
@@ -91,25 +94,39 @@ fn test_multiplos_pontos_milhar() {
     // 1 milhão com pontos de milhar
     assert_eq!(
         mock_colunas_com_valor("1.000.000,00").get_valor_do_item(),
-        Some(1000000.0)
+        Some(dec("1000000.0"))
     );
 }
 
 #[test]
 fn test_notacao_cientifica() {
-    // Embora raro no SPED, o parse do f64 do Rust suporta
+    // Embora rara no SPED, a notação científica é aceita (`Decimal::from_scientific`)
     assert_eq!(
         mock_colunas_com_valor("1.23e4").get_valor_do_item(),
-        Some(12300.0)
+        Some(dec("12300.0"))
     );
 
     assert_eq!(
         mock_colunas_com_valor("4.3e10").get_valor_do_item(),
-        Some(43000000000.0)
+        Some(dec("43000000000.0"))
     );
 
     assert_eq!(
         mock_colunas_com_valor("-3.6e2").get_valor_do_item(),
-        Some(-360.0)
+        Some(dec("-360.0"))
     );
 }
+
+#[test]
+fn test_precisao_decimal() {
+    // Todas as casas decimais da origem são preservadas
+    let valor = mock_colunas_com_valor("1.234,5678").get_valor_do_item();
+    assert_eq!(valor, Some(dec("1234.5678")));
+    assert_eq!(valor.unwrap().scale(), 4);
+
+    // Soma exata: em f64, 0.1 somado 10 vezes resulta em 0.9999999999999999
+    let soma: Decimal = (0..10)
+        .filter_map(|_| mock_colunas_com_valor("0,1").get_valor_do_item())
+        .sum();
+    assert_eq!(soma, dec("1"));
+}
@@ -0,0 +1,155 @@
+use rust_decimal::{Decimal, RoundingStrategy};
+use serde::Serialize;
+use std::{collections::HashMap, ops::AddAssign};
+
+use crate::{
+    Chave, Colunas, DocSummary, FormatoNumerico, Informacoes, QualidadeNumerica, Rateavel,
+    RateioPorItem, decimal_com_sinal_to_str, interpretar_numero, ratear, ratear_por_nfe,
+};
+
+const COLUNA_PIS: &str = "PIS: Valor do Tributo : NF Item (Todos) SOMA";
+const COLUNA_COFINS: &str = "COFINS: Valor do Tributo : NF Item (Todos) SOMA";
+const COLUNA_BC_ICMS: &str = "ICMS: Base de Cálculo : NF Item (Todos) SOMA";
+const COLUNA_ICMS: &str = "ICMS: Valor do Tributo : NF Item (Todos) SOMA";
+
+/// Colunas dos tributos dos CT-es acrescentadas às linhas de NF-e (`--tributos-cte`):
+/// a parcela de cada item (ver `RateioDeTributos`) e a alíquota efetiva dessa parcela.
+pub const COLUNAS_DE_TRIBUTOS: [&str; 5] = [
+    "PIS rateado (CT-es)",
+    "COFINS rateado (CT-es)",
+    "Base de cálculo ICMS rateada (CT-es)",
+    "ICMS rateado (CT-es)",
+    "Alíquota efetiva ICMS (CT-es)",
+];
+
+/// Tributos destacados nos itens de um CT-e: valores de PIS, COFINS e ICMS
+/// e base de cálculo do ICMS, somados entre os itens do resumo.
+///
+/// Na exportação JSON, os valores são textos decimais exatos (ex: `"16.50"`).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct TributosDoCte {
+    #[serde(with = "rust_decimal::serde::str")]
+    pub valor_pis: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub valor_cofins: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub valor_bc_icms: Decimal,
+    #[serde(with = "rust_decimal::serde::str")]
+    pub valor_icms: Decimal,
+}
+
+impl AddAssign for TributosDoCte {
+    fn add_assign(&mut self, other: Self) {
+        self.valor_pis += other.valor_pis;
+        self.valor_cofins += other.valor_cofins;
+        self.valor_bc_icms += other.valor_bc_icms;
+        self.valor_icms += other.valor_icms;
+    }
+}
+
+impl TributosDoCte {
+    /// Interpreta os tributos da linha no `formato` informado.
+    ///
+    /// Valores vazios ou inválidos contam como zero; os inválidos e ambíguos
+    /// são contabilizados em `qualidade`.
+    pub fn ler(row: &Colunas, formato: FormatoNumerico, qualidade: &mut QualidadeNumerica) -> Self {
+        let mut ler = |coluna: &'static str, texto: &str| {
+            let numero = interpretar_numero(texto, formato);
+            qualidade.registrar(coluna, texto, &numero);
+            numero.valor().unwrap_or_default()
+        };
+
+        TributosDoCte {
+            valor_pis: ler(COLUNA_PIS, &row.valor_pis),
+            valor_cofins: ler(COLUNA_COFINS, &row.valor_cofins),
+            valor_bc_icms: ler(COLUNA_BC_ICMS, &row.valor_bc_icms),
+            valor_icms: ler(COLUNA_ICMS, &row.valor_icms),
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        *self == TributosDoCte::default()
+    }
+
+    /// Alíquota efetiva do ICMS (%): valor do ICMS / base de cálculo.
+    ///
+    /// `None` se a base de cálculo for nula.
+    pub fn aliquota_efetiva_icms(&self) -> Option<Decimal> {
+        if self.valor_bc_icms.is_zero() {
+            return None;
+        }
+        let aliquota = self.valor_icms * Decimal::ONE_HUNDRED / self.valor_bc_icms;
+        Some(aliquota.round_dp_with_strategy(2, RoundingStrategy::MidpointAwayFromZero))
+    }
+
+    /// Valores das colunas `COLUNAS_DE_TRIBUTOS` (com sinal: `ler` preserva os negativos).
+    pub fn valores(&self) -> Vec<String> {
+        vec![
+            decimal_com_sinal_to_str(self.valor_pis),
+            decimal_com_sinal_to_str(self.valor_cofins),
+            decimal_com_sinal_to_str(self.valor_bc_icms),
+            decimal_com_sinal_to_str(self.valor_icms),
+            self.aliquota_efetiva_icms()
+                .map(decimal_com_sinal_to_str)
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+impl Rateavel for TributosDoCte {
+    /// Rateia cada tributo separadamente (soma exata de cada um deles).
+    fn ratear(&self, pesos: &[Decimal]) -> Vec<Self> {
+        let pis = ratear(self.valor_pis, pesos);
+        let cofins = ratear(self.valor_cofins, pesos);
+        let bc_icms = ratear(self.valor_bc_icms, pesos);
+        let icms = ratear(self.valor_icms, pesos);
+
+        (0..pesos.len())
+            .map(|i| TributosDoCte {
+                valor_pis: pis[i],
+                valor_cofins: cofins[i],
+                valor_bc_icms: bc_icms[i],
+                valor_icms: icms[i],
+            })
+            .collect()
+    }
+}
+
+/// Tributos dos CT-es rateados entre os itens das NF-es (`--tributos-cte`),
+/// como o frete (ver `RateioPorItem`): cada linha de item recebe a sua parcela,
+/// e não a soma dos tributos dos CT-es repetida em todos os itens.
+pub type RateioDeTributos = RateioPorItem<TributosDoCte>;
+
+impl RateioDeTributos {
+    pub fn new(
+        info: &Informacoes,
+        cte_info: &HashMap<Chave, DocSummary>,
+        nfe_info: &HashMap<Chave, DocSummary>,
+    ) -> Self {
+        Self::ratear(info, cte_info, nfe_info, |resumo| resumo.tributos)
+    }
+}
+
+/// Parcela de cada NF-e nos tributos dos CT-es relacionados: a etapa 1 de
+/// `RateioDeTributos`, antes do rateio entre os itens.
+///
+/// Mesmo significado nas colunas (`COLUNAS_DE_TRIBUTOS`), na exportação JSON e no
+/// relatório: um CT-e que transporta várias NF-es contribui com uma parte dos seus
+/// tributos para cada uma, e nunca é contado mais de uma vez.
+pub fn tributos_por_nfe(
+    info: &Informacoes,
+    cte_info: &HashMap<Chave, DocSummary>,
+    nfe_info: &HashMap<Chave, DocSummary>,
+) -> HashMap<Chave, TributosDoCte> {
+    ratear_por_nfe(info, cte_info, nfe_info, |resumo| resumo.tributos)
+}
+
+//----------------------------------------------------------------------------//
+//                                   Tests                                    //
+//----------------------------------------------------------------------------//
+
+/// Run tests with:
+/// cargo test -- --show-output tributos_tests
+#[cfg(test)]
+#[path = "tests/tributos_tests.rs"]
+mod tributos_tests;
@@ -1,14 +1,22 @@
+use csv::StringRecord;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fs::{self, File},
-    io::{self, BufReader, BufWriter, Write},
+    io::{self, BufWriter, Write},
     path::{Path, PathBuf},
     process::Command,
 };
 
 use crate::{
-    BUFFER, Chave, Colunas, Config, DocSummary, Informacoes, SpedError, SpedResult,
-    adicionar_info_de_ctes_em_nfe, adicionar_info_de_nfes_em_cte,
+    Alteracao, ArquivoMapeado, BUFFER, COLUNA_FRETE_RATEADO, COLUNA_INCONSISTENCIAS,
+    COLUNA_MODALIDADE, COLUNAS_DE_TRIBUTOS, Chave, Codificacao, Codificador, Colunas,
+    ColunasDedicadas, Config, Diagnostico, DocSummary, IndiceDeLinhas, Informacoes, MapaDeColunas,
+    Modelo, ModoDeSaida, RateioDeFrete, RateioDeTributos, Relatorio, SpedResult,
+    adicionar_info_de_ctes_em_mdfe, adicionar_info_de_ctes_em_nfe, adicionar_info_de_mdfes_em_cte,
+    adicionar_info_de_nfes_em_cte, coluna_modalidade, decimal_to_str, leitor, registros,
+    verificar_colunas_acrescentadas,
 };
 
 /// Tipo alias para representar o mapa de relações entre chaves de CTe.
@@ -91,7 +99,7 @@ pub fn sobrescrever_arquivo(original: &Path, alterado: &Path) -> SpedResult<()>
 
             if resposta == "s" || resposta == "y" {
                 println!("\n\tmv '{}' '{}'", alterado.display(), original.display());
-                fs::rename(alterado, original)?;
+                substituir_arquivo(alterado, original)?;
                 break;
             } else if resposta == "n" {
                 break;
@@ -102,98 +110,528 @@ pub fn sobrescrever_arquivo(original: &Path, alterado: &Path) -> SpedResult<()>
     Ok(())
 }
 
+/// Move o arquivo alterado para o original, junto com os seus valores originais
+/// (`caminho_dos_originais`). Os valores originais de uma execução anterior
+/// não correspondem mais ao arquivo e são removidos.
+pub fn substituir_arquivo(alterado: &Path, original: &Path) -> SpedResult<()> {
+    fs::rename(alterado, original)?;
+
+    let originais = caminho_dos_originais(original);
+    match fs::rename(caminho_dos_originais(alterado), &originais) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => match fs::remove_file(&originais) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        },
+        resultado => Ok(resultado?),
+    }
+}
+
+/// Cria o Writer CSV de saída (mesmo formato para o arquivo e para as linhas em memória).
+fn csv_writer<W: Write>(
+    destino: W,
+    capacidade: usize,
+    terminador: csv::Terminator,
+) -> csv::Writer<W> {
+    csv::WriterBuilder::new()
+        .delimiter(b';')
+        .has_headers(false)
+        .quote_style(csv::QuoteStyle::Necessary)
+        .double_quote(true)
+        .terminator(terminador)
+        .buffer_capacity(capacidade.max(1))
+        .from_writer(destino)
+}
+
+/// Cria o arquivo de saída na codificação de `--encoding-saida`
+/// ou, se omitida, na mesma codificação do arquivo de entrada.
+fn criar_saida(
+    output_path: &Path,
+    arquivo: &ArquivoMapeado,
+    config: &Config,
+) -> SpedResult<Codificador<BufWriter<File>>> {
+    let entrada = arquivo.codificacao();
+    let saida = config.codificacao_saida.unwrap_or(entrada);
+
+    if entrada != Codificacao::Utf8 || saida != entrada {
+        println!(" -> Codificação: entrada {entrada}, saída {saida}");
+    }
+
+    let destino = BufWriter::with_capacity(BUFFER, File::create(output_path)?);
+    Ok(Codificador::new(destino, saida)?)
+}
+
+/// Campos acrescentados ao final de uma linha: ";valor1;valor2;...",
+/// sem terminador de linha (ver `estender_registro`).
+fn campos_acrescentados(valores: &[String]) -> SpedResult<Vec<u8>> {
+    let mut wtr = csv_writer(Vec::new(), BUFFER / 16, csv::Terminator::Any(b'\n'));
+    wtr.write_record(valores)?;
+    let mut campos = wtr.into_inner().map_err(|e| e.into_error())?;
+    campos.pop(); // terminador
+    campos.insert(0, b';');
+    Ok(campos)
+}
+
+/// Grava o registro bruto com `campos` acrescentados antes do seu terminador de linha.
+///
+/// Linhas em branco são gravadas sem alteração.
+fn estender_registro<W: Write>(destino: &mut W, registro: &[u8], campos: &[u8]) -> io::Result<()> {
+    // Com terminador CRLF, a faixa de uma linha candidata pode terminar no '\r'
+    let conteudo = registro.strip_suffix(b"\n").unwrap_or(registro);
+    let conteudo = conteudo.strip_suffix(b"\r").unwrap_or(conteudo);
+
+    if conteudo.is_empty() {
+        return destino.write_all(registro);
+    }
+
+    destino.write_all(conteudo)?;
+    destino.write_all(campos)?;
+    destino.write_all(&registro[conteudo.len()..])
+}
+
+/// Copia um trecho de linhas inalteradas: diretamente ou, com colunas dedicadas,
+/// com as colunas acrescentadas vazias (`vazias`).
+fn copiar_trecho<W: Write>(
+    destino: &mut W,
+    trecho: &[u8],
+    vazias: Option<&[u8]>,
+) -> io::Result<()> {
+    match vazias {
+        None => destino.write_all(trecho),
+        Some(vazias) => registros(trecho)
+            .try_for_each(|faixa| estender_registro(destino, &trecho[faixa], vazias)),
+    }
+}
+
+/// Enriquece uma linha, conforme o modelo do documento, e retorna a alteração.
+fn enriquecer_linha(
+    row: &mut Colunas,
+    config: &Config,
+    info: &Informacoes,
+    cte_info: &HashMap<Chave, DocSummary>,
+    nfe_info: &HashMap<Chave, DocSummary>,
+) -> Alteracao {
+    let modelo = row.chave.modelo();
+
+    // Notas canceladas são apenas escritas de volta sem alteração.
+    if row.chave_cancelada() {
+        Alteracao::Nenhuma
+    } else if modelo.is_nota() {
+        // Se a função adicionar_info mexeu em algum Cow via to_mut(),
+        // ele agora é Cow::Owned.
+        adicionar_info_de_ctes_em_nfe(row, config, info, cte_info)
+    } else if modelo.is_conhecimento() {
+        // Ambas as funções são executadas; prevalece o resultado "maior"
+        adicionar_info_de_nfes_em_cte(row, config, info, nfe_info)
+            .max(adicionar_info_de_mdfes_em_cte(row, config, info, cte_info))
+    } else if modelo == Modelo::Mdfe {
+        adicionar_info_de_ctes_em_mdfe(row, config, info, cte_info)
+    } else {
+        Alteracao::Nenhuma
+    }
+}
+
+/// Indica se a linha possui documentos relacionados, ou seja,
+/// se pode ser alterada por `enriquecer_linha`.
+///
+/// Utilizada na Passagem 1 para registrar as linhas candidatas (`IndiceDeLinhas`):
+/// as demais linhas são copiadas sem alteração na Passagem 2.
+pub fn pode_ser_enriquecida(row: &Colunas, info: &Informacoes) -> bool {
+    let chave = &row.chave;
+    let modelo = chave.modelo();
+
+    if row.chave_cancelada() {
+        false
+    } else if modelo.is_nota() {
+        info.nfe_ctes.contains_key(chave)
+    } else if modelo.is_conhecimento() {
+        info.cte_nfes.contains_key(chave) || info.cte_mdfes.contains_key(chave)
+    } else if modelo == Modelo::Mdfe {
+        info.mdfe_ctes.contains_key(chave)
+    } else {
+        false
+    }
+}
+
 /// Processa o enriquecimento do arquivo CSV (Passagem 2).
 /// Utiliza a Abordagem 1: Deserialização direta para a struct Colunas.
+///
+/// O arquivo é mapeado em memória e apenas as linhas candidatas de `indice`
+/// (registradas na Passagem 1) são interpretadas, em blocos de
+/// `config.linhas_por_bloco()` linhas enriquecidas em paralelo (Rayon).
+/// As demais linhas, e as candidatas que não mudaram, são copiadas diretamente
+/// do arquivo mapeado, byte a byte, sem re-serialização.
+/// O arquivo gerado é idêntico para qualquer número de threads ou tamanho de bloco.
+///
+/// Com `--modo-saida colunas`, as colunas originais não são alteradas: todas as
+/// linhas recebem as colunas dedicadas (`ColunasDedicadas`), vazias nas linhas
+/// sem documentos relacionados.
+///
+/// No modo `dry_run`, as linhas são enriquecidas apenas em memória:
+/// nenhum arquivo é gravado e somente o `Relatorio` é produzido.
 pub fn enriquecer_arquivo(
     config: &Config,
-    info: &mut Informacoes,
+    info: &Informacoes,
     cte_info: &HashMap<Chave, DocSummary>,
     nfe_info: &HashMap<Chave, DocSummary>,
-) -> SpedResult<(PathBuf, usize)> {
-    println!("--- Passagem 2: Gravando arquivo enriquecido ---");
+    indice: &IndiceDeLinhas,
+) -> SpedResult<(PathBuf, Relatorio)> {
+    if config.dry_run {
+        println!("--- Passagem 2: Simulação (nenhum arquivo será gravado) ---");
+    } else {
+        println!("--- Passagem 2: Gravando arquivo enriquecido ---");
+    }
 
     let input_path = &config.doc_path;
     let output_path = input_path.with_extension("modificado.csv");
 
-    // 1. Configurar Reader com buffer otimizado
-    let file_in = File::open(input_path).map_err(|e| SpedError::IoReader {
-        source: e,
-        arquivo: input_path.clone(),
-    })?;
-
-    let mut rdr = csv::ReaderBuilder::new()
-        .delimiter(b';')
-        .has_headers(true)
-        .trim(csv::Trim::All)
-        .buffer_capacity(BUFFER)
-        .from_reader(BufReader::new(file_in));
+    // 1. Mapeamento do arquivo em memória (convertido para UTF-8, se necessário)
+    let arquivo = ArquivoMapeado::abrir(input_path, config.codificacao)?;
+    let bytes = arquivo.bytes();
+    let cabecalho = arquivo.cabecalho()?;
+    let mapa = MapaDeColunas::new(&cabecalho, config, input_path)?;
+    let terminador = arquivo.terminador();
+
+    // Colunas acrescentadas ao final de todas as linhas: colunas dedicadas
+    // (--modo-saida colunas), tributos dos CT-es (--tributos-cte), frete rateado (--rateio-frete)
+    // modalidade do frete (--modalidade-frete) e inconsistências (--coluna-consistencia)
+    let dedicadas = match config.modo_saida {
+        ModoDeSaida::Anotacoes => None,
+        ModoDeSaida::Colunas => Some(ColunasDedicadas::new(&config.perfil)?),
+    };
+    let rateio = config
+        .rateio_frete
+        .then(|| RateioDeFrete::new(info, cte_info, nfe_info));
+    let tributos = config
+        .tributos_cte
+        .then(|| RateioDeTributos::new(info, cte_info, nfe_info));
+    let diagnostico = config
+        .consistencia
+        .then(|| Diagnostico::new(info, cte_info, nfe_info));
+
+    if let Some(diagnostico) = &diagnostico {
+        diagnostico.imprimir();
+        if !config.dry_run {
+            let diagnostico_path = input_path.with_extension("consistencia.csv");
+            diagnostico.gravar(&diagnostico_path)?;
+            println!(
+                " -> Diagnóstico de consistência gravado em <{}>",
+                diagnostico_path.display()
+            );
+        }
+    }
 
-    // Inicializa contador (considerando header se existir)
-    info.numero_total_de_linhas = if rdr.has_headers() { 1 } else { 0 };
+    let mut acrescentadas: Vec<String> = dedicadas
+        .as_ref()
+        .map_or_else(Vec::new, |d| d.nomes.clone());
+    if let Some(tributos) = &tributos {
+        tributos.imprimir("Tributos dos CT-es rateados");
+        acrescentadas.extend(COLUNAS_DE_TRIBUTOS.map(String::from));
+    }
+    if let Some(rateio) = &rateio {
+        rateio.imprimir("Frete rateado");
+        acrescentadas.push(COLUNA_FRETE_RATEADO.to_string());
+    }
+    if config.modalidade_frete {
+        acrescentadas.push(COLUNA_MODALIDADE.to_string());
+    }
+    if config.coluna_consistencia {
+        acrescentadas.push(COLUNA_INCONSISTENCIAS.to_string());
+    }
+    verificar_colunas_acrescentadas(&acrescentadas, &cabecalho, input_path)?;
+
+    let vazias = if acrescentadas.is_empty() {
+        None
+    } else {
+        Some(campos_acrescentados(&vec![
+            String::new();
+            acrescentadas.len()
+        ])?)
+    };
+
+    // 2. Writer de saída (ausente no modo dry_run): o cabeçalho original é copiado
+    let mut wtr = if config.dry_run {
+        None
+    } else {
+        let mut wtr = criar_saida(&output_path, &arquivo, config)?;
+        if vazias.is_some() {
+            let nomes = campos_acrescentados(&acrescentadas)?;
+            estender_registro(&mut wtr, arquivo.cabecalho_bruto(), &nomes)?;
+        } else {
+            wtr.write_all(arquivo.cabecalho_bruto())?;
+        }
+        Some(wtr)
+    };
+
+    // Valores originais das linhas alteradas, para a restauração por --limpar.
+    // Um arquivo de uma execução anterior não deve restaurar a nova saída.
+    let originais_path = caminho_dos_originais(&output_path);
+    let mut originais = None;
+    if wtr.is_some() {
+        match fs::remove_file(&originais_path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
 
-    // 2. Configurar Writer com buffer otimizado
-    let file_out = File::create(&output_path)?;
-    let mut wtr = csv::WriterBuilder::new()
-        .delimiter(b';')
-        .has_headers(true)
-        .quote_style(csv::QuoteStyle::Necessary)
-        .double_quote(true)
-        .buffer_capacity(BUFFER)
-        .from_writer(BufWriter::new(file_out));
-
-    let mut alteracoes_realizadas = 0;
-
-    // Reutilizamos o buffer do StringRecord para evitar alocações a cada linha
-    let mut record = csv::StringRecord::new();
-
-    while rdr.read_record(&mut record)? {
-        info.numero_total_de_linhas += 1;
-
-        // Deserialização "Zero-Copy": os campos da struct Colunas aponta para dentro do 'record'
-        // Deserialização com captura detalhada de erro
-        let mut row: Colunas = record
-            .deserialize(None)
-            .map_err(|e| SpedError::CsvDetailed {
-                arquivo: input_path.to_path_buf(),
-                linha_numero: record.position().map(|p| p.line()).unwrap_or(0),
-                conteudo: record.iter().collect::<Vec<_>>().join(";"),
-                erro: e.to_string(),
-            })?;
-
-        let mut mudou = false;
-
-        // Notas canceladas são apenas escritas de volta sem alteração
-        if !row.chave_cancelada() {
-            let chave = row.chave;
-
-            if chave.is_nfe() {
-                // Se a função adicionar_info mexeu em algum Cow via to_mut(),
-                // ele agora é Cow::Owned.
-                mudou = adicionar_info_de_ctes_em_nfe(&mut row, config, info, cte_info);
-            } else if chave.is_cte() {
-                mudou = adicionar_info_de_nfes_em_cte(&mut row, config, info, nfe_info);
+    // Contadores da Passagem 1 (linhas de dados + cabeçalho)
+    let mut relatorio = Relatorio {
+        linhas: indice.linhas + 1,
+        ja_enriquecidas: indice.ja_enriquecidas,
+        ..Default::default()
+    };
+
+    // Fim do trecho do arquivo já gravado e número da sua última linha (1: cabeçalho)
+    let mut gravado_ate = arquivo.inicio_dos_dados();
+    let mut linha_gravada = 1;
+
+    for bloco in indice.candidatas.chunks(config.linhas_por_bloco()) {
+        // 3. Etapa paralela: cada linha candidata é enriquecida e, se alterada,
+        // serializada em memória. O collect preserva a ordem das linhas.
+        type Linha = (Modelo, Alteracao, Option<Vec<u8>>, BTreeMap<String, String>);
+        let linhas: Vec<Linha> = bloco
+            .par_iter()
+            .map_init(
+                || (StringRecord::new(), StringRecord::new()),
+                |(record, buf), faixa| -> SpedResult<_> {
+                    leitor(&bytes[faixa.clone()]).read_record(record)?;
+
+                    // Deserialização "Zero-Copy": os campos da struct Colunas apontam para dentro do 'record'
+                    // Deserialização com captura detalhada de erro
+                    let mut row: Colunas = mapa.deserializar(record, buf).map_err(|e| {
+                        arquivo.erro_detalhado(faixa.start, record.as_byte_record(), e)
+                    })?;
+
+                    // Linha de uma execução anterior: não é anotada novamente, mas as
+                    // colunas acrescentadas são calculadas sobre os valores originais
+                    let ja_enriquecida = row.ja_enriquecida();
+                    if ja_enriquecida {
+                        row.limpar_anotacoes();
+                    }
+
+                    // Modalidade do frete, antes que as anotações alterem a linha
+                    let modalidade = config
+                        .modalidade_frete
+                        .then(|| coluna_modalidade(&row, config, info, cte_info));
+
+                    // Colunas dedicadas: a linha original não é alterada
+                    let (alteracao, mut valores) = match &dedicadas {
+                        Some(d) => d.preencher(&row, config, info, cte_info, nfe_info),
+                        None if ja_enriquecida => (Alteracao::Nenhuma, Vec::new()),
+                        None => (
+                            enriquecer_linha(&mut row, config, info, cte_info, nfe_info),
+                            Vec::new(),
+                        ),
+                    };
+
+                    if let Some(tributos) = &tributos {
+                        match tributos.valor(faixa.start) {
+                            Some(tributos) => valores.extend(tributos.valores()),
+                            None => valores.extend(COLUNAS_DE_TRIBUTOS.map(|_| String::new())),
+                        }
+                    }
+
+                    if let Some(rateio) = &rateio {
+                        let frete = rateio.frete(faixa.start);
+                        valores.push(frete.map(decimal_to_str).unwrap_or_default());
+                    }
+
+                    valores.extend(modalidade);
+
+                    if config.coluna_consistencia
+                        && let Some(diagnostico) = &diagnostico
+                    {
+                        let inconsistencias = if row.chave_cancelada() {
+                            String::new()
+                        } else {
+                            diagnostico.coluna(&row.chave, &config.separador)
+                        };
+                        valores.push(inconsistencias);
+                    }
+
+                    // Serializa a struct modificada (na ordem original das colunas)
+                    let mut valores_originais = BTreeMap::new();
+                    let linha = if alteracao.mudou() && dedicadas.is_none() && !config.dry_run {
+                        let mut linha = csv_writer(Vec::new(), 2 * faixa.len(), terminador);
+                        mapa.gravar(&mut linha, record, &row)?;
+                        valores_originais = mapa.valores_originais(record, &row);
+                        Some(linha.into_inner().map_err(|e| e.into_error())?)
+                    } else {
+                        None
+                    };
+
+                    // Colunas acrescentadas: ao final da linha serializada ou da original
+                    let saida = if vazias.is_some() && !config.dry_run {
+                        let original = linha.as_deref().unwrap_or(&bytes[faixa.clone()]);
+                        let mut saida = Vec::with_capacity(2 * original.len());
+                        estender_registro(&mut saida, original, &campos_acrescentados(&valores)?)?;
+                        Some(saida)
+                    } else {
+                        linha
+                    };
+
+                    Ok((row.chave.modelo(), alteracao, saida, valores_originais))
+                },
+            )
+            .collect::<SpedResult<_>>()?;
+
+        // 4. Etapa sequencial: gravação na ordem original
+        for (faixa, (modelo, alteracao, saida, valores_originais)) in bloco.iter().zip(linhas) {
+            relatorio.registrar(modelo, alteracao);
+
+            if let Some(wtr) = wtr.as_mut() {
+                linha_gravada += registros(&bytes[gravado_ate..faixa.start]).count() + 1;
+                if !valores_originais.is_empty() {
+                    let destino = match &mut originais {
+                        Some(destino) => destino,
+                        None => originais.insert(BufWriter::new(File::create(&originais_path)?)),
+                    };
+                    let registro = OriginaisDaLinha {
+                        linha: linha_gravada,
+                        originais: valores_originais,
+                    };
+                    serde_json::to_writer(&mut *destino, &registro)?;
+                    destino.write_all(b"\n")?;
+                }
+
+                // Linhas não candidatas entre a linha anterior e esta: cópia direta
+                copiar_trecho(wtr, &bytes[gravado_ate..faixa.start], vazias.as_deref())?;
+                // Performance Máxima: linha inalterada escrita sem re-serializar
+                match saida {
+                    Some(saida) => wtr.write_all(&saida)?,
+                    None => copiar_trecho(wtr, &bytes[faixa.clone()], vazias.as_deref())?,
+                }
             }
+            gravado_ate = faixa.end;
         }
+    }
 
-        if mudou {
-            // Serializa a struct modificada
-            wtr.serialize(row)?;
-            alteracoes_realizadas += 1;
+    // Garante que tudo foi gravado no disco
+    if let Some(mut wtr) = wtr {
+        copiar_trecho(&mut wtr, &bytes[gravado_ate..], vazias.as_deref())?;
+        wtr.flush()?;
+    }
+    if let Some(mut originais) = originais {
+        originais.flush()?;
+        println!(
+            " -> Valores originais das linhas alteradas: {}",
+            originais_path.display()
+        );
+    }
+
+    relatorio.analisar_relacoes(info, cte_info, nfe_info, config.top);
+
+    println!(
+        " -> Total de linhas enriquecidas: {}",
+        fmt_milhares(relatorio.alteracoes())
+    );
+
+    Ok((output_path, relatorio))
+}
+
+/// Valores originais das colunas alteradas de uma linha do arquivo enriquecido
+/// (uma linha JSON de `caminho_dos_originais`), lidos por `limpar_arquivo`.
+#[derive(Debug, Serialize, Deserialize)]
+struct OriginaisDaLinha {
+    /// Número da linha no arquivo (1: cabeçalho).
+    linha: usize,
+    /// Valor lido de cada coluna alterada, por nome de coluna.
+    originais: BTreeMap<String, String>,
+}
+
+/// Arquivo com os valores originais das linhas alteradas de um arquivo
+/// enriquecido: `<saida>.originais.jsonl`, ao lado do próprio arquivo.
+pub fn caminho_dos_originais(output_path: &Path) -> PathBuf {
+    output_path.with_extension("originais.jsonl")
+}
+
+/// Remove as anotações de execuções anteriores (modo `--limpar`).
+///
+/// Grava `<doc>.modificado.csv` com as linhas enriquecidas restauradas e retorna
+/// o `Relatorio` com o número de linhas lidas e restauradas. As demais linhas
+/// são copiadas sem alteração.
+///
+/// Se houver o arquivo de valores originais (`caminho_dos_originais`), as colunas
+/// alteradas voltam exatamente ao valor lido antes do enriquecimento; caso
+/// contrário, apenas as anotações são removidas (`Colunas::limpar_anotacoes`).
+pub fn limpar_arquivo(config: &Config) -> SpedResult<(PathBuf, Relatorio)> {
+    println!("--- Removendo anotações de execuções anteriores ---");
+
+    let input_path = &config.doc_path;
+    let output_path = input_path.with_extension("modificado.csv");
+
+    let arquivo = ArquivoMapeado::abrir(input_path, config.codificacao)?;
+    let inicio = arquivo.inicio_dos_dados();
+    let dados = &arquivo.bytes()[inicio..];
+    let terminador = arquivo.terminador();
+    let originais = ler_originais(&caminho_dos_originais(input_path))?;
+
+    // O cabeçalho original é copiado sem alteração
+    let mut saida = criar_saida(&output_path, &arquivo, config)?;
+    saida.write_all(arquivo.cabecalho_bruto())?;
+
+    let mapa = MapaDeColunas::new(&arquivo.cabecalho()?, config, input_path)?;
+    mapa.relatar(input_path);
+
+    let mut relatorio = Relatorio {
+        linhas: 1,
+        ..Default::default()
+    };
+    let mut record = StringRecord::new();
+    let mut buf = StringRecord::new();
+
+    for (linha, faixa) in (2..).zip(registros(dados)) {
+        let registro = &dados[faixa.clone()];
+
+        // Linhas em branco são copiadas
+        if !leitor(registro).read_record(&mut record)? {
+            saida.write_all(registro)?;
+            continue;
+        }
+        relatorio.linhas += 1;
+
+        let mut row: Colunas = mapa.deserializar(&record, &mut buf).map_err(|e| {
+            arquivo.erro_detalhado(inicio + faixa.start, record.as_byte_record(), e)
+        })?;
+
+        let restaurada = match originais.get(&linha) {
+            Some(valores) if row.ja_enriquecida() => row.restaurar(valores),
+            _ => row.limpar_anotacoes(),
+        };
+
+        if restaurada {
+            let mut linha = csv_writer(Vec::new(), 2 * registro.len(), terminador);
+            mapa.gravar(&mut linha, &record, &row)?;
+            saida.write_all(&linha.into_inner().map_err(|e| e.into_error())?)?;
+            relatorio.linhas_restauradas += 1;
         } else {
-            // Performance Máxima: Escreve o buffer original sem re-serializar
-            // Se não mudou nada, escrevemos o buffer original diretamente.
-            // Isso evita converter String -> Struct -> String.
-            wtr.write_record(&record)?;
+            saida.write_all(registro)?;
         }
     }
 
-    // Garante que tudo foi gravado no disco
-    wtr.flush()?;
+    saida.flush()?;
 
     println!(
-        " -> Total de linhas enriquecidas: {}",
-        fmt_milhares(alteracoes_realizadas)
+        " -> Total de linhas restauradas: {}",
+        fmt_milhares(relatorio.linhas_restauradas)
     );
 
-    Ok((output_path, alteracoes_realizadas))
+    Ok((output_path, relatorio))
+}
+
+/// Valores originais por número de linha (vazio se o arquivo não existir).
+fn ler_originais(path: &Path) -> SpedResult<HashMap<usize, BTreeMap<String, String>>> {
+    let arquivo = match File::open(path) {
+        Ok(arquivo) => arquivo,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e.into()),
+    };
+    println!(" -> Valores originais: {}", path.display());
+
+    serde_json::Deserializer::from_reader(io::BufReader::new(arquivo))
+        .into_iter::<OriginaisDaLinha>()
+        .map(|registro| {
+            let registro = registro?;
+            Ok((registro.linha, registro.originais))
+        })
+        .collect()
 }